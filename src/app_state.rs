@@ -1,71 +1,600 @@
-use std::sync::Arc;
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Instant,
+};
+
+use anyhow::{Context, bail};
+use cgmath::Vector2;
 
 use crate::{
     camera_controller::CameraController,
-    graphics::{GraphicsState, textured_pipeline::TexturedQuad},
+    game::{Game, TICKS_PER_SECOND},
+    game_clock::GameClock,
+    graphics::{GraphicsState, atlas::Atlas, render_error::RenderError},
+    input::{
+        actions::{
+            self, PAUSE, QUICKLOAD, QUICKSAVE, QUIT, REBIND, RESET_CONTROLS, RESET_SPEED,
+            SAVE_SCENE, SLOW_DOWN, SPEED_UP, STEP, TOGGLE_TIME_FIELDS,
+        },
+        controls::{Controls, Rebind, RebindProgress},
+        event::InputEvent,
+        gamepad::{GamepadBackend, GamepadEvent, Gamepads, VirtualGamepads},
+        map::InputMap,
+        mouse::Mouse,
+        script::InputScript,
+    },
+    options::Options,
+    replay::{Replay, ReplayPlayer},
+    save::SaveSlots,
 };
 
-use cgmath::Vector2;
 use winit::window::Window;
 
-pub struct AppState {
+/// Where saving the scene writes the live world to, relative to the working directory
+const SAVED_SCENE_PATH: &str = "saved-scene.ron";
+
+/// Logical pixels the cursor has to move with a button held to start a drag
+const DRAG_THRESHOLD: f32 = 4.0;
+
+/// Save slot quicksaving saves to and quickloading loads from
+const QUICKSAVE_SLOT: u32 = 1;
+
+/// The window and everything drawn to it
+struct Display {
     window: Arc<Window>, // We need window to be an Arc so that the surface can hold a reference to it
     graphics_state: GraphicsState,
+    atlas: Atlas,
+}
+
+pub struct AppState {
+    /// `None` when running headlessly
+    display: Option<Display>,
     pub camera_controller: CameraController,
+    /// Actions for running and viewing the game, as opposed to playing it
+    pub input: InputMap,
+    /// The cursor in logical pixels from the window's top left
+    pub mouse: Mouse,
+    /// Last cursor position sent to the game, in world space
+    pointer: Option<Vector2<f32>>,
+    /// Applies dead zones to what `gamepad_backend` reports
+    gamepads: Gamepads,
+    gamepad_backend: Box<dyn GamepadBackend>,
+    /// Bindings of both the game's and the app's actions
+    controls: Controls,
+    /// Where `controls` are saved, if there is a config directory
+    controls_path: Option<PathBuf>,
+    /// Captures input instead of the actions while set
+    rebind: Option<Rebind>,
+    pub clock: GameClock,
+    pub game: Game,
+    /// Every input event so far, saved to the path on `finish_recording`
+    recording: Option<(Replay, PathBuf)>,
+    /// Drives the game instead of live input while set
+    replay: Option<ReplayPlayer>,
+    /// Sends its events alongside live input while set, with the frame it
+    /// started on
+    script: Option<(InputScript, u64)>,
+    /// Frames run so far
+    frames: u64,
 }
 
 impl AppState {
-    /// Function is async because some wgpu functions are async
-    pub async fn resumed(window: Arc<Window>) -> anyhow::Result<Self> {
-        let camera_controller = CameraController::new(0.01);
-        let graphics_state = GraphicsState::new(window.clone()).await?;
+    /// Function is async because some wgpu functions are async. When `replay`
+    /// is given it drives the game and its seed overrides the one in `options`.
+    pub async fn resumed(
+        window: Arc<Window>,
+        options: &Options,
+        replay: Option<Replay>,
+    ) -> anyhow::Result<Self> {
+        let (seed, scene) = match &replay {
+            Some(replay) => (replay.seed, replay.scene.clone()),
+            None => (options.seed, options.scene.clone()),
+        };
+        let mut game = Game::load(seed, scene.as_deref())?;
+        if let Some(path) = &options.checksum_log {
+            game.log_checksums_to(path, seed)?;
+        }
+
+        let (controls, controls_path) = Self::load_controls();
+        let mut input = actions::app_defaults();
+        input.set_all_bindings(&controls.app);
+        match &replay {
+            Some(replay) => replay.apply_bindings(&mut game),
+            None => game.input.set_all_bindings(&controls.game),
+        }
+
+        let recording = options.record.clone().map(|path| {
+            let replay = Replay {
+                scene,
+                bindings: Some(game.input.all_bindings()),
+                ..Replay::new(seed)
+            };
+            (replay, path)
+        });
+
+        let camera_controller = CameraController::new(300.0, 0.1);
+        let mut graphics_state = GraphicsState::new(window.clone()).await?;
+        let atlas = game.load_sprites(&mut graphics_state)?;
 
         Ok(Self {
-            window,
-            graphics_state,
+            display: Some(Display {
+                window,
+                graphics_state,
+                atlas,
+            }),
             camera_controller,
+            input,
+            mouse: Mouse::new(DRAG_THRESHOLD),
+            pointer: None,
+            gamepads: Gamepads::new(controls.dead_zones),
+            gamepad_backend: Self::gamepad_backend(),
+            controls,
+            controls_path,
+            rebind: None,
+            clock: GameClock::new(TICKS_PER_SECOND),
+            game,
+            recording,
+            replay: replay.map(ReplayPlayer::new),
+            script: None,
+            frames: 0,
         })
     }
 
+    /// Runs `game` without a window, e.g. to drive it with an `InputScript`
+    /// in tests. The game keeps its bindings and the app's actions get the
+    /// default ones rather than the player's. Gamepads are virtual until
+    /// `set_gamepad_backend` is called.
+    pub fn headless(game: Game) -> Self {
+        let controls = Controls::default();
+        let mut input = actions::app_defaults();
+        input.set_all_bindings(&controls.app);
+
+        Self {
+            display: None,
+            camera_controller: CameraController::new(300.0, 0.1),
+            input,
+            mouse: Mouse::new(DRAG_THRESHOLD),
+            pointer: None,
+            gamepads: Gamepads::new(controls.dead_zones),
+            gamepad_backend: Box::new(VirtualGamepads::new()),
+            controls,
+            controls_path: None,
+            rebind: None,
+            clock: GameClock::new(TICKS_PER_SECOND),
+            game,
+            recording: None,
+            replay: None,
+            script: None,
+            frames: 0,
+        }
+    }
+
+    /// Replaces where gamepad events come from, e.g. with `VirtualGamepads`
+    /// a test keeps a clone of
+    pub fn set_gamepad_backend(&mut self, backend: Box<dyn GamepadBackend>) {
+        self.gamepad_backend = backend;
+    }
+
+    /// The player's controls and where they are kept. Problems are reported
+    /// and leave the default controls, since the game is playable without
+    /// the player's own.
+    fn load_controls() -> (Controls, Option<PathBuf>) {
+        let path = match Controls::user_config_path() {
+            Ok(path) => path,
+            Err(error) => {
+                eprintln!("{:#}", error);
+                return (Controls::default(), None);
+            }
+        };
+        let controls = Controls::load(&path).unwrap_or_else(|error| {
+            eprintln!("{:#}, using the default controls", error);
+            Controls::default()
+        });
+        for conflict in controls.conflicts() {
+            eprintln!(
+                "{} is bound to several actions: {}",
+                conflict.binding,
+                conflict.actions.join(", ")
+            );
+        }
+        (controls, Some(path))
+    }
+
+    /// Physical pads if built with them and they can be opened, otherwise a
+    /// virtual backend that no pads ever connect to
+    fn gamepad_backend() -> Box<dyn GamepadBackend> {
+        #[cfg(feature = "gilrs")]
+        match crate::input::gamepad::GilrsGamepads::new() {
+            Ok(backend) => return Box::new(backend),
+            Err(error) => eprintln!("{:#}", error),
+        }
+        Box::new(VirtualGamepads::new())
+    }
+
+    /// Rebuilds every GPU resource, e.g. after the device was lost. Leaves no
+    /// display behind if it fails.
+    pub async fn recreate_graphics(&mut self) -> anyhow::Result<()> {
+        let Some(Display {
+            window,
+            graphics_state,
+            atlas,
+        }) = self.display.take()
+        else {
+            return Ok(());
+        };
+        // The atlas' texture belongs to the old device
+        drop(atlas);
+
+        let mut graphics_state = graphics_state.recreate().await?;
+        let atlas = self.game.load_sprites(&mut graphics_state)?;
+        self.display = Some(Display {
+            window,
+            graphics_state,
+            atlas,
+        });
+        Ok(())
+    }
+
     pub fn resize(&mut self, width: u32, height: u32) {
-        self.graphics_state.resize(width, height)
+        if let Some(display) = &mut self.display {
+            display.graphics_state.resize(width, height)
+        }
     }
 
-    pub fn update(&mut self) {
-        // Main entities
-        {
-            let logical_size = self.graphics_state.get_logical_size();
-            self.graphics_state.push_textured_quad(TexturedQuad {
-                position: Vector2::new(logical_size.width / 2.0, logical_size.height / 2.0),
-                dimensions: Vector2::new(200.0, 200.0),
-                layer: 1,
-            });
+    pub fn scale_factor_changed(&mut self, scale_factor: f64) {
+        if let Some(display) = &mut self.display {
+            display.graphics_state.scale_factor_changed(scale_factor)
+        }
+    }
+
+    /// Handles a keyboard or mouse event from the window. Both the game's and
+    /// the app's actions see every event, so that chords and releases work
+    /// across the two. While a replay is playing, live input only reaches
+    /// the app's actions.
+    pub fn handle_event(&mut self, event: InputEvent) {
+        if let Some((replay, _)) = &mut self.recording {
+            replay.record(self.game.ticks(), event);
+        }
+
+        match event {
+            InputEvent::CursorMoved { x, y } => {
+                let scale_factor = self
+                    .display
+                    .as_ref()
+                    .map_or(1.0, |display| display.graphics_state.scale_factor());
+                self.mouse.move_to(Vector2::new(
+                    (x / scale_factor) as f32,
+                    (y / scale_factor) as f32,
+                ));
+            }
+            _ => {
+                self.mouse.handle_event(&event);
+            }
+        }
+
+        if self.rebind.is_some() {
+            self.rebinding(&event);
+            // Releases still go to the actions so that no key is left held
+            if matches!(
+                event,
+                InputEvent::Key { pressed: true, .. }
+                    | InputEvent::MouseButton { pressed: true, .. }
+            ) {
+                return;
+            }
+        }
+
+        if self.replay.is_none() {
+            self.game.handle_event(&event);
         }
+        self.input.handle_event(&event);
+    }
 
-        // Debug entities
+    /// Handles everything the gamepads did since the last frame
+    fn poll_gamepads(&mut self) {
+        for event in self.gamepad_backend.poll() {
+            for event in self.gamepads.handle_event(event) {
+                match event {
+                    GamepadEvent::Connected(id) => println!("Gamepad {} connected", id.0),
+                    GamepadEvent::Disconnected(id) => println!("Gamepad {} disconnected", id.0),
+                    _ => (),
+                }
+                self.handle_event(InputEvent::Gamepad(event));
+            }
+        }
+    }
+
+    /// Sends the game the cursor's world position if it has changed, since
+    /// the cursor staying put over a moving camera still moves it in the world
+    fn update_pointer(&mut self) {
+        let Some(display) = &self.display else {
+            return;
+        };
+        if self.replay.is_some() {
+            return;
+        }
+        let camera = display.graphics_state.camera();
+        let pointer = self
+            .mouse
+            .position()
+            .map(|position| camera.screen_to_world(position));
+        if pointer == self.pointer {
+            return;
+        }
+        self.pointer = pointer;
+        if let Some(pointer) = pointer {
+            let event = InputEvent::PointerMoved {
+                x: pointer.x,
+                y: pointer.y,
+            };
+            if let Some((replay, _)) = &mut self.recording {
+                replay.record(self.game.ticks(), event);
+            }
+            self.game.handle_event(&event);
+        }
+    }
+
+    /// Feeds an event to the rebinding in progress
+    fn rebinding(&mut self, event: &InputEvent) {
+        let Some(rebind) = &mut self.rebind else {
+            return;
+        };
+        match rebind.handle_event(event, &mut self.controls) {
+            Ok(RebindProgress::Waiting) => (),
+            Ok(RebindProgress::Chose { action, old }) => {
+                println!("Press the new binding for {} in place of {}", action, old)
+            }
+            Ok(RebindProgress::Cancelled) => {
+                println!("Cancelled rebinding");
+                self.rebind = None;
+            }
+            Ok(RebindProgress::Rebound { action, binding }) => {
+                println!("Bound {} to {}", action, binding);
+                self.rebind = None;
+                self.controls_changed();
+            }
+            Err(error) => eprintln!("{:#}", error),
+        }
+    }
+
+    /// Applies and saves the controls after they changed
+    fn controls_changed(&mut self) {
+        self.gamepads.dead_zones = self.controls.dead_zones;
+        self.game.input.set_all_bindings(&self.controls.game);
+        self.input.set_all_bindings(&self.controls.app);
+        if let Some(path) = &self.controls_path
+            && let Err(error) = self.controls.save(path)
         {
-            self.graphics_state.push_debug_square(
-                Vector2::new(50.0, 50.0),
-                Vector2::new(30.0, 30.0),
-                3.14 / 4.0,
-                (1.0, 0.0, 1.0),
-            );
-            self.graphics_state.push_debug_triangle(
-                Vector2::new(100.0, 100.0),
-                Vector2::new(30.0, 30.0),
-                3.14 / 4.0,
-                (0.0, 1.0, 1.0),
-            );
+            eprintln!("{:#}", error);
         }
     }
 
-    pub fn render(&mut self) -> anyhow::Result<()> {
-        self.window.request_redraw();
+    /// Game bindings are part of a replay, so they can't change during one
+    fn check_controls_editable(&self) -> anyhow::Result<()> {
+        if self.recording.is_some() || self.replay.is_some() {
+            bail!("Can't change controls while recording or playing a replay");
+        }
+        Ok(())
+    }
+
+    /// Whether the player asked to quit since the last frame
+    pub fn quit_requested(&self) -> bool {
+        self.input.just_pressed(QUIT)
+    }
+
+    /// Acts on the app's actions since the last frame
+    fn apply_actions(&mut self) {
+        // Debug time controls
+        if self.input.just_pressed(PAUSE) {
+            let paused = self.clock.is_paused();
+            self.clock.set_paused(!paused);
+        }
+        if self.input.just_pressed(STEP) {
+            self.clock.step();
+        }
+        if self.input.just_pressed(SLOW_DOWN) {
+            let time_scale = self.clock.time_scale();
+            self.clock.set_time_scale(time_scale / 2.0);
+        }
+        if self.input.just_pressed(SPEED_UP) {
+            let time_scale = self.clock.time_scale();
+            self.clock.set_time_scale(time_scale * 2.0);
+        }
+        if self.input.just_pressed(RESET_SPEED) {
+            self.clock.set_time_scale(1.0);
+        }
 
-        self.graphics_state.render()?;
+        if self.input.just_pressed(TOGGLE_TIME_FIELDS) {
+            self.game.show_time_fields = !self.game.show_time_fields;
+        }
+        if self.input.just_pressed(SAVE_SCENE) {
+            match self.game.save_scene(Path::new(SAVED_SCENE_PATH)) {
+                Ok(()) => println!("Saved scene to {}", SAVED_SCENE_PATH),
+                Err(error) => eprintln!("{:#}", error),
+            }
+        }
+        if self.input.just_pressed(QUICKSAVE) {
+            match self.quicksave() {
+                Ok(()) => println!("Saved to slot {}", QUICKSAVE_SLOT),
+                Err(error) => eprintln!("{:#}", error),
+            }
+        }
+        if self.input.just_pressed(REBIND) {
+            match self.check_controls_editable() {
+                Ok(()) => {
+                    println!("Press a binding of the action to rebind, or Escape to cancel");
+                    self.rebind = Some(Rebind::new());
+                }
+                Err(error) => eprintln!("{:#}", error),
+            }
+        }
+        if self.input.just_pressed(RESET_CONTROLS) {
+            match self.check_controls_editable() {
+                Ok(()) => {
+                    self.controls.reset();
+                    self.controls_changed();
+                    println!("Reset the controls to their defaults");
+                }
+                Err(error) => eprintln!("{:#}", error),
+            }
+        }
+        if self.input.just_pressed(QUICKLOAD) {
+            match self.quickload() {
+                Ok(()) => println!("Loaded slot {}", QUICKSAVE_SLOT),
+                Err(error) => eprintln!("{:#}", error),
+            }
+        }
+    }
 
-        self.graphics_state.clear_instances();
+    fn quicksave(&self) -> anyhow::Result<()> {
+        SaveSlots::in_user_data_dir()?.save(QUICKSAVE_SLOT, &self.game.save_data())
+    }
 
+    /// Replaces the game with the one in the quicksave slot. Not allowed while
+    /// recording or replaying, since replays always start from a fresh game.
+    fn quickload(&mut self) -> anyhow::Result<()> {
+        if self.recording.is_some() || self.replay.is_some() {
+            bail!("Can't load a save while recording or playing a replay");
+        }
+        let save = SaveSlots::in_user_data_dir()?
+            .load(QUICKSAVE_SLOT)?
+            .with_context(|| format!("Save slot {} is empty", QUICKSAVE_SLOT))?;
+        let mut game = Game::from_save(self.game.world.rng().state(), &save)?;
+        game.input.set_all_bindings(&self.controls.game);
+        if let Some(display) = &mut self.display
+            && game.textures() != self.game.textures()
+        {
+            display.atlas = game.load_sprites(&mut display.graphics_state)?;
+        }
+        self.game = game;
+        // The new game hasn't been told where the pointer is
+        self.pointer = None;
         Ok(())
     }
+
+    /// Returns the final checksum once a replay has played to its end
+    pub fn replay_finished(&self) -> Option<u64> {
+        let replay = self.replay.as_ref()?;
+        replay
+            .is_finished(self.game.ticks())
+            .then(|| self.game.world.checksum())
+    }
+
+    /// The replay being played, if any
+    pub fn replay(&self) -> Option<&Replay> {
+        self.replay.as_ref().map(ReplayPlayer::replay)
+    }
+
+    /// Saves the recorded input, if recording. Call once when exiting.
+    pub fn finish_recording(&mut self) -> anyhow::Result<()> {
+        let Some((mut replay, path)) = self.recording.take() else {
+            return Ok(());
+        };
+        replay.finish(self.game.ticks(), self.game.world.checksum());
+        replay.save(&path)?;
+        println!("Saved replay to {}", path.display());
+        Ok(())
+    }
+
+    /// Starts sending the events in `script`, counting its frames from the
+    /// next one
+    pub fn play_script(&mut self, script: InputScript) {
+        self.script = Some((script, self.frames));
+    }
+
+    /// Whether every event of the script being played has been sent
+    pub fn script_finished(&self) -> bool {
+        self.script
+            .as_ref()
+            .is_none_or(|(script, _)| script.is_finished())
+    }
+
+    /// Frames run so far
+    pub fn frames(&self) -> u64 {
+        self.frames
+    }
+
+    /// Runs every fixed tick that is due and then renders one frame
+    pub fn frame(&mut self) -> Result<(), RenderError> {
+        self.begin_frame();
+        let ticks = self.clock.advance_to(Instant::now());
+        self.run_ticks(ticks);
+        let result = self.render(self.clock.frame_seconds(), self.clock.alpha());
+        self.end_frame();
+        result
+    }
+
+    /// Runs `frames` frames as if each took exactly one tick, as fast as
+    /// possible and without rendering. Unless the clock is paused or scaled,
+    /// every frame runs one tick.
+    pub fn run_frames(&mut self, frames: u32) {
+        for _ in 0..frames {
+            self.begin_frame();
+            let ticks = self.clock.advance(self.clock.step_duration());
+            self.run_ticks(ticks);
+            self.end_frame();
+        }
+    }
+
+    /// Takes in everything that happened since the last frame
+    fn begin_frame(&mut self) {
+        if let Some((script, start)) = &mut self.script {
+            let events: Vec<InputEvent> = script
+                .take_due(self.frames - *start)
+                .iter()
+                .map(|scripted| scripted.event)
+                .collect();
+            for event in events {
+                self.handle_event(event);
+            }
+        }
+        self.poll_gamepads();
+        self.apply_actions();
+        self.update_pointer();
+    }
+
+    fn run_ticks(&mut self, ticks: u32) {
+        for _ in 0..ticks {
+            if let Some(replay) = &mut self.replay {
+                if replay.is_finished(self.game.ticks()) {
+                    break;
+                }
+                replay.feed(&mut self.game);
+            }
+            self.game.update(self.clock.step_seconds());
+        }
+    }
+
+    /// Forgets this frame's input edges
+    fn end_frame(&mut self) {
+        self.input.advance();
+        self.mouse.advance();
+        self.frames += 1;
+    }
+
+    /// Renders the game `alpha` of the way between the last two ticks.
+    /// `frame_dt` is the real time since the previous frame. Does nothing
+    /// when headless.
+    pub fn render(&mut self, frame_dt: f32, alpha: f32) -> Result<(), RenderError> {
+        let Some(display) = &mut self.display else {
+            return Ok(());
+        };
+        display.window.request_redraw();
+
+        self.camera_controller.update_camera(
+            display.graphics_state.camera_mut(),
+            &self.input,
+            frame_dt,
+        );
+
+        self.game
+            .draw(&mut display.graphics_state, &display.atlas, alpha);
+
+        let result = display.graphics_state.render();
+
+        display.graphics_state.clear_instances();
+
+        result
+    }
 }
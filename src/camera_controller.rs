@@ -1,77 +1,44 @@
-use cgmath::InnerSpace;
-use winit::keyboard::KeyCode;
-
-use crate::graphics::camera::Camera;
+use crate::{
+    graphics::camera::Camera2D,
+    input::{
+        actions::{CAMERA_DOWN, CAMERA_LEFT, CAMERA_RIGHT, CAMERA_UP, ZOOM},
+        map::InputMap,
+    },
+};
+use cgmath::Vector2;
+
+const MIN_ZOOM: f32 = 0.1;
+const MAX_ZOOM: f32 = 10.0;
 
 pub struct CameraController {
+    /// Logical pixels moved per second, independent of zoom
     speed: f32,
-    is_forward_pressed: bool,
-    is_backward_pressed: bool,
-    is_left_pressed: bool,
-    is_right_pressed: bool,
+    /// Fractional zoom change per scrolled line
+    zoom_speed: f32,
 }
 
 impl CameraController {
-    pub fn new(speed: f32) -> Self {
-        Self {
-            speed,
-            is_forward_pressed: false,
-            is_backward_pressed: false,
-            is_left_pressed: false,
-            is_right_pressed: false,
-        }
-    }
-
-    pub fn handle_key(&mut self, code: KeyCode, is_pressed: bool) -> bool {
-        match code {
-            KeyCode::KeyW | KeyCode::ArrowUp => {
-                self.is_forward_pressed = is_pressed;
-                true
-            }
-            KeyCode::KeyA | KeyCode::ArrowLeft => {
-                self.is_left_pressed = is_pressed;
-                true
-            }
-            KeyCode::KeyS | KeyCode::ArrowDown => {
-                self.is_backward_pressed = is_pressed;
-                true
-            }
-            KeyCode::KeyD | KeyCode::ArrowRight => {
-                self.is_right_pressed = is_pressed;
-                true
-            }
-            _ => false,
-        }
+    pub fn new(speed: f32, zoom_speed: f32) -> Self {
+        Self { speed, zoom_speed }
     }
 
-    pub fn update_camera(&self, camera: &mut Camera) {
-        let forward = camera.target - camera.eye;
-        let forward_norm = forward.normalize();
-        let forward_mag = forward.magnitude();
-
-        // Prevents glitching when the camera gets too close to the
-        // center of the scene.
-        if self.is_forward_pressed && forward_mag > self.speed {
-            camera.eye += forward_norm * self.speed;
-        }
-        if self.is_backward_pressed {
-            camera.eye -= forward_norm * self.speed;
+    /// Pans and zooms by the camera actions held or scrolled since the last
+    /// frame
+    pub fn update_camera(&self, camera: &mut Camera2D, input: &InputMap, dt: f32) {
+        let scroll_lines = input.value(ZOOM);
+        if scroll_lines != 0.0 {
+            camera.zoom = (camera.zoom * (1.0 + self.zoom_speed).powf(scroll_lines))
+                .clamp(MIN_ZOOM, MAX_ZOOM);
         }
 
-        let right = forward_norm.cross(camera.up);
-
-        // Redo radius calc in case the forward/backward is pressed.
-        let forward = camera.target - camera.eye;
-        let forward_mag = forward.magnitude();
+        // Pan in screen space so that "up" is always up on the screen, even
+        // when the camera is rotated or zoomed
+        let screen_direction = Vector2::new(
+            input.axis(CAMERA_LEFT, CAMERA_RIGHT),
+            input.axis(CAMERA_UP, CAMERA_DOWN),
+        );
 
-        if self.is_right_pressed {
-            // Rescale the distance between the target and the eye so
-            // that it doesn't change. The eye, therefore, still
-            // lies on the circle made by the target and eye.
-            camera.eye = camera.target - (forward + right * self.speed).normalize() * forward_mag;
-        }
-        if self.is_left_pressed {
-            camera.eye = camera.target - (forward - right * self.speed).normalize() * forward_mag;
-        }
+        let center = camera.viewport / 2.0;
+        camera.position = camera.screen_to_world(center + screen_direction * self.speed * dt);
     }
 }
@@ -0,0 +1,184 @@
+use cgmath::{Matrix3, Rad, Vector2, VectorSpace};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    echo::ECHO_TINT,
+    ecs::entity::Entity,
+    graphics::{blend::BlendMode, color::Color},
+};
+
+/// Placement of an entity relative to its parent, or to the world if it has
+/// none. Children move, rotate and scale with their parent, so attaching a
+/// hand to a character and a weapon to the hand makes all three move together.
+///
+/// The resulting world matrices are computed once per tick by
+/// `systems::hierarchy` and stored in `World::world_matrices`.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Transform2D {
+    pub position: Vector2<f32>,
+    /// Radians, counterclockwise
+    pub rotation: f32,
+    /// Multiplies the size of the entity's sprite or shape and everything
+    /// attached to it. Colliders and time fields aren't scaled.
+    pub scale: Vector2<f32>,
+    /// The entity this one is attached to. Entities whose parent is gone or
+    /// has no transform are placed in world space.
+    pub parent: Option<Entity>,
+}
+
+impl Default for Transform2D {
+    fn default() -> Self {
+        Self {
+            position: Vector2::new(0.0, 0.0),
+            rotation: 0.0,
+            scale: Vector2::new(1.0, 1.0),
+            parent: None,
+        }
+    }
+}
+
+impl Transform2D {
+    pub fn from_position(position: Vector2<f32>) -> Self {
+        Self {
+            position,
+            ..Self::default()
+        }
+    }
+
+    /// The same placement relative to `parent`
+    pub fn with_parent(self, parent: Entity) -> Self {
+        Self {
+            parent: Some(parent),
+            ..self
+        }
+    }
+
+    /// Maps points from this entity's space to its parent's: scale, then
+    /// rotate, then translate
+    pub fn local_matrix(&self) -> Matrix3<f32> {
+        Matrix3::from_translation(self.position)
+            * Matrix3::from_angle_z(Rad(self.rotation))
+            * Matrix3::from_nonuniform_scale(self.scale.x, self.scale.y)
+    }
+
+    /// Interpolates placement. The parent is taken from `other`.
+    pub fn lerp(self, other: Transform2D, amount: f32) -> Transform2D {
+        Transform2D {
+            position: self.position.lerp(other.position, amount),
+            rotation: self.rotation + (other.rotation - self.rotation) * amount,
+            scale: self.scale.lerp(other.scale, amount),
+            parent: other.parent,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Velocity {
+    pub linear: Vector2<f32>,
+    /// Radians per second
+    pub angular: f32,
+}
+
+impl Velocity {
+    pub const ZERO: Self = Self {
+        linear: Vector2::new(0.0, 0.0),
+        angular: 0.0,
+    };
+}
+
+/// How fast time passes for an entity before time fields are applied, e.g.
+/// 0.0 for a frozen object or 2.0 for an accelerated enemy. Entities without
+/// one run at 1.0.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimeScale(pub f32);
+
+/// Axis aligned box centered on the entity's position. Entities without one
+/// are treated as points.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Collider {
+    pub half_extents: Vector2<f32>,
+}
+
+impl Collider {
+    pub const POINT: Self = Self {
+        half_extents: Vector2::new(0.0, 0.0),
+    };
+
+    pub fn new(half_extents: Vector2<f32>) -> Self {
+        Self { half_extents }
+    }
+
+    /// Whether this collider at `position` overlaps `other` at `other_position`
+    pub fn overlaps(
+        &self,
+        position: Vector2<f32>,
+        other: &Collider,
+        other_position: Vector2<f32>,
+    ) -> bool {
+        let offset = other_position - position;
+        let reach = self.half_extents + other.half_extents;
+        offset.x.abs() <= reach.x && offset.y.abs() <= reach.y
+    }
+}
+
+/// Multiplies `color` by the echo tint
+fn fade(color: Color) -> Color {
+    Color::rgba(
+        color.r * ECHO_TINT.r,
+        color.g * ECHO_TINT.g,
+        color.b * ECHO_TINT.b,
+        color.a * ECHO_TINT.a,
+    )
+}
+
+/// A region of the game's sprite atlas, looked up by name when drawing
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sprite {
+    pub name: String,
+    pub dimensions: Vector2<f32>,
+    pub layer: u32,
+    pub tint: Color,
+    pub blend: BlendMode,
+}
+
+impl Sprite {
+    /// The translucent, tinted look of an echo of this sprite
+    pub fn echo(&self) -> Self {
+        Self {
+            tint: fade(self.tint),
+            // Premultiplied sprites stay premultiplied, everything else
+            // needs blending to show the fade
+            blend: match self.blend {
+                BlendMode::PremultipliedAlpha => BlendMode::PremultipliedAlpha,
+                _ => BlendMode::Alpha,
+            },
+            ..self.clone()
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ShapeKind {
+    Square,
+    Triangle,
+}
+
+/// A flat colored shape drawn with the debug pipeline
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Shape {
+    pub kind: ShapeKind,
+    pub scale: Vector2<f32>,
+    pub color: Color,
+    pub blend: BlendMode,
+}
+
+impl Shape {
+    /// The translucent, tinted look of an echo of this shape
+    pub fn echo(&self) -> Self {
+        Self {
+            color: fade(self.color),
+            blend: BlendMode::Alpha,
+            ..*self
+        }
+    }
+}
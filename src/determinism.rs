@@ -0,0 +1,148 @@
+use std::fmt;
+
+use crate::{echo::PlayerInput, world::World};
+
+/// FNV-1a hash over the bits of simulation state. Floats are hashed by their
+/// bit patterns, so two runs only match if every value is bit for bit equal.
+pub struct Checksum {
+    hash: u64,
+}
+
+impl Checksum {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    pub fn new() -> Self {
+        Self {
+            hash: Self::OFFSET_BASIS,
+        }
+    }
+
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.hash ^= byte as u64;
+            self.hash = self.hash.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    pub fn write_u64(&mut self, value: u64) {
+        self.write_bytes(&value.to_le_bytes());
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.write_u64(value.to_bits() as u64);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.write_u64(value as u64);
+    }
+
+    /// Writes the length first, so that "ab" then "c" differs from "a" then "bc"
+    pub fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write_bytes(value.as_bytes());
+    }
+
+    pub fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The first tick at which two runs disagreed
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Divergence {
+    pub tick: u64,
+    pub expected: Option<u64>,
+    pub actual: Option<u64>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |checksum: Option<u64>| match checksum {
+            Some(checksum) => format!("{:016x}", checksum),
+            None => "nothing".to_string(),
+        };
+        write!(
+            f,
+            "Simulation diverged at tick {}: expected {}, got {}",
+            self.tick,
+            show(self.expected),
+            show(self.actual)
+        )
+    }
+}
+
+impl std::error::Error for Divergence {}
+
+/// Per-tick checksums of one run, in the order the ticks were simulated
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ChecksumLog {
+    entries: Vec<(u64, u64)>,
+}
+
+impl ChecksumLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, tick: u64, checksum: u64) {
+        self.entries.push((tick, checksum));
+    }
+
+    pub fn entries(&self) -> &[(u64, u64)] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Checksum of the last simulated tick
+    pub fn last(&self) -> Option<u64> {
+        self.entries.last().map(|&(_, checksum)| checksum)
+    }
+
+    /// Compares this run against an expected one and reports the first tick
+    /// where they differ, including one run ending before the other
+    pub fn verify(&self, expected: &ChecksumLog) -> Result<(), Divergence> {
+        let ticks = self.entries.len().max(expected.entries.len());
+        for index in 0..ticks {
+            let actual = self.entries.get(index);
+            let wanted = expected.entries.get(index);
+            if actual != wanted {
+                return Err(Divergence {
+                    tick: actual.or(wanted).map(|&(tick, _)| tick).unwrap_or_default(),
+                    expected: wanted.map(|&(_, checksum)| checksum),
+                    actual: actual.map(|&(_, checksum)| checksum),
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Runs `world` headlessly for one tick per input, feeding each input to the
+/// player controlled bodies, and returns the checksum after every tick
+pub fn run_inputs(
+    world: &mut World,
+    dt: f32,
+    inputs: impl IntoIterator<Item = PlayerInput>,
+) -> ChecksumLog {
+    let mut log = ChecksumLog::new();
+    for input in inputs {
+        world.player_input = input;
+        world.update(dt);
+        log.record(world.tick(), world.checksum());
+    }
+    log
+}
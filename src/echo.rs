@@ -0,0 +1,161 @@
+use std::{collections::VecDeque, sync::Arc};
+
+use cgmath::{InnerSpace, Vector2};
+
+use crate::{components::Transform2D, graphics::color::Color};
+
+/// How fast controllable entities walk, in world units per second
+pub const WALK_SPEED: f32 = 150.0;
+
+/// Multiplied into the appearance of echoes to tell them apart from the present
+pub const ECHO_TINT: Color = Color::rgba(0.6, 0.8, 1.0, 0.5);
+
+/// What a controllable entity was asked to do during one tick
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PlayerInput {
+    pub up: bool,
+    pub down: bool,
+    pub left: bool,
+    pub right: bool,
+}
+
+impl PlayerInput {
+    /// Unit length walking direction, or zero when standing still
+    pub fn direction(&self) -> Vector2<f32> {
+        let axis = |negative: bool, positive: bool| positive as i32 as f32 - negative as i32 as f32;
+        let direction = Vector2::new(axis(self.left, self.right), axis(self.down, self.up));
+        if direction.x != 0.0 && direction.y != 0.0 {
+            direction.normalize()
+        } else {
+            direction
+        }
+    }
+}
+
+/// One recorded tick of a player controlled entity
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct TimelineEntry {
+    tick: u64,
+    /// Where the entity was before the input was applied
+    transform: Transform2D,
+    input: PlayerInput,
+}
+
+/// Bounded recording of the inputs a player controlled entity received, used
+/// to spawn echoes that repeat them. This is history rather than simulation
+/// state, so it isn't part of world snapshots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputTimeline {
+    entries: VecDeque<TimelineEntry>,
+    capacity: usize,
+}
+
+impl InputTimeline {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Creates a timeline that holds `seconds` of inputs at the given tick rate
+    pub fn with_seconds(seconds: f32, ticks_per_second: u32) -> Self {
+        Self::new((seconds * ticks_per_second as f32).ceil() as usize)
+    }
+
+    pub fn record(&mut self, tick: u64, transform: Transform2D, input: PlayerInput) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(TimelineEntry {
+            tick,
+            transform,
+            input,
+        });
+    }
+
+    /// Forgets every input from `tick` onwards, e.g. after rewinding to it
+    pub fn truncate_from(&mut self, tick: u64) {
+        while self.entries.back().is_some_and(|entry| entry.tick >= tick) {
+            self.entries.pop_back();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The last `ticks` recorded ticks, starting from where the entity was at
+    /// the beginning of them. Returns `None` if nothing has been recorded.
+    pub fn segment(&self, ticks: usize) -> Option<EchoPlayback> {
+        let start = self.entries.len().saturating_sub(ticks);
+        let first = self.entries.get(start)?;
+        Some(EchoPlayback {
+            start: first.transform,
+            inputs: self
+                .entries
+                .range(start..)
+                .map(|entry| entry.input)
+                .collect(),
+            cursor: 0,
+        })
+    }
+}
+
+/// A recorded segment of inputs being replayed by an echo, one per tick.
+/// The inputs are shared so that snapshotting an echo is cheap.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EchoPlayback {
+    start: Transform2D,
+    inputs: Arc<[PlayerInput]>,
+    cursor: usize,
+}
+
+impl EchoPlayback {
+    /// Where the recorded entity was when the segment began. Echoes spawn
+    /// here so that replaying the inputs retraces the same path.
+    pub fn start(&self) -> Transform2D {
+        self.start
+    }
+
+    /// Number of ticks that have been replayed so far
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    pub fn set_cursor(&mut self, cursor: usize) {
+        self.cursor = cursor.min(self.inputs.len());
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.cursor == self.inputs.len()
+    }
+
+    /// Input for the next tick. Once the segment runs out the echo stands still.
+    pub fn next_input(&mut self) -> PlayerInput {
+        match self.inputs.get(self.cursor) {
+            Some(&input) => {
+                self.cursor += 1;
+                input
+            }
+            None => PlayerInput::default(),
+        }
+    }
+}
+
+/// Where a controllable entity gets its input from each tick
+#[derive(Clone, Debug, PartialEq)]
+pub enum Controller {
+    /// Follows the live player input, recording it to the entity's
+    /// `InputTimeline` if it has one
+    Player,
+    /// Replays a segment recorded from a player
+    Echo(EchoPlayback),
+}
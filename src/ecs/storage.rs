@@ -0,0 +1,135 @@
+use crate::ecs::entity::Entity;
+
+#[derive(Clone, Debug, PartialEq)]
+struct Slot<T> {
+    generation: u32,
+    value: T,
+}
+
+/// Components of one type, indexed by entity. Iteration is always in entity
+/// index order, which keeps systems deterministic.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Storage<T> {
+    slots: Vec<Option<Slot<T>>>,
+    len: usize,
+}
+
+impl<T> Default for Storage<T> {
+    fn default() -> Self {
+        Self {
+            slots: vec![],
+            len: 0,
+        }
+    }
+}
+
+impl<T> Storage<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces the entity's component, returning the replaced one
+    pub fn insert(&mut self, entity: Entity, value: T) -> Option<T> {
+        if self.slots.len() <= entity.index() {
+            self.slots.resize_with(entity.index() + 1, || None);
+        }
+        let slot = self.slots[entity.index()].replace(Slot {
+            generation: entity.generation(),
+            value,
+        });
+        match slot {
+            // An older entity's leftover component is dropped, not returned
+            Some(slot) if slot.generation == entity.generation() => Some(slot.value),
+            Some(_) => None,
+            None => {
+                self.len += 1;
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, entity: Entity) -> Option<T> {
+        let slot = self.slots.get_mut(entity.index())?;
+        if slot
+            .as_ref()
+            .is_some_and(|slot| slot.generation == entity.generation())
+        {
+            self.len -= 1;
+            slot.take().map(|slot| slot.value)
+        } else {
+            None
+        }
+    }
+
+    pub fn get(&self, entity: Entity) -> Option<&T> {
+        match self.slots.get(entity.index()) {
+            Some(Some(slot)) if slot.generation == entity.generation() => Some(&slot.value),
+            _ => None,
+        }
+    }
+
+    pub fn get_mut(&mut self, entity: Entity) -> Option<&mut T> {
+        match self.slots.get_mut(entity.index()) {
+            Some(Some(slot)) if slot.generation == entity.generation() => Some(&mut slot.value),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.get(entity).is_some()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.clear();
+        self.len = 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (Entity, &T)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            slot.as_ref()
+                .map(|slot| (entity(index, slot.generation), &slot.value))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Entity, &mut T)> {
+        self.slots
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(index, slot)| {
+                slot.as_mut()
+                    .map(|slot| (entity(index, slot.generation), &mut slot.value))
+            })
+    }
+
+    /// Entities that have a component in both storages
+    pub fn join<'a, U>(
+        &'a self,
+        other: &'a Storage<U>,
+    ) -> impl Iterator<Item = (Entity, &'a T, &'a U)> {
+        self.iter()
+            .filter_map(|(entity, a)| other.get(entity).map(|b| (entity, a, b)))
+    }
+
+    /// Like `join`, with mutable access to this storage's components
+    pub fn join_mut<'a, U>(
+        &'a mut self,
+        other: &'a Storage<U>,
+    ) -> impl Iterator<Item = (Entity, &'a mut T, &'a U)> {
+        self.iter_mut()
+            .filter_map(|(entity, a)| other.get(entity).map(|b| (entity, a, b)))
+    }
+}
+
+/// Storages only learn entities through insertion, so they rebuild ids from
+/// their slots
+fn entity(index: usize, generation: u32) -> Entity {
+    Entity::from_raw(index as u32, generation)
+}
@@ -0,0 +1,303 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use cgmath::Vector2;
+
+use crate::{
+    components::Transform2D,
+    echo::{Controller, InputTimeline, PlayerInput},
+    ecs::entity::Entity,
+    graphics::{
+        GraphicsState, ViewportResized,
+        atlas::{Atlas, AtlasBuilder},
+    },
+    input::{
+        actions::{self, MOVE_DOWN, MOVE_LEFT, MOVE_RIGHT, MOVE_UP, REWIND, SPAWN_ECHO},
+        event::InputEvent,
+        map::InputMap,
+        mouse::Mouse,
+    },
+    prefab::PrefabLibrary,
+    progress::Progress,
+    rewind::Rewind,
+    save::SaveData,
+    scene::{DEFAULT_SCENE, Scene, SceneTexture},
+    systems,
+    world::World,
+};
+
+/// Simulation rate, independent of the display's refresh rate
+pub const TICKS_PER_SECOND: u32 = 60;
+
+/// How far back in time the player can rewind
+const REWIND_SECONDS: f32 = 10.0;
+
+/// World units the pointer has to move with a button held to start a drag
+const POINTER_DRAG_THRESHOLD: f32 = 4.0;
+
+/// How much of the player's past an echo replays
+const ECHO_SECONDS: f32 = 5.0;
+
+/// The simulated part of the game and the input that drives it. Has no
+/// window or GPU resources, so it can run headlessly.
+pub struct Game {
+    pub world: World,
+    pub rewind: Rewind,
+    /// Draws the boundaries of every time field on top of the world
+    pub show_time_fields: bool,
+    /// Turns input events into the actions the game acts on each tick
+    pub input: InputMap,
+    /// The pointer in world space, for clicking on and dragging things
+    pub mouse: Mouse,
+    /// Kept by save games, unlike the rest of the world
+    pub progress: Progress,
+    /// Logical size of what the game is drawn to, for laying out screen
+    /// anchored content. Zero until the first `draw`.
+    viewport: Vector2<f32>,
+    player: Entity,
+    /// Scene file the game started in, or `None` for the built in sandbox
+    scene: Option<PathBuf>,
+    /// Templates `spawn_prefab` can spawn
+    prefabs: PrefabLibrary,
+    /// Where the atlas gets each sprite's image from
+    textures: Vec<SceneTexture>,
+    /// Ticks run so far, counting rewound ones. Unlike the world's tick this
+    /// never goes backwards, so it can timestamp input.
+    ticks: u64,
+    /// Receives a `tick checksum` line after every tick
+    checksum_log: Option<BufWriter<File>>,
+}
+
+impl Game {
+    /// Starts a game in the built in sandbox scene
+    pub fn new(seed: u64) -> Self {
+        let scene = Scene::parse(DEFAULT_SCENE, "sandbox.ron").expect("Built in scene is valid");
+        Self::with_scene(seed, &scene, PrefabLibrary::built_in())
+            .expect("Built in scene has a player")
+    }
+
+    /// Starts a game in `scene`, which needs exactly one player entity
+    pub fn with_scene(seed: u64, scene: &Scene, prefabs: PrefabLibrary) -> anyhow::Result<Self> {
+        let mut world = World::with_seed(seed);
+        let entities = scene.instantiate(&mut world, &prefabs)?;
+
+        let mut players = entities
+            .into_iter()
+            .filter(|&entity| matches!(world.controllers.get(entity), Some(Controller::Player)));
+        let (Some(player), None) = (players.next(), players.next()) else {
+            bail!("Scene needs exactly one player entity");
+        };
+        // Rewinding consumes recorded input, so keep enough to still spawn a
+        // full echo after rewinding as far as possible
+        world.timelines.insert(
+            player,
+            InputTimeline::with_seconds(ECHO_SECONDS + REWIND_SECONDS, TICKS_PER_SECOND),
+        );
+
+        // The scene's textures win over prefab ones with the same name
+        let mut textures = scene.textures.clone();
+        for texture in prefabs.textures() {
+            if !textures.iter().any(|known| known.name == texture.name) {
+                textures.push(texture.clone());
+            }
+        }
+
+        Ok(Self {
+            world,
+            rewind: Rewind::new(REWIND_SECONDS, TICKS_PER_SECOND),
+            show_time_fields: false,
+            input: actions::game_defaults(),
+            mouse: Mouse::new(POINTER_DRAG_THRESHOLD),
+            progress: Progress::default(),
+            viewport: Vector2::new(0.0, 0.0),
+            player,
+            scene: None,
+            prefabs,
+            textures,
+            ticks: 0,
+            checksum_log: None,
+        })
+    }
+
+    /// Starts a game in the scene at `path`, or in the sandbox without one.
+    /// Prefabs in a `prefabs` directory next to the scene are added to the
+    /// built in ones, replacing those with the same name.
+    pub fn load(seed: u64, path: Option<&Path>) -> anyhow::Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::new(seed));
+        };
+        let scene = Scene::load(path)?;
+        let mut prefabs = PrefabLibrary::built_in();
+        let prefab_dir = path.with_file_name("prefabs");
+        if prefab_dir.is_dir() {
+            prefabs.load_dir(&prefab_dir)?;
+        }
+        let mut game = Self::with_scene(seed, &scene, prefabs)
+            .with_context(|| format!("Failed to start scene {}", path.display()))?;
+        game.scene = Some(path.to_path_buf());
+        Ok(game)
+    }
+
+    /// Starts the scene `save` was made in, with the player and their
+    /// progress where they were
+    pub fn from_save(seed: u64, save: &SaveData) -> anyhow::Result<Self> {
+        let mut game = Self::load(seed, save.scene.as_deref())?;
+        let (x, y) = save.player_position;
+        game.place_player(Vector2::new(x, y));
+        game.progress = save.progress.clone();
+        Ok(game)
+    }
+
+    /// The entity the player controls
+    pub fn player(&self) -> Entity {
+        self.player
+    }
+
+    /// Where the player is in world space
+    pub fn player_position(&self) -> Vector2<f32> {
+        self.world
+            .world_position(self.player)
+            .unwrap_or(Vector2::new(0.0, 0.0))
+    }
+
+    /// What a save slot keeps of the game
+    pub fn save_data(&self) -> SaveData {
+        let position = self.player_position();
+        SaveData::new(
+            self.scene.clone(),
+            (position.x, position.y),
+            self.progress.clone(),
+        )
+    }
+
+    /// Moves the player to `position` without interpolating there
+    fn place_player(&mut self, position: Vector2<f32>) {
+        let Some(transform) = self.world.transforms.get_mut(self.player) else {
+            return;
+        };
+        transform.position = position;
+        let transform = *transform;
+        self.world
+            .previous_transforms
+            .insert(self.player, transform);
+        systems::hierarchy::propagate(&mut self.world);
+    }
+
+    /// Spawns an instance of the prefab `name` placed by `transform` and
+    /// returns its root
+    pub fn spawn_prefab(&mut self, name: &str, transform: Transform2D) -> anyhow::Result<Entity> {
+        self.prefabs.spawn(&mut self.world, name, transform)
+    }
+
+    /// The textures the game's sprites are drawn with
+    pub fn textures(&self) -> &[SceneTexture] {
+        &self.textures
+    }
+
+    /// Uploads every texture the game draws with
+    pub fn load_sprites(&self, graphics_state: &mut GraphicsState) -> anyhow::Result<Atlas> {
+        let mut builder = AtlasBuilder::premultiplied();
+        for texture in &self.textures {
+            builder.add_file(&texture.name, &texture.path)?;
+        }
+        graphics_state.build_atlas(builder)
+    }
+
+    /// Writes the live world to `path` as a scene that can be loaded again
+    pub fn save_scene(&self, path: &Path) -> anyhow::Result<()> {
+        Scene::from_world(&self.world, &self.textures).save(path)
+    }
+
+    /// Starts writing the world checksum after every tick to `path`
+    pub fn log_checksums_to(&mut self, path: &Path, seed: u64) -> anyhow::Result<()> {
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+        let mut writer = BufWriter::new(file);
+        writeln!(writer, "seed {}", seed)?;
+        self.checksum_log = Some(writer);
+        Ok(())
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Feeds an input event to the game's actions and mouse, which take
+    /// effect on the next tick. Returns whether any action is bound to the
+    /// input.
+    pub fn handle_event(&mut self, event: &InputEvent) -> bool {
+        self.mouse.handle_event(event);
+        self.input.handle_event(event)
+    }
+
+    /// Advances the simulation by one fixed tick of `dt` seconds, or steps it
+    /// back one tick while rewinding
+    pub fn update(&mut self, dt: f32) {
+        self.apply_actions();
+        self.rewind.update(&mut self.world, dt);
+        self.ticks += 1;
+        self.progress.play_seconds += dt as f64;
+
+        if let Some(log) = &mut self.checksum_log {
+            let tick = self.world.tick();
+            if let Err(error) = writeln!(log, "{} {:016x}", tick, self.world.checksum()) {
+                eprintln!("Failed to write checksum log, disabling it: {}", error);
+                self.checksum_log = None;
+            }
+        }
+    }
+
+    /// Acts on the input since the last tick
+    fn apply_actions(&mut self) {
+        if self.input.just_pressed(REWIND) {
+            self.rewind.set_rewinding(true);
+        }
+        if self.input.just_released(REWIND) {
+            self.rewind.set_rewinding(false);
+        }
+        if self.input.just_pressed(SPAWN_ECHO) {
+            self.spawn_echo();
+        }
+        self.world.player_input = PlayerInput {
+            up: self.input.is_pressed(MOVE_UP),
+            down: self.input.is_pressed(MOVE_DOWN),
+            left: self.input.is_pressed(MOVE_LEFT),
+            right: self.input.is_pressed(MOVE_RIGHT),
+        };
+        self.input.advance();
+        self.mouse.advance();
+    }
+
+    /// Spawns an echo that replays the player's last few seconds alongside them
+    pub fn spawn_echo(&mut self) {
+        let ticks = (ECHO_SECONDS * TICKS_PER_SECOND as f32) as usize;
+        self.world.spawn_echo(self.player, ticks);
+    }
+
+    /// Logical size of what the game was last drawn to
+    pub fn viewport(&self) -> Vector2<f32> {
+        self.viewport
+    }
+
+    /// Lays out screen anchored content for a new window size or scale factor
+    pub fn viewport_resized(&mut self, resized: ViewportResized) {
+        let size = resized.logical_size;
+        self.viewport = Vector2::new(size.width, size.height);
+    }
+
+    /// Draws the world `alpha` of the way between the last two ticks, first
+    /// catching up with any change to the viewport
+    pub fn draw(&mut self, graphics_state: &mut GraphicsState, atlas: &Atlas, alpha: f32) {
+        if let Some(resized) = graphics_state.take_resized() {
+            self.viewport_resized(resized);
+        }
+        systems::render::draw(&self.world, graphics_state, atlas, alpha);
+        if self.show_time_fields {
+            systems::render::draw_time_fields(&self.world, graphics_state);
+        }
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::{Duration, Instant};
+
+const MIN_TIME_SCALE: f32 = 1.0 / 16.0;
+const MAX_TIME_SCALE: f32 = 16.0;
+
+/// Frames longer than this are clamped so that a stall (e.g. dragging the
+/// window) doesn't queue up seconds of ticks that would take even longer to run
+const MAX_FRAME_TIME: Duration = Duration::from_millis(250);
+
+/// Converts real frame times into a whole number of fixed simulation ticks.
+///
+/// Leftover time that isn't enough for a full tick carries over to the next
+/// frame and is exposed as `alpha` so rendering can interpolate between the
+/// last two simulated states.
+///
+/// Only simulated time is affected by the time scale and pausing. The real
+/// frame time is still reported so rendering and UI run at normal speed.
+pub struct GameClock {
+    step: Duration,
+    accumulator: Duration,
+    last_frame: Option<Instant>,
+    frame_time: Duration,
+    time_scale: f32,
+    paused: bool,
+    /// Ticks requested with `step` while paused
+    pending_steps: u32,
+}
+
+impl GameClock {
+    pub fn new(ticks_per_second: u32) -> Self {
+        Self {
+            step: Duration::from_secs(1) / ticks_per_second,
+            accumulator: Duration::ZERO,
+            last_frame: None,
+            frame_time: Duration::ZERO,
+            time_scale: 1.0,
+            paused: false,
+            pending_steps: 0,
+        }
+    }
+
+    pub fn time_scale(&self) -> f32 {
+        self.time_scale
+    }
+
+    /// Sets how many simulated seconds pass per real second, e.g. 0.5 for
+    /// slow motion or 2.0 for fast forward
+    pub fn set_time_scale(&mut self, time_scale: f32) {
+        self.time_scale = time_scale.clamp(MIN_TIME_SCALE, MAX_TIME_SCALE);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        self.pending_steps = 0;
+    }
+
+    /// Runs exactly one tick on the next `advance`. Only has an effect while paused.
+    pub fn step(&mut self) {
+        if self.paused {
+            self.pending_steps += 1;
+        }
+    }
+
+    /// Simulated seconds per tick
+    pub fn step_seconds(&self) -> f32 {
+        self.step.as_secs_f32()
+    }
+
+    /// Simulated time per tick, exactly
+    pub fn step_duration(&self) -> Duration {
+        self.step
+    }
+
+    /// Advances by the real time since the previous call and returns how many
+    /// ticks to simulate. The first call only starts the clock.
+    pub fn advance_to(&mut self, now: Instant) -> u32 {
+        let elapsed = match self.last_frame {
+            Some(last_frame) => now.saturating_duration_since(last_frame),
+            None => Duration::ZERO,
+        };
+        self.last_frame = Some(now);
+        self.advance(elapsed)
+    }
+
+    /// Advances by `elapsed` real time and returns how many ticks to simulate
+    pub fn advance(&mut self, elapsed: Duration) -> u32 {
+        self.frame_time = elapsed.min(MAX_FRAME_TIME);
+
+        if self.paused {
+            return std::mem::take(&mut self.pending_steps);
+        }
+
+        self.accumulator += self.frame_time.mul_f32(self.time_scale);
+
+        let mut ticks = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            ticks += 1;
+        }
+        ticks
+    }
+
+    /// Real seconds covered by the last `advance`, after clamping
+    pub fn frame_seconds(&self) -> f32 {
+        self.frame_time.as_secs_f32()
+    }
+
+    /// How far rendering is between the previous tick and the next one, in [0, 1)
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
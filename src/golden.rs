@@ -0,0 +1,184 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use image::{Rgba, RgbaImage};
+
+/// Set to update the reference images from what is rendered instead of
+/// checking against them
+pub const UPDATE_ENV_VAR: &str = "UPDATE_GOLDEN";
+
+/// How far a rendered image may stray from its reference before the check
+/// fails. Different rasterizers may disagree slightly about rounding and
+/// about which pixels an edge covers.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Tolerance {
+    /// Largest difference allowed in any one channel of a pixel, out of 255
+    pub channel: u8,
+    /// How many pixels may differ by more than `channel`
+    pub pixels: usize,
+}
+
+impl Tolerance {
+    pub const EXACT: Self = Self {
+        channel: 0,
+        pixels: 0,
+    };
+}
+
+/// How two images of the same size differ
+#[derive(Clone, Debug)]
+pub struct ImageDiff {
+    /// Pixels with a channel differing by more than the tolerance
+    pub mismatched: usize,
+    /// Largest difference of any channel of any pixel
+    pub max_difference: u8,
+    /// Mismatched pixels in red, ones that differ within the tolerance in
+    /// yellow, and the rest as a faded gray copy of the expected image
+    pub image: RgbaImage,
+}
+
+impl ImageDiff {
+    pub fn is_within(&self, tolerance: Tolerance) -> bool {
+        self.mismatched <= tolerance.pixels
+    }
+}
+
+/// Compares `actual` with `expected` pixel by pixel, counting pixels that
+/// differ by more than `channel_tolerance` in any channel as mismatched
+pub fn compare(
+    actual: &RgbaImage,
+    expected: &RgbaImage,
+    channel_tolerance: u8,
+) -> anyhow::Result<ImageDiff> {
+    if actual.dimensions() != expected.dimensions() {
+        bail!(
+            "Expected a {}x{} image but got {}x{}",
+            expected.width(),
+            expected.height(),
+            actual.width(),
+            actual.height()
+        );
+    }
+
+    let mut mismatched = 0;
+    let mut max_difference = 0;
+    let image = RgbaImage::from_fn(actual.width(), actual.height(), |x, y| {
+        let actual = actual.get_pixel(x, y);
+        let expected = expected.get_pixel(x, y);
+        let difference = (0..4)
+            .map(|channel| actual[channel].abs_diff(expected[channel]))
+            .max()
+            .unwrap_or(0);
+        max_difference = max_difference.max(difference);
+
+        if difference > channel_tolerance {
+            mismatched += 1;
+            Rgba([255, 0, 0, 255])
+        } else if difference > 0 {
+            Rgba([255, 255, 0, 255])
+        } else {
+            let [r, g, b, _] = expected.0;
+            let luma = (r as u32 * 3 + g as u32 * 6 + b as u32) / 10;
+            let faded = (luma / 4) as u8;
+            Rgba([faded, faded, faded, 255])
+        }
+    });
+
+    Ok(ImageDiff {
+        mismatched,
+        max_difference,
+        image,
+    })
+}
+
+/// Checks rendered images against reference PNGs checked in with the tests.
+/// When a check fails the rendered image and a diff image are written out
+/// for inspection.
+pub struct GoldenImages {
+    /// Directory with a `<name>.png` reference for every check
+    references: PathBuf,
+    /// Directory failed checks write `<name>.actual.png` and
+    /// `<name>.diff.png` to
+    failures: PathBuf,
+    tolerance: Tolerance,
+}
+
+impl GoldenImages {
+    pub fn new(
+        references: impl Into<PathBuf>,
+        failures: impl Into<PathBuf>,
+        tolerance: Tolerance,
+    ) -> Self {
+        Self {
+            references: references.into(),
+            failures: failures.into(),
+            tolerance,
+        }
+    }
+
+    pub fn reference_path(&self, name: &str) -> PathBuf {
+        self.references.join(format!("{}.png", name))
+    }
+
+    /// Fails unless `actual` matches the reference image called `name`
+    /// within the tolerance. Writes `actual` as the new reference instead
+    /// when `UPDATE_GOLDEN` is set.
+    pub fn check(&self, name: &str, actual: &RgbaImage) -> anyhow::Result<()> {
+        let reference_path = self.reference_path(name);
+        if std::env::var_os(UPDATE_ENV_VAR).is_some() {
+            return save_png(actual, &reference_path);
+        }
+
+        if !reference_path.exists() {
+            let actual_path = self.write_failure(name, "actual", actual)?;
+            bail!(
+                "There is no reference image {}. Rendered {}, run with {}=1 to make it the reference.",
+                reference_path.display(),
+                actual_path.display(),
+                UPDATE_ENV_VAR
+            );
+        }
+        let expected = image::open(&reference_path)
+            .with_context(|| format!("Failed to load {}", reference_path.display()))?
+            .to_rgba8();
+
+        let diff = compare(actual, &expected, self.tolerance.channel)
+            .with_context(|| format!("{} doesn't match {}", name, reference_path.display()))?;
+        if diff.is_within(self.tolerance) {
+            return Ok(());
+        }
+
+        let actual_path = self.write_failure(name, "actual", actual)?;
+        let diff_path = self.write_failure(name, "diff", &diff.image)?;
+        bail!(
+            "{} differs from {} in {} pixels (at most {} allowed), by up to {}. \
+             Rendered {}, differences in {}.",
+            name,
+            reference_path.display(),
+            diff.mismatched,
+            self.tolerance.pixels,
+            diff.max_difference,
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+
+    fn write_failure(&self, name: &str, kind: &str, image: &RgbaImage) -> anyhow::Result<PathBuf> {
+        let path = self.failures.join(format!("{}.{}.png", name, kind));
+        save_png(image, &path)?;
+        Ok(path)
+    }
+}
+
+fn save_png(image: &RgbaImage, path: &Path) -> anyhow::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create {}", parent.display()))?;
+    }
+    image
+        .save_with_format(path, image::ImageFormat::Png)
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
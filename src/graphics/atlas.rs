@@ -0,0 +1,290 @@
+use std::{collections::HashMap, path::Path};
+
+use anyhow::{Context, bail};
+use cgmath::Vector2;
+use image::RgbaImage;
+
+use crate::graphics::{
+    texture_registry::{TextureHandle, TextureRegistry},
+    textured_pipeline::UvRect,
+};
+
+/// Pixels of extruded border around each packed image. Prevents linear filtering
+/// from sampling neighboring images at region edges.
+pub const PADDING: u32 = 1;
+
+/// A named sub-rectangle of an atlas texture
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct AtlasRegion {
+    pub texture: TextureHandle,
+    pub uv: UvRect,
+    /// Size of the source image in pixels
+    pub size: Vector2<f32>,
+}
+
+pub struct Atlas {
+    regions: HashMap<String, AtlasRegion>,
+}
+
+impl Atlas {
+    pub fn region(&self, name: &str) -> Option<AtlasRegion> {
+        self.regions.get(name).copied()
+    }
+}
+
+/// Collects images on the CPU so that they can be packed into a single texture
+#[derive(Default)]
+pub struct AtlasBuilder {
+    images: Vec<(String, RgbaImage)>,
+    premultiplied_alpha: bool,
+}
+
+impl AtlasBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Builds an atlas whose color is multiplied by alpha on upload. Regions
+    /// from this atlas should be drawn with `BlendMode::PremultipliedAlpha`,
+    /// which avoids dark fringes where linear filtering meets transparent texels.
+    pub fn premultiplied() -> Self {
+        Self {
+            premultiplied_alpha: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn add_image(&mut self, name: &str, image: RgbaImage) {
+        self.images.push((name.to_owned(), image));
+    }
+
+    /// Loads a PNG or JPEG file from disk into the atlas
+    pub fn add_file(&mut self, name: &str, path: &Path) -> anyhow::Result<()> {
+        let image = image::open(path)
+            .with_context(|| format!("Failed to load atlas image {}", path.display()))?;
+        self.add_image(name, image.to_rgba8());
+        Ok(())
+    }
+
+    /// Packs every added image into one texture and registers it
+    pub fn build(
+        self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        textures: &mut TextureRegistry,
+    ) -> anyhow::Result<Atlas> {
+        let packed = self.pack(device.limits().max_texture_dimension_2d)?;
+        let texture = textures.insert_image(device, queue, &packed.image, "Atlas Texture");
+
+        let regions = packed
+            .regions
+            .into_iter()
+            .map(|region| {
+                let size = Vector2::new(region.width as f32, region.height as f32);
+                let uv = region.uv;
+                (region.name, AtlasRegion { texture, uv, size })
+            })
+            .collect();
+
+        Ok(Atlas { regions })
+    }
+
+    /// Packs every added image into one image no wider or taller than
+    /// `max_size`, without uploading it
+    pub fn pack(self, max_size: u32) -> anyhow::Result<PackedAtlas> {
+        let sizes: Vec<(u32, u32)> = self
+            .images
+            .iter()
+            .map(|(_, image)| (image.width() + 2 * PADDING, image.height() + 2 * PADDING))
+            .collect();
+
+        let Some(packing) = pack(&sizes, max_size) else {
+            bail!(
+                "Failed to pack {} images into a {}x{} atlas",
+                self.images.len(),
+                max_size,
+                max_size
+            );
+        };
+
+        let mut atlas_image = RgbaImage::new(packing.width, packing.height);
+        for ((_, image), position) in self.images.iter().zip(&packing.positions) {
+            blit_extruded(&mut atlas_image, image, position.0, position.1);
+        }
+        if self.premultiplied_alpha {
+            premultiply_alpha(&mut atlas_image);
+        }
+
+        let atlas_size = Vector2::new(packing.width as f32, packing.height as f32);
+        let regions = self
+            .images
+            .into_iter()
+            .zip(&packing.positions)
+            .map(|((name, image), position)| {
+                let (x, y) = (position.0 + PADDING, position.1 + PADDING);
+                let (width, height) = image.dimensions();
+                PackedRegion {
+                    name,
+                    x,
+                    y,
+                    width,
+                    height,
+                    uv: UvRect {
+                        offset: Vector2::new(x as f32 / atlas_size.x, y as f32 / atlas_size.y),
+                        scale: Vector2::new(
+                            width as f32 / atlas_size.x,
+                            height as f32 / atlas_size.y,
+                        ),
+                    },
+                }
+            })
+            .collect();
+
+        Ok(PackedAtlas {
+            image: atlas_image,
+            regions,
+        })
+    }
+}
+
+/// The images of an `AtlasBuilder` packed together, before uploading
+pub struct PackedAtlas {
+    pub image: RgbaImage,
+    /// In the order the images were added
+    pub regions: Vec<PackedRegion>,
+}
+
+/// Where one image ended up in a `PackedAtlas`. The pixel rectangle doesn't
+/// include the `PADDING` around it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PackedRegion {
+    pub name: String,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+    pub uv: UvRect,
+}
+
+struct Packing {
+    width: u32,
+    height: u32,
+    positions: Vec<(u32, u32)>,
+}
+
+/// Shelf packs rectangles, growing the atlas by powers of two until everything
+/// fits or `max_size` is exceeded. Positions are returned in input order.
+fn pack(sizes: &[(u32, u32)], max_size: u32) -> Option<Packing> {
+    let total_area: u64 = sizes.iter().map(|(w, h)| *w as u64 * *h as u64).sum();
+    let widest = sizes.iter().map(|(w, _)| *w).max().unwrap_or(1);
+
+    let mut width = widest
+        .max((total_area as f64).sqrt().ceil() as u32)
+        .next_power_of_two();
+    let mut height = width;
+
+    // Place tall rectangles first so shelves waste less space
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(sizes[i].1));
+
+    while width <= max_size && height <= max_size {
+        if let Some(positions) = pack_shelves(sizes, &order, width, height) {
+            return Some(Packing {
+                width,
+                height,
+                positions,
+            });
+        }
+
+        // Alternate growing height and width to keep the atlas square-ish
+        if height <= width {
+            height *= 2;
+        } else {
+            width *= 2;
+        }
+    }
+
+    None
+}
+
+fn pack_shelves(
+    sizes: &[(u32, u32)],
+    order: &[usize],
+    width: u32,
+    height: u32,
+) -> Option<Vec<(u32, u32)>> {
+    let mut positions = vec![(0, 0); sizes.len()];
+    let mut shelf_x = 0;
+    let mut shelf_y = 0;
+    let mut shelf_height = 0;
+
+    for &index in order {
+        let (w, h) = sizes[index];
+        if w > width {
+            return None;
+        }
+
+        if shelf_x + w > width {
+            shelf_y += shelf_height;
+            shelf_x = 0;
+            shelf_height = 0;
+        }
+        if shelf_y + h > height {
+            return None;
+        }
+
+        positions[index] = (shelf_x, shelf_y);
+        shelf_x += w;
+        shelf_height = shelf_height.max(h);
+    }
+
+    Some(positions)
+}
+
+/// Copies `image` into `atlas` inside a `PADDING` wide border that repeats the
+/// image's edge pixels
+fn blit_extruded(atlas: &mut RgbaImage, image: &RgbaImage, x: u32, y: u32) {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return;
+    }
+
+    for py in 0..height + 2 * PADDING {
+        for px in 0..width + 2 * PADDING {
+            let source_x = px.saturating_sub(PADDING).min(width - 1);
+            let source_y = py.saturating_sub(PADDING).min(height - 1);
+            atlas.put_pixel(x + px, y + py, *image.get_pixel(source_x, source_y));
+        }
+    }
+}
+
+/// Multiplies color by alpha in linear space. The atlas is uploaded as sRGB,
+/// so the GPU decodes texels before filtering and blending, and scaling the
+/// encoded bytes instead would darken every semi-transparent texel.
+fn premultiply_alpha(image: &mut RgbaImage) {
+    let linear: [f32; 256] = std::array::from_fn(|value| srgb_to_linear(value as u8));
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as f32 / 255.0;
+        for channel in &mut pixel.0[..3] {
+            *channel = linear_to_srgb(linear[*channel as usize] * alpha);
+        }
+    }
+}
+
+fn srgb_to_linear(value: u8) -> f32 {
+    let value = value as f32 / 255.0;
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn linear_to_srgb(value: f32) -> u8 {
+    let encoded = if value <= 0.0031308 {
+        value * 12.92
+    } else {
+        1.055 * value.powf(1.0 / 2.4) - 0.055
+    };
+    (encoded * 255.0).round().clamp(0.0, 255.0) as u8
+}
@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use wgpu::{
+    BlendComponent, BlendFactor, BlendOperation, BlendState, ColorTargetState, ColorWrites, Device,
+    Face, FragmentState, FrontFace, MultisampleState, PipelineCompilationOptions, PipelineLayout,
+    PolygonMode, PrimitiveState, PrimitiveTopology, RenderPipeline, RenderPipelineDescriptor,
+    ShaderModule, TextureFormat, VertexBufferLayout, VertexState,
+};
+
+/// How a draw is combined with what is already in the render target
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum BlendMode {
+    /// Overwrites the destination, ignoring alpha
+    Opaque,
+    /// Standard "over" compositing of straight (non-premultiplied) alpha
+    Alpha,
+    /// "Over" compositing of color that has already been multiplied by alpha,
+    /// e.g. sprites from an atlas built with `AtlasBuilder::premultiplied`
+    PremultipliedAlpha,
+    /// Adds the alpha weighted color to the destination. Useful for glows.
+    Additive,
+    /// Multiplies the destination by the color. Useful for shadows and tints.
+    Multiply,
+}
+
+impl BlendMode {
+    pub fn blend_state(self) -> BlendState {
+        match self {
+            BlendMode::Opaque => BlendState::REPLACE,
+            BlendMode::Alpha => BlendState::ALPHA_BLENDING,
+            BlendMode::PremultipliedAlpha => BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Additive => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::SrcAlpha,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent {
+                    src_factor: BlendFactor::Zero,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+            },
+            // Expects premultiplied color so that transparent texels leave the
+            // destination untouched: dst * (src * a + (1 - a))
+            BlendMode::Multiply => BlendState {
+                color: BlendComponent {
+                    src_factor: BlendFactor::Dst,
+                    dst_factor: BlendFactor::OneMinusSrcAlpha,
+                    operation: BlendOperation::Add,
+                },
+                alpha: BlendComponent {
+                    src_factor: BlendFactor::Zero,
+                    dst_factor: BlendFactor::One,
+                    operation: BlendOperation::Add,
+                },
+            },
+        }
+    }
+
+    /// Translucent draws depend on what is behind them, so they must be drawn
+    /// back to front and can't be reordered to improve batching
+    pub fn is_translucent(self) -> bool {
+        self != BlendMode::Opaque
+    }
+
+    /// Whether straight alpha color must be multiplied by alpha before it is
+    /// handed to the blend stage
+    pub fn premultiplies_output(self) -> bool {
+        self == BlendMode::Multiply
+    }
+}
+
+/// Lazily creates and caches one render pipeline per blend mode. All variants
+/// share a shader, layout and vertex buffers and only differ in blending.
+///
+/// The shader's fragment stage must declare `override premultiply_output: bool`
+pub struct BlendPipelines {
+    label: &'static str,
+    shader: ShaderModule,
+    layout: PipelineLayout,
+    vertex_buffers: Vec<VertexBufferLayout<'static>>,
+    format: TextureFormat,
+    pipelines: HashMap<BlendMode, RenderPipeline>,
+}
+
+impl BlendPipelines {
+    pub fn new(
+        label: &'static str,
+        shader: ShaderModule,
+        layout: PipelineLayout,
+        vertex_buffers: Vec<VertexBufferLayout<'static>>,
+        format: TextureFormat,
+    ) -> Self {
+        Self {
+            label,
+            shader,
+            layout,
+            vertex_buffers,
+            format,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    /// Creates the variant for `blend` if it doesn't exist yet
+    pub fn prepare(&mut self, device: &Device, blend: BlendMode) {
+        if self.pipelines.contains_key(&blend) {
+            return;
+        }
+
+        let constants = [(
+            "premultiply_output",
+            if blend.premultiplies_output() {
+                1.0
+            } else {
+                0.0
+            },
+        )];
+        let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some(&format!("{} ({:?})", self.label, blend)),
+            layout: Some(&self.layout),
+            vertex: VertexState {
+                module: &self.shader,
+                entry_point: Some("vs_main"),
+                compilation_options: PipelineCompilationOptions::default(),
+                buffers: &self.vertex_buffers,
+            },
+            fragment: Some(FragmentState {
+                module: &self.shader,
+                entry_point: Some("fs_main"),
+                compilation_options: PipelineCompilationOptions {
+                    constants: &constants,
+                    ..Default::default()
+                },
+                targets: &[Some(ColorTargetState {
+                    format: self.format,
+                    blend: Some(blend.blend_state()),
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: FrontFace::Ccw,
+                cull_mode: Some(Face::Back),
+                unclipped_depth: false,
+                polygon_mode: PolygonMode::Fill,
+                conservative: false,
+            },
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache: None,
+        });
+
+        self.pipelines.insert(blend, pipeline);
+    }
+
+    /// Returns a variant previously created with `prepare`
+    pub fn get(&self, blend: BlendMode) -> &RenderPipeline {
+        &self.pipelines[&blend]
+    }
+}
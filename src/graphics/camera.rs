@@ -1,83 +1,73 @@
-use cgmath::{Matrix4, Point3, SquareMatrix, Vector3, perspective};
+use cgmath::{Matrix4, Rad, Vector2, Vector3};
 
-// TODO: Is this obviously a POD type? If not, we might want to add a "new" method.
-// TODO: delete 3d camera?
-pub struct Camera {
-    pub eye: Point3<f32>,
-    pub target: Point3<f32>,
-    pub up: Vector3<f32>,
-    pub aspect: f32,
-    pub fovy: f32,
-    pub znear: f32,
-    pub zfar: f32,
+/// An orthographic camera over the 2D world.
+///
+/// World space has y pointing up. Screen space is in logical pixels with the
+/// origin at the top left of the window and y pointing down, matching winit.
+#[derive(Copy, Clone, Debug)]
+pub struct Camera2D {
+    /// World position shown at the center of the viewport
+    pub position: Vector2<f32>,
+    /// Screen pixels per world unit
+    pub zoom: f32,
+    pub rotation: Rad<f32>,
+    /// Logical size of the area the camera renders into
+    pub viewport: Vector2<f32>,
 }
 
-const OPEN_GL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
-    1.0, 0.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.5, 0.0, 0.0, 0.0, 0.5, 1.0,
-);
-
-impl Camera {
-    pub fn build_view_projection_matrix(&self) -> Matrix4<f32> {
-        let view = Matrix4::look_at_rh(self.eye, self.target, self.up);
-        let proj = perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
-        OPEN_GL_TO_WGPU_MATRIX * proj * view
-    }
-}
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-pub struct CameraUniform {
-    view_projection: [[f32; 4]; 4],
-}
-
-impl CameraUniform {
-    pub fn new() -> Self {
+impl Camera2D {
+    /// Creates a camera where one world unit is one logical pixel and the
+    /// bottom left of the viewport is the world origin
+    pub fn new(viewport: Vector2<f32>) -> Self {
         Self {
-            view_projection: Matrix4::identity().into(),
+            position: viewport / 2.0,
+            zoom: 1.0,
+            rotation: Rad(0.0),
+            viewport,
         }
     }
 
-    pub fn with_camera(camera: &Camera) -> Self {
-        let mut uniform = Self::new();
-        uniform.update_view_projection(camera);
-        uniform
+    pub fn view_projection_matrix(&self) -> Matrix4<f32> {
+        let projection =
+            Matrix4::from_nonuniform_scale(2.0 / self.viewport.x, 2.0 / self.viewport.y, 1.0);
+        let view = Matrix4::from_scale(self.zoom)
+            * Matrix4::from_angle_z(-self.rotation)
+            * Matrix4::from_translation(Vector3::new(-self.position.x, -self.position.y, 0.0));
+        projection * view
     }
 
-    // TODO: can we just fold this into with_camera?
-    pub fn update_view_projection(&mut self, camera: &Camera) {
-        self.view_projection = camera.build_view_projection_matrix().into();
+    pub fn world_to_screen(&self, world: Vector2<f32>) -> Vector2<f32> {
+        let view = rotate(world - self.position, -self.rotation) * self.zoom;
+        Vector2::new(
+            self.viewport.x / 2.0 + view.x,
+            self.viewport.y / 2.0 - view.y,
+        )
     }
+
+    pub fn screen_to_world(&self, screen: Vector2<f32>) -> Vector2<f32> {
+        let view = Vector2::new(
+            screen.x - self.viewport.x / 2.0,
+            self.viewport.y / 2.0 - screen.y,
+        );
+        self.position + rotate(view / self.zoom, self.rotation)
+    }
+}
+
+fn rotate(v: Vector2<f32>, angle: Rad<f32>) -> Vector2<f32> {
+    let (sin, cos) = angle.0.sin_cos();
+    Vector2::new(v.x * cos - v.y * sin, v.x * sin + v.y * cos)
 }
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Camera2DUniform {
-    projection: [[f32; 4]; 4],
+    view_projection: [[f32; 4]; 4],
 }
 
 impl Camera2DUniform {
-    pub fn new(width: f32, height: f32) -> Self {
-        let mat4 = Matrix4::new(
-            2.0 / width,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            2.0 / height,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            0.0,
-            -1.0,
-            -1.0,
-            0.0,
-            1.0,
-        );
-
+    pub fn from_camera(camera: &Camera2D) -> Self {
         Self {
-            projection: mat4.into(),
+            view_projection: camera.view_projection_matrix().into(),
         }
     }
 }
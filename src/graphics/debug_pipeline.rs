@@ -1,17 +1,19 @@
-use std::mem;
+use std::{mem, ops::Range};
 
-use cgmath::{Matrix3, Vector2};
+use cgmath::Matrix3;
 use wgpu::{
-    BindGroup, BindGroupLayout, BlendState, BufferDescriptor, BufferUsages, ColorTargetState,
-    ColorWrites, CompareFunction, DepthBiasState, DepthStencilState, Device, Face, FragmentState,
-    FrontFace, IndexFormat, MultisampleState, PipelineCompilationOptions, PipelineLayoutDescriptor,
-    PolygonMode, PrimitiveState, PrimitiveTopology, RenderPass, RenderPipeline,
-    RenderPipelineDescriptor, StencilState, SurfaceConfiguration, VertexAttribute,
-    VertexBufferLayout, VertexFormat, VertexState, VertexStepMode,
+    BindGroup, BindGroupLayout, BufferDescriptor, BufferUsages, Device, IndexFormat,
+    PipelineLayoutDescriptor, RenderPass, TextureFormat, VertexAttribute, VertexBufferLayout,
+    VertexFormat, VertexStepMode,
     util::{BufferInitDescriptor, DeviceExt},
 };
 
-use crate::graphics::{common_models::SQUARE_INDICES, shader::load_shader, texture};
+use crate::graphics::{
+    blend::{BlendMode, BlendPipelines},
+    color::Color,
+    common_models::SQUARE_INDICES,
+    shader::load_shader,
+};
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
@@ -61,17 +63,16 @@ const SQUARE_VERTICES: &[Vertex2] = &[
 ];
 
 struct Instance {
-    position: Vector2<f32>,
-    scale: Vector2<f32>,
-    rotation: cgmath::Rad<f32>,
-    color: (f32, f32, f32), // TODO: replace with a codified color struct ?
+    /// Maps the unit shape to world space
+    model: Matrix3<f32>,
+    color: Color,
 }
 
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct InstanceRaw {
     model: [[f32; 3]; 3],
-    color: [f32; 3],
+    color: [f32; 4],
 }
 
 impl InstanceRaw {
@@ -98,7 +99,7 @@ impl InstanceRaw {
                 wgpu::VertexAttribute {
                     offset: mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
                     shader_location: 5,
-                    format: wgpu::VertexFormat::Float32x3,
+                    format: wgpu::VertexFormat::Float32x4,
                 },
             ],
         }
@@ -108,15 +109,31 @@ impl InstanceRaw {
 impl Instance {
     fn to_raw(&self) -> InstanceRaw {
         InstanceRaw {
-            model: (Matrix3::from_translation(self.position)
-                * Matrix3::from_angle_z(self.rotation)
-                * Matrix3::from_nonuniform_scale(self.scale.x, self.scale.y))
-            .into(),
-            color: [self.color.0, self.color.1, self.color.2],
+            model: self.model.into(),
+            color: [self.color.r, self.color.g, self.color.b, self.color.a],
         }
     }
 }
 
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum ShapeKind {
+    Square,
+    Triangle,
+}
+
+struct Shape {
+    kind: ShapeKind,
+    blend: BlendMode,
+    instance: Instance,
+}
+
+/// A run of consecutive instances in one shape's instance buffer that share a blend mode
+struct Batch {
+    kind: ShapeKind,
+    blend: BlendMode,
+    instances: Range<u32>,
+}
+
 struct Squares {
     vertex_buffer: wgpu::Buffer,
     index_buffer: wgpu::Buffer,
@@ -131,9 +148,11 @@ struct Triangles {
 }
 
 pub struct DebugPipeline {
-    pipeline: RenderPipeline,
+    pipelines: BlendPipelines,
     triangles: Triangles,
     squares: Squares,
+    shapes: Vec<Shape>,
+    batches: Vec<Batch>,
 }
 
 impl DebugPipeline {
@@ -142,58 +161,26 @@ impl DebugPipeline {
 
     pub fn new(
         device: &Device,
-        config: &SurfaceConfiguration,
+        format: TextureFormat,
         camera_bind_group_layout: &BindGroupLayout,
     ) -> Self {
-        let pipeline = {
+        let pipelines = {
             let shader = load_shader(device, "debug_shader.wgsl", "Debug pipeline shader");
 
             let pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
                 label: Some("Debug Pipeline Layout"),
-                bind_group_layouts: &[&camera_bind_group_layout],
+                bind_group_layouts: &[camera_bind_group_layout],
                 push_constant_ranges: &[],
             });
-            let pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-                label: Some("Debug Pipeline"),
-                layout: Some(&pipeline_layout),
-                vertex: VertexState {
-                    module: &shader,
-                    entry_point: Some("vs_main"),
-                    compilation_options: PipelineCompilationOptions::default(),
-                    buffers: &[Vertex2::buffer_layout(), InstanceRaw::buffer_layout()],
-                },
-                fragment: Some(FragmentState {
-                    module: &shader,
-                    entry_point: Some("fs_main"),
-                    compilation_options: PipelineCompilationOptions::default(),
-                    targets: &[Some(ColorTargetState {
-                        format: config.format,
-                        blend: Some(BlendState::REPLACE),
-                        write_mask: ColorWrites::ALL,
-                    })],
-                }),
-                primitive: PrimitiveState {
-                    topology: PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: FrontFace::Ccw,
-                    cull_mode: Some(Face::Back),
-                    unclipped_depth: false,
-                    polygon_mode: PolygonMode::Fill,
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview: None,
-                cache: None,
-            });
 
-            pipeline
+            BlendPipelines::new(
+                "Debug Pipeline",
+                shader,
+                pipeline_layout,
+                vec![Vertex2::buffer_layout(), InstanceRaw::buffer_layout()],
+                format,
+            )
         };
-
         let squares = {
             let vertex_buffer = device.create_buffer_init(&BufferInitDescriptor {
                 label: Some("Square Vertex Buffer"),
@@ -240,83 +227,122 @@ impl DebugPipeline {
         };
 
         Self {
-            pipeline,
+            pipelines,
             triangles,
             squares,
+            shapes: vec![],
+            batches: vec![],
         }
     }
 
-    pub fn render(&self, render_pass: &mut RenderPass<'_>, camera_bind_group: &BindGroup) {
-        render_pass.set_pipeline(&self.pipeline);
+    pub fn render(
+        &mut self,
+        device: &Device,
+        queue: &wgpu::Queue,
+        render_pass: &mut RenderPass<'_>,
+        camera_bind_group: &BindGroup,
+    ) {
+        // Opaque shapes are drawn first, grouped by shape. Translucent shapes
+        // follow in the order they were pushed so that later shapes composite
+        // over earlier ones. The sort is stable.
+        self.shapes.sort_by_key(|shape| {
+            let translucent = shape.blend.is_translucent();
+            let kind = if translucent { None } else { Some(shape.kind) };
+            (translucent, kind)
+        });
 
-        render_pass.set_bind_group(0, camera_bind_group, &[]);
+        // Write shapes to instance buffers
+        for shape in &self.shapes {
+            let (instance_buffer, num_instances, max_instances) = match shape.kind {
+                ShapeKind::Square => (
+                    &self.squares.instance_buffer,
+                    &mut self.squares.num_instances,
+                    Self::MAX_SQUARES,
+                ),
+                ShapeKind::Triangle => (
+                    &self.triangles.instance_buffer,
+                    &mut self.triangles.num_instances,
+                    Self::MAX_TRIANGLES,
+                ),
+            };
+            assert!(
+                (*num_instances as usize) < max_instances,
+                "Exceeded maximum number of debug instances"
+            );
 
-        // Draw debug squares
-        {
-            render_pass.set_vertex_buffer(0, self.squares.vertex_buffer.slice(..));
-            render_pass.set_index_buffer(self.squares.index_buffer.slice(..), IndexFormat::Uint32);
-            render_pass.set_vertex_buffer(1, self.squares.instance_buffer.slice(..));
-            render_pass.draw_indexed(0..6, 0, 0..self.squares.num_instances);
+            let instance_index = *num_instances;
+            queue.write_buffer(
+                instance_buffer,
+                (instance_index as usize * mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+                bytemuck::cast_slice(&[shape.instance.to_raw()]),
+            );
+            *num_instances += 1;
+
+            match self.batches.last_mut() {
+                Some(batch) if batch.kind == shape.kind && batch.blend == shape.blend => {
+                    batch.instances.end = instance_index + 1;
+                }
+                _ => self.batches.push(Batch {
+                    kind: shape.kind,
+                    blend: shape.blend,
+                    instances: instance_index..instance_index + 1,
+                }),
+            }
         }
 
-        // Draw debug triangle
-        {
-            render_pass.set_vertex_buffer(0, self.triangles.vertex_buffer.slice(..));
-            render_pass.set_vertex_buffer(1, self.triangles.instance_buffer.slice(..));
-            render_pass.draw(0..3, 0..self.triangles.num_instances);
+        for batch in &self.batches {
+            self.pipelines.prepare(device, batch.blend);
+        }
+
+        let mut current_blend = None;
+        for batch in &self.batches {
+            if current_blend != Some(batch.blend) {
+                render_pass.set_pipeline(self.pipelines.get(batch.blend));
+                render_pass.set_bind_group(0, camera_bind_group, &[]);
+                current_blend = Some(batch.blend);
+            }
+
+            match batch.kind {
+                ShapeKind::Square => {
+                    render_pass.set_vertex_buffer(0, self.squares.vertex_buffer.slice(..));
+                    render_pass
+                        .set_index_buffer(self.squares.index_buffer.slice(..), IndexFormat::Uint32);
+                    render_pass.set_vertex_buffer(1, self.squares.instance_buffer.slice(..));
+                    render_pass.draw_indexed(0..6, 0, batch.instances.clone());
+                }
+                ShapeKind::Triangle => {
+                    render_pass.set_vertex_buffer(0, self.triangles.vertex_buffer.slice(..));
+                    render_pass.set_vertex_buffer(1, self.triangles.instance_buffer.slice(..));
+                    render_pass.draw(0..3, batch.instances.clone());
+                }
+            }
         }
     }
 
-    pub fn push_square(
-        &mut self,
-        queue: &wgpu::Queue,
-        position: Vector2<f32>,
-        scale: Vector2<f32>,
-        rotation: f32,
-        color: (f32, f32, f32),
-    ) {
-        let instance = Instance {
-            position,
-            scale,
-            rotation: cgmath::Rad(rotation),
-            color,
-        };
-        queue.write_buffer(
-            &self.squares.instance_buffer,
-            (self.squares.num_instances as usize * mem::size_of::<InstanceRaw>())
-                as wgpu::BufferAddress,
-            bytemuck::cast_slice(&[instance.to_raw()]),
-        );
-        self.squares.num_instances += 1;
+    /// Draws a unit square mapped to world space by `model`
+    pub fn push_square(&mut self, model: Matrix3<f32>, color: Color, blend: BlendMode) {
+        self.shapes.push(Shape {
+            kind: ShapeKind::Square,
+            blend,
+            instance: Instance { model, color },
+        });
     }
 
-    pub fn push_triangle(
-        &mut self,
-        queue: &wgpu::Queue,
-        position: Vector2<f32>,
-        scale: Vector2<f32>,
-        rotation: f32,
-        color: (f32, f32, f32),
-    ) {
-        let instance = Instance {
-            position,
-            scale,
-            rotation: cgmath::Rad(rotation),
-            color,
-        };
-        queue.write_buffer(
-            &self.triangles.instance_buffer,
-            (self.triangles.num_instances as usize * mem::size_of::<InstanceRaw>())
-                as wgpu::BufferAddress,
-            bytemuck::cast_slice(&[instance.to_raw()]),
-        );
-        self.triangles.num_instances += 1;
+    /// Draws a unit triangle mapped to world space by `model`
+    pub fn push_triangle(&mut self, model: Matrix3<f32>, color: Color, blend: BlendMode) {
+        self.shapes.push(Shape {
+            kind: ShapeKind::Triangle,
+            blend,
+            instance: Instance { model, color },
+        });
     }
 
     /// Sets the number of instances for all debug models to 0. This does not
     /// free the buffers associated with the instances. Typically used for a push/clear
     /// render cycle.
     pub fn clear_instances(&mut self) {
+        self.shapes.clear();
+        self.batches.clear();
         self.squares.num_instances = 0;
         self.triangles.num_instances = 0;
     }
@@ -13,12 +13,12 @@ struct InstanceInput {
     @location(2) model_matrix_0: vec3<f32>,
     @location(3) model_matrix_1: vec3<f32>,
     @location(4) model_matrix_2: vec3<f32>,
-    @location(5) color: vec3<f32>,
+    @location(5) color: vec4<f32>,
 }
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
-    @location(0) color: vec3<f32>,
+    @location(0) color: vec4<f32>,
 };
 
 @vertex
@@ -42,7 +42,13 @@ fn vs_main(
 
 // Fragment shader
 
+// Set per pipeline for blend modes that expect premultiplied color
+override premultiply_output: bool = false;
+
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0);
+    if premultiply_output {
+        return vec4<f32>(in.color.rgb * in.color.a, in.color.a);
+    }
+    return in.color;
 }
@@ -1,38 +1,73 @@
+pub mod atlas;
+pub mod blend;
 pub mod camera;
+pub mod color;
 pub mod common_models; // TODO: probably don't reexport this
 mod debug_pipeline;
+pub mod render_error;
+mod render_target;
 mod shader;
 mod texture;
+pub mod texture_registry;
 pub mod textured_pipeline; // TODO: probably don't reexport this
 
-use std::sync::Arc;
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
 
-use anyhow::Context;
-use cgmath::Vector2;
+use anyhow::{Context, bail};
+use cgmath::{Matrix3, Rad, Vector2};
+use image::RgbaImage;
 use wgpu::{
-    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
+    Adapter, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayoutDescriptor,
     BindGroupLayoutEntry, BindingType, BufferBindingType, BufferUsages, CommandEncoderDescriptor,
     LoadOp, PowerPreference, RenderPassColorAttachment, RenderPassDescriptor,
-    RequestAdapterOptions, ShaderStages, StoreOp, Surface, SurfaceConfiguration, TextureUsages,
-    TextureViewDescriptor,
+    RequestAdapterOptions, ShaderStages, StoreOp, SurfaceConfiguration, TextureUsages,
     util::{BufferInitDescriptor, DeviceExt},
 };
-use winit::{dpi::LogicalSize, window::Window};
+use winit::{
+    dpi::{LogicalSize, PhysicalSize},
+    window::Window,
+};
 
 use crate::graphics::{
-    camera::Camera2DUniform,
+    atlas::{Atlas, AtlasBuilder},
+    blend::BlendMode,
+    camera::{Camera2D, Camera2DUniform},
+    color::Color,
     debug_pipeline::DebugPipeline,
+    render_error::RenderError,
+    render_target::RenderTarget,
+    texture_registry::TextureHandle,
     textured_pipeline::{TexturedPipeline, TexturedQuad},
 };
 
+/// Sent to game code when the logical size of the window changes, either from
+/// a resize or from the window moving to a display with a different scale factor
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ViewportResized {
+    pub logical_size: LogicalSize<f32>,
+    pub scale_factor: f64,
+}
+
 pub struct GraphicsState {
-    window: Arc<Window>,
-    surface: Surface<'static>,
+    target: RenderTarget,
+    /// The window the target's surface belongs to, or `None` offscreen
+    window: Option<Arc<Window>>,
     device: wgpu::Device,
     queue: wgpu::Queue,
-    config: SurfaceConfiguration,
-
-    camera: Camera2DUniform,
+    /// Written by the device lost callback, which may run on another thread
+    device_lost: Arc<Mutex<Option<String>>>,
+    /// Returned in place of the next frame, see `simulate_surface_error`
+    surface_error: Option<wgpu::SurfaceError>,
+    scale_factor: f64,
+    /// Set while the window has a zero sized client area. Nothing is rendered.
+    minimized: bool,
+    /// Starts out as the initial viewport so the first reader lays out for it
+    resized: Option<ViewportResized>,
+
+    camera: Camera2D,
     camera_buffer: wgpu::Buffer,
     camera_bind_group: BindGroup,
 
@@ -58,16 +93,7 @@ impl GraphicsState {
             })
             .await?;
 
-        let (device, queue) = adapter
-            .request_device(&wgpu::DeviceDescriptor {
-                label: None,
-                required_features: wgpu::Features::empty(),
-                required_limits: wgpu::Limits::default(),
-                experimental_features: wgpu::ExperimentalFeatures::disabled(),
-                memory_hints: Default::default(),
-                trace: wgpu::Trace::Off,
-            })
-            .await?;
+        let (device, queue) = Self::request_device(&adapter).await?;
 
         let surface_capabilities = surface.get_capabilities(&adapter);
 
@@ -84,11 +110,13 @@ impl GraphicsState {
 
         let window_size = window.inner_size();
 
+        // The window may start minimized. Surfaces can't be zero sized, so
+        // configure a 1x1 surface until the first real resize.
         let config = SurfaceConfiguration {
             usage: TextureUsages::RENDER_ATTACHMENT,
             format: surface_format,
-            width: window_size.width,
-            height: window_size.height,
+            width: window_size.width.max(1),
+            height: window_size.height.max(1),
             present_mode: surface_capabilities.present_modes[0],
             desired_maximum_frame_latency: 2,
             alpha_mode: surface_capabilities.alpha_modes[0],
@@ -97,14 +125,85 @@ impl GraphicsState {
 
         surface.configure(&device, &config);
 
-        let window_size = window.inner_size();
-        let scale_factor = window.scale_factor();
-        let logical_size: LogicalSize<f32> = window_size.to_logical(scale_factor);
+        let target = RenderTarget::Surface { surface, config };
+        let mut graphics_state = Self::with_target(device, queue, target, window.scale_factor());
+        graphics_state.window = Some(window);
+        graphics_state.minimized = window_size.width == 0 || window_size.height == 0;
+        Ok(graphics_state)
+    }
+
+    /// Draws into a `width` by `height` texture instead of a window, e.g. for
+    /// screenshots and tests. Prefers a software adapter, so that it works
+    /// without a GPU and draws the same on every machine, and uses whatever
+    /// adapter there is when there's no software one.
+    pub async fn offscreen(width: u32, height: u32) -> anyhow::Result<Self> {
+        if width == 0 || height == 0 {
+            bail!("Can't render to a {}x{} target", width, height);
+        }
+
+        // Software adapters are often only available through GL
+        let instance = wgpu::Instance::new(&wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            ..Default::default()
+        });
+        let mut options = RequestAdapterOptions {
+            power_preference: PowerPreference::default(),
+            force_fallback_adapter: true,
+            compatible_surface: None,
+        };
+        let adapter = match instance.request_adapter(&options).await {
+            Ok(adapter) => adapter,
+            Err(_) => {
+                options.force_fallback_adapter = false;
+                instance
+                    .request_adapter(&options)
+                    .await
+                    .context("Found no graphics adapter to render offscreen with")?
+            }
+        };
+
+        let (device, queue) = Self::request_device(&adapter).await?;
+        let target = RenderTarget::offscreen(&device, width, height);
+        Ok(Self::with_target(device, queue, target, 1.0))
+    }
 
-        let camera = Camera2DUniform::new(logical_size.width, logical_size.height);
+    async fn request_device(adapter: &Adapter) -> anyhow::Result<(wgpu::Device, wgpu::Queue)> {
+        let device = adapter
+            .request_device(&wgpu::DeviceDescriptor {
+                label: None,
+                required_features: wgpu::Features::empty(),
+                required_limits: wgpu::Limits::default(),
+                experimental_features: wgpu::ExperimentalFeatures::disabled(),
+                memory_hints: Default::default(),
+                trace: wgpu::Trace::Off,
+            })
+            .await?;
+        Ok(device)
+    }
+
+    /// Sets up the camera and pipelines to draw into `target`
+    fn with_target(
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        target: RenderTarget,
+        scale_factor: f64,
+    ) -> Self {
+        let device_lost = Arc::new(Mutex::new(None));
+        {
+            let device_lost = device_lost.clone();
+            device.set_device_lost_callback(move |reason, message| {
+                *device_lost.lock().unwrap() = Some(format!("{:?}: {}", reason, message));
+            });
+        }
+
+        let (width, height) = target.size();
+        let logical_size: LogicalSize<f32> =
+            PhysicalSize::new(width, height).to_logical(scale_factor);
+
+        let camera = Camera2D::new(Vector2::new(logical_size.width, logical_size.height));
         let camera_buffer = device.create_buffer_init(&BufferInitDescriptor {
             label: Some("Debug Camera Buffer"),
-            contents: bytemuck::cast_slice(&[camera]),
+            contents: bytemuck::cast_slice(&[Camera2DUniform::from_camera(&camera)]),
             usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
         });
 
@@ -132,48 +231,157 @@ impl GraphicsState {
             }],
         });
 
-        let textured_pipeline =
-            TexturedPipeline::new(&device, &queue, &camera_bind_group_layout, &config)
-                .context("Failed to make textured pipeline")?;
-        let debug_pipeline = DebugPipeline::new(&device, &config, &camera_bind_group_layout);
+        let format = target.format();
+        let textured_pipeline = TexturedPipeline::new(&device, &camera_bind_group_layout, format);
+        let debug_pipeline = DebugPipeline::new(&device, format, &camera_bind_group_layout);
 
-        Ok(Self {
-            window,
-            surface,
+        Self {
+            target,
+            window: None,
             device,
             queue,
-            config,
+            device_lost,
+            surface_error: None,
+            scale_factor,
+            minimized: false,
+            resized: Some(ViewportResized {
+                logical_size,
+                scale_factor,
+            }),
             camera,
             camera_buffer,
             camera_bind_group,
             textured_pipeline,
             debug_pipeline,
-        })
+        }
+    }
+
+    /// Rebuilds every GPU resource on a new device, e.g. after the device was
+    /// lost. The camera, scale factor, size and minimized state are carried
+    /// over so the view doesn't jump. Textures have to be loaded again.
+    pub async fn recreate(self) -> anyhow::Result<Self> {
+        let camera = self.camera;
+        let scale_factor = self.scale_factor;
+        let (width, height) = self.target.size();
+        let minimized = self.minimized;
+        let window = self.window.clone();
+        // Some backends refuse a second surface on a window whose swapchain
+        // is still alive
+        drop(self);
+
+        let mut graphics_state = match window {
+            Some(window) => Self::new(window).await?,
+            None => Self::offscreen(width, height).await?,
+        };
+        graphics_state.scale_factor_changed(scale_factor);
+        if minimized {
+            graphics_state.resize(0, 0);
+        } else {
+            graphics_state.resize(width, height);
+        }
+        let viewport = graphics_state.camera.viewport;
+        graphics_state.camera = Camera2D { viewport, ..camera };
+        Ok(graphics_state)
+    }
+
+    /// Makes the next `render` report that the device was lost, as if the
+    /// driver had reset, to exercise recovery without a real device loss
+    pub fn simulate_device_lost(&self, message: &str) {
+        *self.device_lost.lock().unwrap() = Some(message.to_string());
+    }
+
+    /// Makes acquiring the next frame fail with `error`, to exercise how
+    /// `render` recovers from surface problems on any target
+    pub fn simulate_surface_error(&mut self, error: wgpu::SurfaceError) {
+        self.surface_error = Some(error);
     }
 
     pub fn get_logical_size(&self) -> LogicalSize<f32> {
-        let window_size = self.window.inner_size();
-        let scale_factor = self.window.scale_factor();
-        window_size.to_logical(scale_factor)
+        let (width, height) = self.target.size();
+        PhysicalSize::new(width, height).to_logical(self.scale_factor)
+    }
+
+    /// Returns the most recent viewport change since the last call, if any.
+    /// The first call returns the initial viewport.
+    pub fn take_resized(&mut self) -> Option<ViewportResized> {
+        self.resized.take()
+    }
+
+    pub fn scale_factor(&self) -> f64 {
+        self.scale_factor
+    }
+
+    /// Size of the target in physical pixels. Keeps the last non-zero size
+    /// while minimized.
+    pub fn physical_size(&self) -> PhysicalSize<u32> {
+        let (width, height) = self.target.size();
+        PhysicalSize::new(width, height)
+    }
+
+    pub fn is_minimized(&self) -> bool {
+        self.minimized
+    }
+
+    pub fn camera(&self) -> &Camera2D {
+        &self.camera
+    }
+
+    pub fn camera_mut(&mut self) -> &mut Camera2D {
+        &mut self.camera
     }
 
     pub fn resize(&mut self, width: u32, height: u32) {
-        // TODO: is it possible to get zero size?
-        self.config.width = width;
-        self.config.height = height;
-        self.surface.configure(&self.device, &self.config);
-        // TODO: need to reacquire logical size of the window?
+        // Minimized windows report a zero size, which can't be used to configure a surface
+        self.minimized = width == 0 || height == 0;
+        if self.minimized {
+            return;
+        }
+
+        self.target.resize(&self.device, width, height);
+        self.update_viewport();
     }
 
-    pub fn render(&mut self) -> anyhow::Result<()> {
-        let output = self
-            .surface
-            .get_current_texture()
-            .with_context(|| "Failed to get current texture on render")?;
+    pub fn scale_factor_changed(&mut self, scale_factor: f64) {
+        self.scale_factor = scale_factor;
+        if !self.minimized {
+            self.update_viewport();
+        }
+    }
+
+    /// Fits the camera's projection to the current logical size of the window
+    fn update_viewport(&mut self) {
+        let logical_size = self.get_logical_size();
+        self.camera.viewport = Vector2::new(logical_size.width, logical_size.height);
+        self.resized = Some(ViewportResized {
+            logical_size,
+            scale_factor: self.scale_factor,
+        });
+    }
 
-        let view = output
-            .texture
-            .create_view(&TextureViewDescriptor::default());
+    /// Draws everything pushed since the last `clear_instances`. Surface
+    /// problems that can be fixed by reconfiguring or waiting skip the frame
+    /// and return `Ok`.
+    pub fn render(&mut self) -> Result<(), RenderError> {
+        if let Some(message) = self.device_lost.lock().unwrap().take() {
+            return Err(RenderError::DeviceLost(message));
+        }
+
+        if self.minimized {
+            return Ok(());
+        }
+
+        if let Some(error) = self.surface_error.take() {
+            return self.target.recover(&self.device, error);
+        }
+        let Some(frame) = self.target.begin_frame(&self.device)? else {
+            return Ok(());
+        };
+
+        self.queue.write_buffer(
+            &self.camera_buffer,
+            0,
+            bytemuck::cast_slice(&[Camera2DUniform::from_camera(&self.camera)]),
+        );
 
         let mut encoder = self
             .device
@@ -185,7 +393,7 @@ impl GraphicsState {
             let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
                 label: Some("Render pass"),
                 color_attachments: &[Some(RenderPassColorAttachment {
-                    view: &view,
+                    view: &frame.view,
                     resolve_target: None,
                     ops: wgpu::Operations {
                         load: LoadOp::Clear(wgpu::Color {
@@ -204,20 +412,63 @@ impl GraphicsState {
             });
 
             self.textured_pipeline.render(
-                &mut self.queue,
+                &self.device,
+                &self.queue,
                 &mut render_pass,
                 &self.camera_bind_group,
             );
 
-            self.debug_pipeline
-                .render(&mut render_pass, &self.camera_bind_group);
+            self.debug_pipeline.render(
+                &self.device,
+                &self.queue,
+                &mut render_pass,
+                &self.camera_bind_group,
+            );
         }
 
         self.queue.submit(std::iter::once(encoder.finish()));
-        output.present();
+        self.target.end_frame(&self.device, frame);
+
         Ok(())
     }
 
+    /// Copies the last rendered frame back to the CPU. Only works for
+    /// graphics created with `offscreen`.
+    pub fn read_pixels(&self) -> anyhow::Result<RgbaImage> {
+        self.target.read_pixels(&self.device, &self.queue)
+    }
+
+    /// Writes the last rendered frame to a PNG file at `path`. Only works for
+    /// graphics created with `offscreen`.
+    pub fn save_png(&self, path: &Path) -> anyhow::Result<()> {
+        self.read_pixels()?
+            .save_with_format(path, image::ImageFormat::Png)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Loads a PNG or JPEG file so that it can be drawn with `push_textured_quad`
+    pub fn load_texture(&mut self, path: &Path) -> anyhow::Result<TextureHandle> {
+        self.textured_pipeline
+            .load_texture(&self.device, &self.queue, path)
+    }
+
+    /// Size of a loaded texture in texels, or `None` if it was released
+    pub fn texture_size(&self, texture: TextureHandle) -> Option<(u32, u32)> {
+        self.textured_pipeline.texture_size(texture)
+    }
+
+    /// Frees a texture loaded with `load_texture`. Quads still using it are
+    /// skipped when drawn. Returns false if it was already released.
+    pub fn release_texture(&mut self, texture: TextureHandle) -> bool {
+        self.textured_pipeline.release_texture(texture)
+    }
+
+    /// Packs the builder's images into a single texture with named regions
+    pub fn build_atlas(&mut self, builder: AtlasBuilder) -> anyhow::Result<Atlas> {
+        self.textured_pipeline
+            .build_atlas(&self.device, &self.queue, builder)
+    }
+
     pub fn push_textured_quad(&mut self, quad: TexturedQuad) {
         self.textured_pipeline.push_textured_quad(quad)
     }
@@ -227,10 +478,11 @@ impl GraphicsState {
         position: Vector2<f32>,
         scale: Vector2<f32>,
         rotation: f32,
-        color: (f32, f32, f32),
+        color: Color,
+        blend: BlendMode,
     ) {
         self.debug_pipeline
-            .push_square(&self.queue, position, scale, rotation, color);
+            .push_square(model_matrix(position, scale, rotation), color, blend);
     }
 
     pub fn push_debug_triangle(
@@ -238,10 +490,43 @@ impl GraphicsState {
         position: Vector2<f32>,
         scale: Vector2<f32>,
         rotation: f32,
-        color: (f32, f32, f32),
+        color: Color,
+        blend: BlendMode,
     ) {
         self.debug_pipeline
-            .push_triangle(&self.queue, position, scale, rotation, color);
+            .push_triangle(model_matrix(position, scale, rotation), color, blend);
+    }
+
+    /// Draws a square of `scale` placed by `transform`, e.g. an entity's
+    /// world matrix
+    pub fn push_debug_square_transformed(
+        &mut self,
+        transform: Matrix3<f32>,
+        scale: Vector2<f32>,
+        color: Color,
+        blend: BlendMode,
+    ) {
+        self.debug_pipeline.push_square(
+            transform * Matrix3::from_nonuniform_scale(scale.x, scale.y),
+            color,
+            blend,
+        );
+    }
+
+    /// Draws a triangle of `scale` placed by `transform`, e.g. an entity's
+    /// world matrix
+    pub fn push_debug_triangle_transformed(
+        &mut self,
+        transform: Matrix3<f32>,
+        scale: Vector2<f32>,
+        color: Color,
+        blend: BlendMode,
+    ) {
+        self.debug_pipeline.push_triangle(
+            transform * Matrix3::from_nonuniform_scale(scale.x, scale.y),
+            color,
+            blend,
+        );
     }
 
     pub fn clear_instances(&mut self) {
@@ -249,3 +534,10 @@ impl GraphicsState {
         self.debug_pipeline.clear_instances();
     }
 }
+
+/// Scales, then rotates by `rotation` radians, then translates to `position`
+fn model_matrix(position: Vector2<f32>, scale: Vector2<f32>, rotation: f32) -> Matrix3<f32> {
+    Matrix3::from_translation(position)
+        * Matrix3::from_angle_z(Rad(rotation))
+        * Matrix3::from_nonuniform_scale(scale.x, scale.y)
+}
@@ -0,0 +1,28 @@
+use std::fmt;
+
+/// Errors from `GraphicsState::render` that the app has to act on. Recoverable
+/// surface problems (lost, outdated, timeout) are handled inside `render` and
+/// never reach the caller.
+#[derive(Debug)]
+pub enum RenderError {
+    /// The GPU is out of memory. The app should exit.
+    OutOfMemory,
+    /// The device was lost, e.g. from a driver reset. Every pipeline and GPU
+    /// resource is invalid and the graphics state has to be recreated.
+    DeviceLost(String),
+    /// Acquiring the frame failed for a reason wgpu doesn't categorize. The
+    /// frame was skipped.
+    Surface(wgpu::SurfaceError),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::OutOfMemory => write!(f, "Out of GPU memory"),
+            RenderError::DeviceLost(message) => write!(f, "Graphics device lost: {}", message),
+            RenderError::Surface(error) => write!(f, "Failed to acquire frame: {}", error),
+        }
+    }
+}
+
+impl std::error::Error for RenderError {}
@@ -0,0 +1,202 @@
+use std::sync::mpsc;
+
+use anyhow::{Context, bail};
+use image::RgbaImage;
+use wgpu::{
+    BufferDescriptor, BufferUsages, COPY_BYTES_PER_ROW_ALIGNMENT, CommandEncoderDescriptor, Device,
+    Extent3d, MapMode, Origin3d, PollType, Queue, Surface, SurfaceConfiguration, SurfaceError,
+    SurfaceTexture, TexelCopyBufferInfo, TexelCopyBufferLayout, TexelCopyTextureInfo,
+    TextureAspect, TextureDescriptor, TextureDimension, TextureFormat, TextureUsages, TextureView,
+    TextureViewDescriptor,
+};
+
+use crate::graphics::render_error::RenderError;
+
+/// Format of offscreen targets. sRGB like the window's surface, so both
+/// blend the same and read back as ordinary image colors.
+const OFFSCREEN_FORMAT: TextureFormat = TextureFormat::Rgba8UnormSrgb;
+
+/// What `GraphicsState` draws into: a window's surface, or a texture that
+/// can be read back to the CPU
+pub(crate) enum RenderTarget {
+    Surface {
+        surface: Surface<'static>,
+        config: SurfaceConfiguration,
+    },
+    Offscreen(wgpu::Texture),
+}
+
+/// The texture a frame is being drawn into
+pub(crate) struct Frame {
+    pub view: TextureView,
+    /// Has to be presented once drawn, when drawing to a surface
+    surface_texture: Option<SurfaceTexture>,
+}
+
+impl RenderTarget {
+    pub fn offscreen(device: &Device, width: u32, height: u32) -> Self {
+        Self::Offscreen(device.create_texture(&TextureDescriptor {
+            label: Some("Offscreen Target"),
+            size: Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: OFFSCREEN_FORMAT,
+            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC,
+            view_formats: &[],
+        }))
+    }
+
+    pub fn format(&self) -> TextureFormat {
+        match self {
+            Self::Surface { config, .. } => config.format,
+            Self::Offscreen(texture) => texture.format(),
+        }
+    }
+
+    /// Size in physical pixels
+    pub fn size(&self) -> (u32, u32) {
+        match self {
+            Self::Surface { config, .. } => (config.width, config.height),
+            Self::Offscreen(texture) => (texture.width(), texture.height()),
+        }
+    }
+
+    /// Takes a non-zero size in physical pixels. Offscreen targets get a new
+    /// texture, so their contents are lost.
+    pub fn resize(&mut self, device: &Device, width: u32, height: u32) {
+        match self {
+            Self::Surface { surface, config } => {
+                config.width = width;
+                config.height = height;
+                surface.configure(device, config);
+            }
+            Self::Offscreen(_) => *self = Self::offscreen(device, width, height),
+        }
+    }
+
+    /// Rebuilds the swap chain at its current size. Offscreen targets get a
+    /// new texture, like when resized.
+    pub fn reconfigure(&mut self, device: &Device) {
+        let (width, height) = self.size();
+        self.resize(device, width, height);
+    }
+
+    /// Deals with an error from acquiring a frame. Problems that can be fixed
+    /// by reconfiguring or waiting return `Ok` and the frame is skipped.
+    pub fn recover(&mut self, device: &Device, error: SurfaceError) -> Result<(), RenderError> {
+        match error {
+            // The swap chain no longer matches the window, e.g. after alt-tab or
+            // a monitor change. Rebuild it and try again next frame.
+            SurfaceError::Lost | SurfaceError::Outdated => {
+                self.reconfigure(device);
+                Ok(())
+            }
+            // The compositor didn't hand us a frame in time. Try again next frame.
+            SurfaceError::Timeout => Ok(()),
+            SurfaceError::OutOfMemory => Err(RenderError::OutOfMemory),
+            error @ SurfaceError::Other => Err(RenderError::Surface(error)),
+        }
+    }
+
+    /// The texture to draw the next frame into, or `None` to skip the frame
+    /// after recovering from a surface problem
+    pub fn begin_frame(&mut self, device: &Device) -> Result<Option<Frame>, RenderError> {
+        let (texture, surface_texture) = match self {
+            Self::Surface { surface, .. } => match surface.get_current_texture() {
+                Ok(output) => (output.texture.clone(), Some(output)),
+                Err(error) => {
+                    self.recover(device, error)?;
+                    return Ok(None);
+                }
+            },
+            Self::Offscreen(texture) => (texture.clone(), None),
+        };
+        Ok(Some(Frame {
+            view: texture.create_view(&TextureViewDescriptor::default()),
+            surface_texture,
+        }))
+    }
+
+    /// Shows a frame whose commands have been submitted
+    pub fn end_frame(&mut self, device: &Device, frame: Frame) {
+        let Some(output) = frame.surface_texture else {
+            return;
+        };
+        let suboptimal = output.suboptimal;
+        output.present();
+        if suboptimal {
+            self.reconfigure(device);
+        }
+    }
+
+    /// Copies what was last drawn to an offscreen target back to the CPU.
+    /// Blocks until the GPU has finished drawing it.
+    pub fn read_pixels(&self, device: &Device, queue: &Queue) -> anyhow::Result<RgbaImage> {
+        let Self::Offscreen(texture) = self else {
+            bail!("Only offscreen targets can be read back");
+        };
+        let (width, height) = (texture.width(), texture.height());
+
+        // Rows of a texture copy have to start at aligned offsets
+        let row_bytes = 4 * width;
+        let padded_row_bytes =
+            row_bytes.div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT;
+        let buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Readback Buffer"),
+            size: (padded_row_bytes * height) as wgpu::BufferAddress,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Readback Encoder"),
+        });
+        encoder.copy_texture_to_buffer(
+            TexelCopyTextureInfo {
+                texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            TexelCopyBufferInfo {
+                buffer: &buffer,
+                layout: TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row_bytes),
+                    rows_per_image: Some(height),
+                },
+            },
+            texture.size(),
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let (sender, receiver) = mpsc::channel();
+        buffer.map_async(MapMode::Read, .., move |result| {
+            // The receiver is still waiting below, so sending can't fail
+            let _ = sender.send(result);
+        });
+        device
+            .poll(PollType::wait_indefinitely())
+            .context("Failed to wait for the GPU")?;
+        receiver
+            .recv()
+            .context("Readback was dropped")?
+            .context("Failed to map the readback buffer")?;
+
+        let mut pixels = Vec::with_capacity((row_bytes * height) as usize);
+        {
+            let mapped = buffer.get_mapped_range(..);
+            for row in mapped.chunks(padded_row_bytes as usize) {
+                pixels.extend_from_slice(&row[..row_bytes as usize]);
+            }
+        }
+        buffer.unmap();
+
+        RgbaImage::from_raw(width, height, pixels).context("Readback has the wrong size")
+    }
+}
@@ -2,12 +2,16 @@ use std::{env, fs::File, io::Read, path::Path};
 
 use wgpu::{self, ShaderModule, ShaderModuleDescriptor, ShaderSource};
 
+/// Where shaders are read from when `SHADER_SOURCE_DIR` isn't set
+const DEFAULT_SHADER_SOURCE_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/graphics");
+
 pub fn load_shader(
     device: &wgpu::Device,
     shader_file_name: &str,
     shader_label: &str,
 ) -> ShaderModule {
-    let shader_source_dir = env::var("SHADER_SOURCE_DIR").unwrap();
+    let shader_source_dir =
+        env::var("SHADER_SOURCE_DIR").unwrap_or_else(|_| DEFAULT_SHADER_SOURCE_DIR.to_string());
     let shader_path = Path::new(&shader_source_dir).join(shader_file_name);
     let mut shader_source_file = File::open(shader_path).unwrap();
 
@@ -14,12 +14,16 @@ struct InstanceInput {
     @location(2) model_matrix_0: vec3<f32>,
     @location(3) model_matrix_1: vec3<f32>,
     @location(4) model_matrix_2: vec3<f32>,
+    @location(5) uv_offset: vec2<f32>,
+    @location(6) uv_scale: vec2<f32>,
+    @location(7) tint: vec4<f32>,
 }
 
 
 struct VertexOutput {
     @builtin(position) clip_position: vec4<f32>,
     @location(0) tex_coords: vec2<f32>,
+    @location(1) tint: vec4<f32>,
 };
 
 @vertex
@@ -33,7 +37,8 @@ fn vs_main(
     );
 
     var out: VertexOutput;
-    out.tex_coords = model.tex_coords;
+    out.tex_coords = instance.uv_offset + model.tex_coords * instance.uv_scale;
+    out.tint = instance.tint;
 
     let position = model_matrix * vec3<f32>(model.position.x, model.position.y, 1.0);
     out.clip_position = camera.view_proj * vec4<f32>(position.x, position.y, 0.0, 1.0);
@@ -41,11 +46,19 @@ fn vs_main(
 }
 
 // Fragment shader
+
+// Set per pipeline for blend modes that expect premultiplied color
+override premultiply_output: bool = false;
+
 @group(0) @binding(0)
 var t_diffuse: texture_2d<f32>;
 @group(0) @binding(1)
 var s_diffuse: sampler;
 @fragment
 fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
-    return textureSample(t_diffuse, s_diffuse, in.tex_coords);
+    let color = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.tint;
+    if premultiply_output {
+        return vec4<f32>(color.rgb * color.a, color.a);
+    }
+    return color;
 }
@@ -1,6 +1,8 @@
+use image::RgbaImage;
 use wgpu::{
-    AddressMode, CompareFunction, Device, Extent3d, FilterMode, SamplerDescriptor,
-    SurfaceConfiguration,
+    AddressMode, Device, Extent3d, FilterMode, Origin3d, Queue, SamplerDescriptor,
+    TexelCopyBufferLayout, TexelCopyTextureInfo, TextureAspect, TextureDescriptor,
+    TextureDimension, TextureFormat, TextureUsages, TextureViewDescriptor,
 };
 
 pub struct Texture {
@@ -10,43 +12,51 @@ pub struct Texture {
 }
 
 impl Texture {
-    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
-
-    pub fn create_depth_texture(
-        device: &Device,
-        config: &SurfaceConfiguration,
-        label: &str,
-    ) -> Self {
+    /// Uploads an RGBA image into a new sampled texture
+    pub fn from_image(device: &Device, queue: &Queue, image: &RgbaImage, label: &str) -> Self {
+        let dimensions = image.dimensions();
         let size = Extent3d {
-            width: config.width.max(1),
-            height: config.height.max(1),
+            width: dimensions.0,
+            height: dimensions.1,
             depth_or_array_layers: 1,
         };
 
-        let desc = wgpu::TextureDescriptor {
+        let texture = device.create_texture(&TextureDescriptor {
             label: Some(label),
             size,
             mip_level_count: 1,
             sample_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: Self::DEPTH_FORMAT,
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8UnormSrgb,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
             view_formats: &[],
-        };
+        });
 
-        let texture = device.create_texture(&desc);
+        queue.write_texture(
+            TexelCopyTextureInfo {
+                texture: &texture,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            image,
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * dimensions.0),
+                rows_per_image: Some(dimensions.1),
+            },
+            size,
+        );
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let view = texture.create_view(&TextureViewDescriptor::default());
         let sampler = device.create_sampler(&SamplerDescriptor {
+            label: Some(label),
             address_mode_u: AddressMode::ClampToEdge,
             address_mode_v: AddressMode::ClampToEdge,
             address_mode_w: AddressMode::ClampToEdge,
             mag_filter: FilterMode::Linear,
-            min_filter: FilterMode::Linear,
+            min_filter: FilterMode::Nearest,
             mipmap_filter: FilterMode::Nearest,
-            lod_min_clamp: 0.0,
-            lod_max_clamp: 100.0,
-            compare: Some(CompareFunction::LessEqual),
             ..Default::default()
         });
 
@@ -0,0 +1,216 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Context;
+use wgpu::{
+    BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout, BindGroupLayoutDescriptor,
+    BindGroupLayoutEntry, BindingResource, BindingType, Device, Queue, SamplerBindingType,
+    ShaderStages, TextureSampleType, TextureViewDimension,
+};
+
+use crate::graphics::texture::Texture;
+
+/// Identifies a texture loaded into a `TextureRegistry`. Handles are only
+/// meaningful for the registry that created them. Like entities, they carry
+/// a generation so handles to released textures never alias newer ones.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TextureHandle {
+    index: u32,
+    generation: u32,
+}
+
+impl TextureHandle {
+    /// Slot in the registry
+    pub fn index(self) -> usize {
+        self.index as usize
+    }
+
+    pub fn generation(self) -> u32 {
+        self.generation
+    }
+}
+
+struct RegisteredTexture {
+    texture: Texture,
+    bind_group: BindGroup,
+}
+
+/// A texture slot, empty once its texture is released
+struct Slot {
+    generation: u32,
+    texture: Option<RegisteredTexture>,
+}
+
+/// Owns every texture the textured pipeline can draw with, along with the
+/// bind group used to sample each one.
+pub struct TextureRegistry {
+    bind_group_layout: BindGroupLayout,
+    slots: Vec<Slot>,
+    /// Released slots, reused last in, first out
+    free: Vec<u32>,
+    /// Files already loaded, so loading one again shares its texture
+    paths: HashMap<PathBuf, TextureHandle>,
+}
+
+impl TextureRegistry {
+    pub fn new(device: &Device) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Texture Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: TextureViewDimension::D2,
+                        sample_type: TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Sampler(SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        Self {
+            bind_group_layout,
+            slots: vec![],
+            free: vec![],
+            paths: HashMap::new(),
+        }
+    }
+
+    pub fn bind_group_layout(&self) -> &BindGroupLayout {
+        &self.bind_group_layout
+    }
+
+    /// Loads a PNG or JPEG file from disk. Loading a file that is already
+    /// loaded returns the same handle.
+    pub fn load(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        path: &Path,
+    ) -> anyhow::Result<TextureHandle> {
+        if let Some(&handle) = self.paths.get(path) {
+            return Ok(handle);
+        }
+        let bytes = std::fs::read(path)
+            .with_context(|| format!("Failed to read texture file {}", path.display()))?;
+        let handle = self.load_from_memory(device, queue, &bytes, &path.display().to_string())?;
+        self.paths.insert(path.to_path_buf(), handle);
+        Ok(handle)
+    }
+
+    /// Decodes an encoded image (PNG, JPEG) already in memory
+    pub fn load_from_memory(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        bytes: &[u8],
+        label: &str,
+    ) -> anyhow::Result<TextureHandle> {
+        let image = image::load_from_memory(bytes)
+            .with_context(|| format!("Failed to decode texture {}", label))?;
+        Ok(self.insert_image(device, queue, &image.to_rgba8(), label))
+    }
+
+    pub fn insert_image(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        image: &image::RgbaImage,
+        label: &str,
+    ) -> TextureHandle {
+        let texture = Texture::from_image(device, queue, image, label);
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some(label),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&texture.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: BindingResource::Sampler(&texture.sampler),
+                },
+            ],
+        });
+
+        let registered = Some(RegisteredTexture {
+            texture,
+            bind_group,
+        });
+        let index = match self.free.pop() {
+            Some(index) => {
+                self.slots[index as usize].texture = registered;
+                index
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    texture: registered,
+                });
+                self.slots.len() as u32 - 1
+            }
+        };
+        TextureHandle {
+            index,
+            generation: self.slots[index as usize].generation,
+        }
+    }
+
+    /// Drops the texture and frees its slot for reuse. Returns false if the
+    /// handle was already released or never belonged to this registry.
+    pub fn release(&mut self, handle: TextureHandle) -> bool {
+        if !self.contains(handle) {
+            return false;
+        }
+        let slot = &mut self.slots[handle.index()];
+        slot.texture = None;
+        slot.generation += 1;
+        self.free.push(handle.index);
+        self.paths.retain(|_, loaded| *loaded != handle);
+        true
+    }
+
+    pub fn contains(&self, handle: TextureHandle) -> bool {
+        self.get(handle).is_some()
+    }
+
+    /// Number of textures currently loaded
+    pub fn len(&self) -> usize {
+        self.slots.len() - self.free.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get(&self, handle: TextureHandle) -> Option<&RegisteredTexture> {
+        self.slots
+            .get(handle.index())
+            .filter(|slot| slot.generation == handle.generation)?
+            .texture
+            .as_ref()
+    }
+
+    /// The bind group to sample the texture with, or `None` for released or
+    /// unknown handles
+    pub fn bind_group(&self, handle: TextureHandle) -> Option<&BindGroup> {
+        Some(&self.get(handle)?.bind_group)
+    }
+
+    /// Size of the texture in texels
+    pub fn size(&self, handle: TextureHandle) -> Option<(u32, u32)> {
+        let texture = &self.get(handle)?.texture.texture;
+        Some((texture.width(), texture.height()))
+    }
+}
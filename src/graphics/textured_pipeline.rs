@@ -1,26 +1,22 @@
-use std::mem;
+use std::{mem, ops::Range, path::Path};
 
-use anyhow::Context;
 use cgmath::{Matrix3, Vector2};
-use image::GenericImageView;
 use wgpu::{
-    AddressMode, BindGroup, BindGroupDescriptor, BindGroupEntry, BindGroupLayout,
-    BindGroupLayoutDescriptor, BindGroupLayoutEntry, BindingResource, BindingType, BlendState,
-    BufferDescriptor, BufferUsages, ColorTargetState, ColorWrites, Extent3d, Face, FilterMode,
-    FragmentState, FrontFace, IndexFormat, MultisampleState, Origin3d, PipelineCompilationOptions,
-    PipelineLayoutDescriptor, PolygonMode, PrimitiveState, PrimitiveTopology, RenderPass,
-    RenderPipeline, RenderPipelineDescriptor, SamplerBindingType, ShaderStages,
-    SurfaceConfiguration, TexelCopyBufferLayout, TexelCopyTextureInfo, TextureAspect,
-    TextureDimension, TextureFormat, TextureSampleType, TextureUsages, TextureViewDescriptor,
-    TextureViewDimension, VertexAttribute, VertexBufferLayout, VertexFormat, VertexState,
-    VertexStepMode,
+    BindGroup, BindGroupLayout, BufferDescriptor, BufferUsages, IndexFormat,
+    PipelineLayoutDescriptor, RenderPass, TextureFormat, VertexAttribute, VertexBufferLayout,
+    VertexFormat, VertexStepMode,
     util::{BufferInitDescriptor, DeviceExt},
-    wgt::{SamplerDescriptor, TextureDescriptor},
 };
 
-use crate::graphics::{common_models::SQUARE_INDICES, shader::load_shader};
+use crate::graphics::{
+    atlas::{Atlas, AtlasBuilder},
+    blend::{BlendMode, BlendPipelines},
+    color::Color,
+    common_models::SQUARE_INDICES,
+    shader::load_shader,
+    texture_registry::{TextureHandle, TextureRegistry},
+};
 
-const MAX_TRIANGLES: usize = 128;
 const MAX_QUADS: usize = 1024;
 
 #[repr(C)]
@@ -51,21 +47,6 @@ impl Vertex2 {
     }
 }
 
-pub const TRIANGLE_VERTICES: &[Vertex2] = &[
-    Vertex2 {
-        position: [0.0, 0.5],
-        tex_coords: [0.0, 0.0], // Debug code, not currently set
-    },
-    Vertex2 {
-        position: [-0.5, -0.5],
-        tex_coords: [0.0, 0.0],
-    },
-    Vertex2 {
-        position: [0.5, -0.5],
-        tex_coords: [0.0, 0.0],
-    },
-];
-
 pub const SQUARE_VERTICES: &[Vertex2] = &[
     Vertex2 {
         position: [-0.5, 0.5],
@@ -85,10 +66,28 @@ pub const SQUARE_VERTICES: &[Vertex2] = &[
     },
 ];
 
-pub struct TexturedInstance {
-    pub position: Vector2<f32>,
+/// The sub-rectangle of a texture sampled by a quad, in normalized texture coordinates
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UvRect {
+    pub offset: Vector2<f32>,
     pub scale: Vector2<f32>,
-    pub rotation: cgmath::Rad<f32>,
+}
+
+impl UvRect {
+    /// Samples the entire texture
+    pub const FULL: UvRect = UvRect {
+        offset: Vector2::new(0.0, 0.0),
+        scale: Vector2::new(1.0, 1.0),
+    };
+}
+
+pub struct TexturedInstance {
+    /// Maps the unit quad to world space
+    pub model: Matrix3<f32>,
+    pub uv: UvRect,
+    /// Multiplied with the sampled color. Must already be premultiplied when
+    /// the texture is.
+    pub tint: Color,
 }
 
 // TODO: does this need to be public?
@@ -96,6 +95,9 @@ pub struct TexturedInstance {
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 struct InstanceRaw {
     model: [[f32; 3]; 3],
+    uv_offset: [f32; 2],
+    uv_scale: [f32; 2],
+    tint: [f32; 4],
 }
 
 impl InstanceRaw {
@@ -119,6 +121,21 @@ impl InstanceRaw {
                     shader_location: 4,
                     format: wgpu::VertexFormat::Float32x3,
                 },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 9]>() as wgpu::BufferAddress,
+                    shader_location: 5,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 11]>() as wgpu::BufferAddress,
+                    shader_location: 6,
+                    format: wgpu::VertexFormat::Float32x2,
+                },
+                wgpu::VertexAttribute {
+                    offset: mem::size_of::<[f32; 13]>() as wgpu::BufferAddress,
+                    shader_location: 7,
+                    format: wgpu::VertexFormat::Float32x4,
+                },
             ],
         }
     }
@@ -127,17 +144,16 @@ impl InstanceRaw {
 impl TexturedInstance {
     fn to_raw(&self) -> InstanceRaw {
         InstanceRaw {
-            model: (Matrix3::from_translation(self.position)
-                * Matrix3::from_angle_z(self.rotation)
-                * Matrix3::from_nonuniform_scale(self.scale.x, self.scale.y))
-            .into(),
+            model: self.model.into(),
+            uv_offset: self.uv.offset.into(),
+            uv_scale: self.uv.scale.into(),
+            tint: [self.tint.r, self.tint.g, self.tint.b, self.tint.a],
         }
     }
 }
 
 struct Model {
     vertex_buffer: wgpu::Buffer,
-    num_vertices: u32,
     index_buffer: wgpu::Buffer,
     num_indices: u32,
     instance_buffer: wgpu::Buffer,
@@ -147,167 +163,60 @@ struct Model {
 
 #[derive(Copy, Clone)]
 pub struct TexturedQuad {
-    pub position: Vector2<f32>,
+    /// Places the quad's center in the world, e.g. an entity's world matrix
+    pub transform: Matrix3<f32>,
+    /// Size of the quad before `transform` is applied
     pub dimensions: Vector2<f32>,
     pub layer: u32, // NOTE: layers will be sorted from smallest to largest
-                    // TODO: we need a texture handle
+    pub texture: TextureHandle,
+    pub uv: UvRect,
+    /// Multiplied with the sampled color, e.g. to fade or recolor a sprite.
+    /// Fading only shows with a translucent blend mode.
+    pub tint: Color,
+    pub blend: BlendMode,
+}
+
+/// A run of consecutive instances in the quad instance buffer that share a
+/// texture and blend mode
+struct Batch {
+    texture: TextureHandle,
+    blend: BlendMode,
+    instances: Range<u32>,
 }
 
 pub struct TexturedPipeline {
-    render_pipeline: RenderPipeline,
+    render_pipelines: BlendPipelines,
     models: Vec<Model>,
-    diffuse_bind_group: BindGroup,
+    textures: TextureRegistry,
     quad_index: usize,
     textured_quads: Vec<TexturedQuad>,
+    batches: Vec<Batch>,
 }
 
 impl TexturedPipeline {
     pub fn new(
         device: &wgpu::Device,
-        queue: &wgpu::Queue,
         camera_bind_group_layout: &BindGroupLayout,
-        config: &SurfaceConfiguration,
-    ) -> anyhow::Result<Self> {
-        // TODO: textures should come from a load function just like shaders do
-        let (texture_bind_group_layout, diffuse_bind_group) = {
-            let diffuse_bytes = include_bytes!("../../data/happy-tree.png");
-            let diffuse_image =
-                image::load_from_memory(diffuse_bytes).context("Failed to load texture")?;
-            let diffuse_rgba = diffuse_image.to_rgba8();
-            let dimensions = diffuse_image.dimensions();
-            let texture_size = Extent3d {
-                width: dimensions.0,
-                height: dimensions.1,
-                depth_or_array_layers: 1,
-            };
-            let diffuse_texture = device.create_texture(&TextureDescriptor {
-                label: Some("Diffuse Texture"),
-                size: texture_size,
-                mip_level_count: 1,
-                sample_count: 1,
-                dimension: TextureDimension::D2,
-                format: TextureFormat::Rgba8UnormSrgb,
-                usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
-                view_formats: &[],
-            });
+        format: TextureFormat,
+    ) -> Self {
+        let textures = TextureRegistry::new(device);
 
-            queue.write_texture(
-                TexelCopyTextureInfo {
-                    texture: &diffuse_texture,
-                    mip_level: 0,
-                    origin: Origin3d::ZERO,
-                    aspect: TextureAspect::All,
-                },
-                &diffuse_rgba,
-                TexelCopyBufferLayout {
-                    offset: 0,
-                    bytes_per_row: Some(4 * dimensions.0),
-                    rows_per_image: Some(dimensions.1),
-                },
-                texture_size,
-            );
-
-            let diffuse_texture_view =
-                diffuse_texture.create_view(&TextureViewDescriptor::default());
-            let diffuse_sampler = device.create_sampler(&SamplerDescriptor {
-                label: Some("Diffuse Sampler"),
-                address_mode_u: AddressMode::ClampToEdge,
-                address_mode_v: AddressMode::ClampToEdge,
-                address_mode_w: AddressMode::ClampToEdge,
-                mag_filter: FilterMode::Linear,
-                min_filter: FilterMode::Nearest,
-                mipmap_filter: FilterMode::Nearest,
-                ..Default::default()
-            });
-
-            let texture_bind_group_layout =
-                device.create_bind_group_layout(&BindGroupLayoutDescriptor {
-                    label: Some("Texture Bind Group Layout"),
-                    entries: &[
-                        BindGroupLayoutEntry {
-                            binding: 0,
-                            visibility: ShaderStages::FRAGMENT,
-                            ty: BindingType::Texture {
-                                multisampled: false,
-                                view_dimension: TextureViewDimension::D2,
-                                sample_type: TextureSampleType::Float { filterable: true },
-                            },
-                            count: None,
-                        },
-                        BindGroupLayoutEntry {
-                            binding: 1,
-                            visibility: ShaderStages::FRAGMENT,
-                            ty: BindingType::Sampler(SamplerBindingType::Filtering),
-                            count: None,
-                        },
-                    ],
-                });
-            let diffuse_bind_group = device.create_bind_group(&BindGroupDescriptor {
-                label: Some("Diffuse Bind Group"),
-                layout: &texture_bind_group_layout,
-                entries: &[
-                    BindGroupEntry {
-                        binding: 0,
-                        resource: BindingResource::TextureView(&diffuse_texture_view),
-                    },
-                    BindGroupEntry {
-                        binding: 1,
-                        resource: BindingResource::Sampler(&diffuse_sampler),
-                    },
-                ],
-            });
-
-            (texture_bind_group_layout, diffuse_bind_group)
-        };
-
-        let render_pipeline = {
-            let shader = load_shader(&device, "shader.wgsl", "Render pipeline shader");
+        let render_pipelines = {
+            let shader = load_shader(device, "shader.wgsl", "Render pipeline shader");
 
             let render_pipeline_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
                 label: Some("Render Pipeline Layout"),
-                bind_group_layouts: &[&texture_bind_group_layout, &camera_bind_group_layout],
+                bind_group_layouts: &[textures.bind_group_layout(), camera_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
-            let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
-                label: Some("Render Pipeline"),
-                layout: Some(&render_pipeline_layout),
-                vertex: VertexState {
-                    module: &shader,
-                    entry_point: Some("vs_main"),
-                    compilation_options: PipelineCompilationOptions::default(),
-                    buffers: &[Vertex2::buffer_layout(), InstanceRaw::buffer_layout()],
-                },
-                fragment: Some(FragmentState {
-                    module: &shader,
-                    entry_point: Some("fs_main"),
-                    compilation_options: PipelineCompilationOptions::default(),
-                    targets: &[Some(ColorTargetState {
-                        format: config.format,
-                        blend: Some(BlendState::REPLACE),
-                        write_mask: ColorWrites::ALL,
-                    })],
-                }),
-                primitive: PrimitiveState {
-                    topology: PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: FrontFace::Ccw,
-                    cull_mode: Some(Face::Back),
-                    unclipped_depth: false,
-                    polygon_mode: PolygonMode::Fill,
-                    conservative: false,
-                },
-                depth_stencil: None,
-                multisample: MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
-                multiview: None,
-                cache: None,
-            });
-
-            render_pipeline
+            BlendPipelines::new(
+                "Render Pipeline",
+                shader,
+                render_pipeline_layout,
+                vec![Vertex2::buffer_layout(), InstanceRaw::buffer_layout()],
+                format,
+            )
         };
 
         let mut models = vec![];
@@ -320,53 +229,120 @@ impl TexturedPipeline {
             MAX_QUADS,
         );
 
-        Ok(Self {
-            render_pipeline,
+        Self {
+            render_pipelines,
             models,
-            diffuse_bind_group,
+            textures,
             quad_index,
             textured_quads: vec![],
-        })
+            batches: vec![],
+        }
+    }
+
+    pub fn load_texture(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+    ) -> anyhow::Result<TextureHandle> {
+        self.textures.load(device, queue, path)
+    }
+
+    pub fn texture_size(&self, texture: TextureHandle) -> Option<(u32, u32)> {
+        self.textures.size(texture)
+    }
+
+    pub fn release_texture(&mut self, texture: TextureHandle) -> bool {
+        self.textures.release(texture)
+    }
+
+    pub fn build_atlas(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        builder: AtlasBuilder,
+    ) -> anyhow::Result<Atlas> {
+        builder.build(device, queue, &mut self.textures)
     }
 
     pub fn render(
         &mut self,
+        device: &wgpu::Device,
         queue: &wgpu::Queue,
         render_pass: &mut RenderPass<'_>,
         camera_bind_group: &BindGroup,
     ) {
         // Write quads to instance buffers
         {
-            // Sort the quads by their layers
-            self.textured_quads.sort_by_key(|k| k.layer);
+            // Sort the quads by their layers so that higher layers are drawn on
+            // top. Within a layer, opaque quads are drawn first and grouped by
+            // texture to reduce bind group changes. Translucent quads are drawn
+            // afterwards in the order they were pushed, since reordering them
+            // would change how they composite. The sort is stable.
+            self.textured_quads.sort_by_key(|k| {
+                let translucent = k.blend.is_translucent();
+                let texture = if translucent { None } else { Some(k.texture) };
+                (k.layer, translucent, texture)
+            });
 
             // Write quads to instance buffers
             for quad in &self.textured_quads {
-                Self::add_instance(
+                let instance_index = Self::add_instance(
                     &mut self.models,
                     queue,
                     self.quad_index,
                     TexturedInstance {
-                        position: quad.position,
-                        scale: quad.dimensions,
-                        rotation: cgmath::Rad(0.0),
+                        model: quad.transform
+                            * Matrix3::from_nonuniform_scale(quad.dimensions.x, quad.dimensions.y),
+                        uv: quad.uv,
+                        // Premultiplied textures need a premultiplied tint
+                        // to fade evenly
+                        tint: if quad.blend == BlendMode::PremultipliedAlpha {
+                            quad.tint.premultiplied()
+                        } else {
+                            quad.tint
+                        },
                     },
                 );
+
+                match self.batches.last_mut() {
+                    Some(batch) if batch.texture == quad.texture && batch.blend == quad.blend => {
+                        batch.instances.end = instance_index + 1;
+                    }
+                    _ => self.batches.push(Batch {
+                        texture: quad.texture,
+                        blend: quad.blend,
+                        instances: instance_index..instance_index + 1,
+                    }),
+                }
             }
         }
 
+        for batch in &self.batches {
+            self.render_pipelines.prepare(device, batch.blend);
+        }
+
         // Buffers are now set. Make render calls
         {
-            render_pass.set_pipeline(&self.render_pipeline);
-            // TODO: move this bind group set into the loop?
-            render_pass.set_bind_group(0, &self.diffuse_bind_group, &[]);
-            render_pass.set_bind_group(1, camera_bind_group, &[]);
-
-            for model in &self.models {
-                render_pass.set_vertex_buffer(0, model.vertex_buffer.slice(..));
-                render_pass.set_index_buffer(model.index_buffer.slice(..), IndexFormat::Uint32);
-                render_pass.set_vertex_buffer(1, model.instance_buffer.slice(..));
-                render_pass.draw_indexed(0..model.num_indices, 0, 0..model.num_instances);
+            let model = &self.models[self.quad_index];
+            render_pass.set_vertex_buffer(0, model.vertex_buffer.slice(..));
+            render_pass.set_index_buffer(model.index_buffer.slice(..), IndexFormat::Uint32);
+            render_pass.set_vertex_buffer(1, model.instance_buffer.slice(..));
+
+            let mut current_blend = None;
+            for batch in &self.batches {
+                // Quads whose texture was released since they were pushed
+                let Some(bind_group) = self.textures.bind_group(batch.texture) else {
+                    continue;
+                };
+                if current_blend != Some(batch.blend) {
+                    render_pass.set_pipeline(self.render_pipelines.get(batch.blend));
+                    render_pass.set_bind_group(1, camera_bind_group, &[]);
+                    current_blend = Some(batch.blend);
+                }
+
+                render_pass.set_bind_group(0, bind_group, &[]);
+                render_pass.draw_indexed(0..model.num_indices, 0, batch.instances.clone());
             }
         }
     }
@@ -399,7 +375,6 @@ impl TexturedPipeline {
         let model_index = models.len();
         models.push(Model {
             vertex_buffer,
-            num_vertices: vertices.len() as u32,
             index_buffer,
             num_indices: indices.len() as u32,
             instance_buffer,
@@ -410,32 +385,34 @@ impl TexturedPipeline {
         model_index
     }
 
+    /// Writes an instance to the model's instance buffer and returns its index
     // TODO: maybe reallocate instance buffer if we exceed max instances?
     fn add_instance(
-        models: &mut Vec<Model>,
+        models: &mut [Model],
         queue: &wgpu::Queue,
         model_index: usize,
         instance: TexturedInstance,
-    ) {
-        if let Some(model) = models.get_mut(model_index) {
-            assert!(
-                (model.num_instances as usize) < model.max_instances,
-                "Exceeded maximum number of instances for model"
-            );
-
-            queue.write_buffer(
-                &model.instance_buffer,
-                (model.num_instances as usize * mem::size_of::<InstanceRaw>())
-                    as wgpu::BufferAddress,
-                bytemuck::cast_slice(&[instance.to_raw()]),
-            );
-            model.num_instances += 1;
-        }
+    ) -> u32 {
+        let model = &mut models[model_index];
+        assert!(
+            (model.num_instances as usize) < model.max_instances,
+            "Exceeded maximum number of instances for model"
+        );
+
+        queue.write_buffer(
+            &model.instance_buffer,
+            (model.num_instances as usize * mem::size_of::<InstanceRaw>()) as wgpu::BufferAddress,
+            bytemuck::cast_slice(&[instance.to_raw()]),
+        );
+        model.num_instances += 1;
+
+        model.num_instances - 1
     }
 
     /// Clears push buffers in preparation for next frame update
     pub fn clear_instances(&mut self) {
         self.textured_quads.clear();
+        self.batches.clear();
         self.models[self.quad_index].num_instances = 0;
     }
 
@@ -0,0 +1,62 @@
+use cgmath::{InnerSpace, Vector2};
+
+use crate::{app_state::AppState, ecs::entity::Entity, game::Game, world::World};
+
+/// Panics unless `actual` is within `tolerance` world units of `expected`
+#[track_caller]
+pub fn assert_near(actual: Vector2<f32>, expected: Vector2<f32>, tolerance: f32) {
+    let distance = (actual - expected).magnitude();
+    assert!(
+        distance <= tolerance,
+        "Expected ({}, {}) but got ({}, {}), {} away",
+        expected.x,
+        expected.y,
+        actual.x,
+        actual.y,
+        distance
+    );
+}
+
+/// Panics unless `entity` is within `tolerance` world units of `expected`
+#[track_caller]
+pub fn assert_entity_at(world: &World, entity: Entity, expected: Vector2<f32>, tolerance: f32) {
+    let Some(position) = world.world_position(entity) else {
+        panic!("{:?} has no position", entity);
+    };
+    assert_near(position, expected, tolerance);
+}
+
+/// Panics unless the player is within `tolerance` world units of `expected`
+#[track_caller]
+pub fn assert_player_at(game: &Game, expected: Vector2<f32>, tolerance: f32) {
+    assert_entity_at(&game.world, game.player(), expected, tolerance);
+}
+
+/// Runs `app` a frame at a time until `condition` holds and returns how many
+/// frames that took. Panics with `what` if it still doesn't after `frames`.
+#[track_caller]
+pub fn run_until(
+    app: &mut AppState,
+    frames: u32,
+    what: &str,
+    mut condition: impl FnMut(&Game) -> bool,
+) -> u32 {
+    for ran in 0..=frames {
+        if condition(&app.game) {
+            return ran;
+        }
+        if ran < frames {
+            app.run_frames(1);
+        }
+    }
+    panic!("Expected {} within {} frames", what, frames);
+}
+
+/// Plays `app`'s script to its last event, then runs `extra` more frames
+/// for it to take effect
+pub fn finish_script(app: &mut AppState, extra: u32) {
+    while !app.script_finished() {
+        app.run_frames(1);
+    }
+    app.run_frames(extra);
+}
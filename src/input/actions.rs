@@ -0,0 +1,107 @@
+use winit::keyboard::KeyCode;
+
+use super::{
+    gamepad::{GamepadAxis, GamepadButton},
+    map::{Axis, Binding, Input, InputMap},
+};
+
+pub const MOVE_UP: &str = "move_up";
+pub const MOVE_DOWN: &str = "move_down";
+pub const MOVE_LEFT: &str = "move_left";
+pub const MOVE_RIGHT: &str = "move_right";
+pub const REWIND: &str = "rewind";
+pub const SPAWN_ECHO: &str = "spawn_echo";
+
+pub const QUIT: &str = "quit";
+pub const PAUSE: &str = "pause";
+pub const STEP: &str = "step";
+pub const SLOW_DOWN: &str = "slow_down";
+pub const SPEED_UP: &str = "speed_up";
+pub const RESET_SPEED: &str = "reset_speed";
+pub const TOGGLE_TIME_FIELDS: &str = "toggle_time_fields";
+pub const SAVE_SCENE: &str = "save_scene";
+pub const QUICKSAVE: &str = "quicksave";
+pub const QUICKLOAD: &str = "quickload";
+pub const REBIND: &str = "rebind";
+pub const RESET_CONTROLS: &str = "reset_controls";
+pub const CAMERA_UP: &str = "camera_up";
+pub const CAMERA_DOWN: &str = "camera_down";
+pub const CAMERA_LEFT: &str = "camera_left";
+pub const CAMERA_RIGHT: &str = "camera_right";
+pub const ZOOM: &str = "zoom";
+
+/// The actions `Game` acts on. They drive the simulation, so they are
+/// replayed along with it.
+pub fn game_defaults() -> InputMap {
+    let mut map = InputMap::new();
+    map.bind(MOVE_UP, Binding::key(KeyCode::ArrowUp));
+    map.bind(MOVE_DOWN, Binding::key(KeyCode::ArrowDown));
+    map.bind(MOVE_LEFT, Binding::key(KeyCode::ArrowLeft));
+    map.bind(MOVE_RIGHT, Binding::key(KeyCode::ArrowRight));
+    map.bind(REWIND, Binding::key(KeyCode::KeyR));
+    map.bind(SPAWN_ECHO, Binding::key(KeyCode::KeyE));
+
+    map.bind(MOVE_UP, gamepad(GamepadButton::DPadUp));
+    map.bind(MOVE_DOWN, gamepad(GamepadButton::DPadDown));
+    map.bind(MOVE_LEFT, gamepad(GamepadButton::DPadLeft));
+    map.bind(MOVE_RIGHT, gamepad(GamepadButton::DPadRight));
+    map.bind(MOVE_UP, push(GamepadAxis::LeftStickY, true));
+    map.bind(MOVE_DOWN, push(GamepadAxis::LeftStickY, false));
+    map.bind(MOVE_LEFT, push(GamepadAxis::LeftStickX, false));
+    map.bind(MOVE_RIGHT, push(GamepadAxis::LeftStickX, true));
+    map.bind(REWIND, gamepad(GamepadButton::West));
+    map.bind(REWIND, push(GamepadAxis::RightTrigger, true));
+    map.bind(SPAWN_ECHO, gamepad(GamepadButton::North));
+    map
+}
+
+/// The actions `AppState` acts on. They only change how the game is shown
+/// and run, so replays leave them to live input.
+pub fn app_defaults() -> InputMap {
+    let mut map = InputMap::new();
+    map.bind(QUIT, Binding::key(KeyCode::Escape));
+    map.bind(PAUSE, Binding::key(KeyCode::KeyP));
+    map.bind(STEP, Binding::key(KeyCode::Period));
+    map.bind(SLOW_DOWN, Binding::key(KeyCode::Minus));
+    map.bind(SPEED_UP, Binding::key(KeyCode::Equal));
+    map.bind(RESET_SPEED, Binding::key(KeyCode::Digit0));
+    map.bind(TOGGLE_TIME_FIELDS, Binding::key(KeyCode::F3));
+    map.bind(SAVE_SCENE, Binding::key(KeyCode::F5));
+    map.bind(QUICKSAVE, Binding::key(KeyCode::F6));
+    for control in [KeyCode::ControlLeft, KeyCode::ControlRight] {
+        map.bind(
+            QUICKSAVE,
+            Binding::chord([Input::Key(control), Input::Key(KeyCode::KeyS)]),
+        );
+    }
+    map.bind(QUICKLOAD, Binding::key(KeyCode::F9));
+    map.bind(REBIND, Binding::key(KeyCode::F2));
+    for shift in [KeyCode::ShiftLeft, KeyCode::ShiftRight] {
+        map.bind(
+            RESET_CONTROLS,
+            Binding::chord([Input::Key(shift), Input::Key(KeyCode::F2)]),
+        );
+    }
+    map.bind(CAMERA_UP, Binding::key(KeyCode::KeyW));
+    map.bind(CAMERA_DOWN, Binding::key(KeyCode::KeyS));
+    map.bind(CAMERA_LEFT, Binding::key(KeyCode::KeyA));
+    map.bind(CAMERA_RIGHT, Binding::key(KeyCode::KeyD));
+    map.bind(ZOOM, Binding::Axis(Axis::WheelY));
+
+    map.bind(PAUSE, gamepad(GamepadButton::Start));
+    map.bind(TOGGLE_TIME_FIELDS, gamepad(GamepadButton::Select));
+    map.bind(CAMERA_UP, push(GamepadAxis::RightStickY, true));
+    map.bind(CAMERA_DOWN, push(GamepadAxis::RightStickY, false));
+    map.bind(CAMERA_LEFT, push(GamepadAxis::RightStickX, false));
+    map.bind(CAMERA_RIGHT, push(GamepadAxis::RightStickX, true));
+    map
+}
+
+fn gamepad(button: GamepadButton) -> Binding {
+    Binding::chord([Input::Gamepad(button)])
+}
+
+/// Pushing `axis` towards its positive or negative end
+fn push(axis: GamepadAxis, positive: bool) -> Binding {
+    Binding::chord([Input::GamepadAxis { axis, positive }])
+}
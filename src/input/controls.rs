@@ -0,0 +1,345 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+use winit::keyboard::KeyCode;
+
+use super::{
+    actions,
+    event::InputEvent,
+    gamepad::{DeadZones, GamepadEvent},
+    map::{AXIS_PRESS_THRESHOLD, Binding, Bindings, Input},
+};
+
+/// Bumped whenever the controls file changes shape
+pub const CONTROLS_VERSION: u32 = 1;
+
+/// Keys that are usually held along with another one rather than on their
+/// own
+const MODIFIERS: &[KeyCode] = &[
+    KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+    KeyCode::ControlLeft,
+    KeyCode::ControlRight,
+    KeyCode::AltLeft,
+    KeyCode::AltRight,
+    KeyCode::SuperLeft,
+    KeyCode::SuperRight,
+];
+
+/// Which inputs trigger which action, as configured by the player. Stored as
+/// RON in the user's config directory.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Controls {
+    pub version: u32,
+    /// Bindings of the actions `Game` acts on
+    pub game: Bindings,
+    /// Bindings of the actions `AppState` acts on
+    pub app: Bindings,
+    #[serde(default)]
+    pub dead_zones: DeadZones,
+}
+
+impl Default for Controls {
+    fn default() -> Self {
+        Self {
+            version: CONTROLS_VERSION,
+            game: actions::game_defaults().all_bindings(),
+            app: actions::app_defaults().all_bindings(),
+            dead_zones: DeadZones::default(),
+        }
+    }
+}
+
+/// A binding that triggers more than one action
+#[derive(Clone, Debug, PartialEq)]
+pub struct Conflict {
+    pub binding: Binding,
+    pub actions: Vec<String>,
+}
+
+impl Controls {
+    /// `time_game/controls.ron` under the user's config directory, e.g.
+    /// `~/.config` on Linux
+    pub fn user_config_path() -> anyhow::Result<PathBuf> {
+        let config_dir = dirs::config_dir().context("Couldn't find the user config directory")?;
+        Ok(config_dir.join("time_game").join("controls.ron"))
+    }
+
+    /// Reads controls written by `save`, or the defaults if there is no file
+    /// at `path` yet
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read controls {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Failed to load controls {}", path.display()))
+    }
+
+    /// Parses the contents of a controls file. Actions it leaves out keep
+    /// their default bindings, so files written before an action existed
+    /// still load.
+    pub fn parse(text: &str) -> anyhow::Result<Self> {
+        let saved: Self = ron::from_str(text)?;
+        if saved.version != CONTROLS_VERSION {
+            bail!(
+                "Controls have version {}, but this build reads version {}",
+                saved.version,
+                CONTROLS_VERSION
+            );
+        }
+
+        let mut controls = Self {
+            dead_zones: saved.dead_zones,
+            ..Self::default()
+        };
+        for (defaults, saved) in [
+            (&mut controls.game, saved.game),
+            (&mut controls.app, saved.app),
+        ] {
+            for (action, bindings) in saved {
+                let Some(default) = defaults.get_mut(&action) else {
+                    bail!("There is no action named {:?}", action);
+                };
+                *default = bindings;
+            }
+        }
+        Ok(controls)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let text = ron::ser::to_string_pretty(self, PrettyConfig::default())?;
+        fs::write(path, text)
+            .with_context(|| format!("Failed to write controls {}", path.display()))
+    }
+
+    fn all(&self) -> impl Iterator<Item = (&String, &Vec<Binding>)> {
+        self.game.iter().chain(&self.app)
+    }
+
+    /// The first action `binding` triggers, if any
+    pub fn action_for(&self, binding: &Binding) -> Option<&str> {
+        self.all()
+            .find(|(_, bindings)| bindings.iter().any(|other| other.overlaps(binding)))
+            .map(|(action, _)| action.as_str())
+    }
+
+    /// Every binding shared by several actions, which would trigger them all
+    /// at once
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts: Vec<Conflict> = vec![];
+        for (action, bindings) in self.all() {
+            for binding in bindings {
+                match conflicts
+                    .iter_mut()
+                    .find(|conflict| conflict.binding.overlaps(binding))
+                {
+                    Some(conflict) => {
+                        if !conflict.actions.contains(action) {
+                            conflict.actions.push(action.clone());
+                        }
+                    }
+                    None => conflicts.push(Conflict {
+                        binding: binding.clone(),
+                        actions: vec![action.clone()],
+                    }),
+                }
+            }
+        }
+        conflicts.retain(|conflict| conflict.actions.len() > 1);
+        conflicts
+    }
+
+    /// Makes `new` trigger `action` in place of `old`, or in addition to its
+    /// other bindings without one. Fails without changing anything if another
+    /// action already uses `new`.
+    pub fn rebind(
+        &mut self,
+        action: &str,
+        old: Option<&Binding>,
+        new: Binding,
+    ) -> anyhow::Result<()> {
+        if let Some(other) = self.action_for(&new)
+            && other != action
+        {
+            bail!("{} is already bound to {}", new, other);
+        }
+        let bindings = match self.game.get_mut(action) {
+            Some(bindings) => bindings,
+            None => self
+                .app
+                .get_mut(action)
+                .with_context(|| format!("There is no action named {:?}", action))?,
+        };
+        bindings.retain(|binding| {
+            !binding.overlaps(&new) && !old.is_some_and(|old| binding.overlaps(old))
+        });
+        bindings.push(new);
+        Ok(())
+    }
+
+    /// Puts every action back on its default bindings
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+/// Turns the next key or button pressed, or stick pushed, into a binding. Modifier keys
+/// held at the time are included, so pressing Ctrl and then S gives Ctrl+S,
+/// while pressing and releasing a modifier on its own binds just that.
+#[derive(Clone, Debug, Default)]
+pub struct Capture {
+    modifiers: Vec<Input>,
+    /// Axis directions past the press threshold. Pads report every change of
+    /// a held stick, so only crossing the threshold counts as a press.
+    held_axes: Vec<Input>,
+}
+
+impl Capture {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Starts capturing the next binding. Sticks still held stay held, so
+    /// they don't count as pressed again until they are let go of.
+    pub fn restart(&mut self) {
+        self.modifiers.clear();
+    }
+
+    /// Returns the binding once the event completes one
+    pub fn handle_event(&mut self, event: &InputEvent) -> Option<Binding> {
+        let (input, pressed) = match *event {
+            InputEvent::Key { code, pressed } => (Input::Key(code), pressed),
+            InputEvent::MouseButton { button, pressed } => (Input::Mouse(button), pressed),
+            InputEvent::Gamepad(GamepadEvent::Button {
+                button, pressed, ..
+            }) => (Input::Gamepad(button), pressed),
+            InputEvent::Gamepad(GamepadEvent::Axis { axis, value, .. }) => {
+                let input = Input::GamepadAxis {
+                    axis,
+                    positive: value > 0.0,
+                };
+                let pressed = value.abs() >= AXIS_PRESS_THRESHOLD;
+                let was_pressed = self.held_axes.contains(&input);
+                self.held_axes.retain(|held| {
+                    !matches!(held, Input::GamepadAxis { axis: held_axis, .. } if *held_axis == axis)
+                });
+                if !pressed {
+                    return None;
+                }
+                self.held_axes.push(input);
+                if was_pressed {
+                    return None;
+                }
+                (input, true)
+            }
+            _ => return None,
+        };
+        let is_modifier = matches!(input, Input::Key(code) if MODIFIERS.contains(&code));
+
+        match (is_modifier, pressed) {
+            (true, true) => {
+                if !self.modifiers.contains(&input) {
+                    self.modifiers.push(input);
+                }
+                None
+            }
+            // Only modifiers pressed since capturing started count, so
+            // letting go of one held from before is ignored
+            (true, false) => self
+                .modifiers
+                .contains(&input)
+                .then(|| Binding::chord(self.modifiers.iter().copied())),
+            (false, true) => Some(Binding::chord(
+                self.modifiers.iter().copied().chain([input]),
+            )),
+            (false, false) => None,
+        }
+    }
+}
+
+/// How far along a `Rebind` is after an event
+#[derive(Clone, Debug, PartialEq)]
+pub enum RebindProgress {
+    /// Nothing happened yet
+    Waiting,
+    /// The action to rebind was picked and the new binding is next
+    Chose {
+        action: String,
+        old: Binding,
+    },
+    Cancelled,
+    Rebound {
+        action: String,
+        binding: Binding,
+    },
+}
+
+/// Rebinding an action from the keyboard, for lack of a menu to pick it
+/// from: the player presses a binding of the action to change and then the
+/// new binding to replace it with. Escape cancels either step.
+#[derive(Clone, Debug, Default)]
+pub struct Rebind {
+    /// The action being rebound and the binding it was picked by
+    chosen: Option<(String, Binding)>,
+    capture: Capture,
+}
+
+impl Rebind {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The action that is being rebound, once it has been picked
+    pub fn action(&self) -> Option<&str> {
+        self.chosen.as_ref().map(|(action, _)| action.as_str())
+    }
+
+    /// Feeds an event to the current step, rebinding the action in
+    /// `controls` once the new binding is pressed. Fails if the binding
+    /// pressed doesn't fit the step, in which case the step can be retried.
+    pub fn handle_event(
+        &mut self,
+        event: &InputEvent,
+        controls: &mut Controls,
+    ) -> anyhow::Result<RebindProgress> {
+        let Some(binding) = self.capture.handle_event(event) else {
+            return Ok(RebindProgress::Waiting);
+        };
+        self.capture.restart();
+        if binding == Binding::key(KeyCode::Escape) {
+            return Ok(RebindProgress::Cancelled);
+        }
+
+        match self.chosen.take() {
+            None => {
+                let action = controls
+                    .action_for(&binding)
+                    .with_context(|| format!("Nothing is bound to {}", binding))?
+                    .to_string();
+                self.chosen = Some((action.clone(), binding.clone()));
+                Ok(RebindProgress::Chose {
+                    action,
+                    old: binding,
+                })
+            }
+            Some((action, old)) => match controls.rebind(&action, Some(&old), binding.clone()) {
+                Ok(()) => Ok(RebindProgress::Rebound { action, binding }),
+                Err(error) => {
+                    self.chosen = Some((action, old));
+                    Err(error)
+                }
+            },
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use serde::{Deserialize, Serialize};
+use winit::{
+    event::{ElementState, KeyEvent, MouseButton, MouseScrollDelta, WindowEvent},
+    keyboard::{KeyCode, PhysicalKey},
+};
+
+use super::gamepad::GamepadEvent;
+
+/// Logical pixels per line for devices that report scrolling in pixels
+const PIXELS_PER_SCROLL_LINE: f32 = 40.0;
+
+/// A keyboard or mouse event in a form that can be recorded and replayed.
+/// Window management events like resizes aren't input and aren't included.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InputEvent {
+    Key {
+        code: KeyCode,
+        pressed: bool,
+    },
+    /// Cursor position in physical pixels relative to the window's top left
+    CursorMoved {
+        x: f64,
+        y: f64,
+    },
+    MouseButton {
+        button: MouseButton,
+        pressed: bool,
+    },
+    MouseWheel(MouseScrollDelta),
+    /// Cursor position in world space. The window never sends this: the app
+    /// works it out through the camera whenever the cursor or the camera
+    /// moves, so that the game and its replays don't depend on the camera.
+    PointerMoved {
+        x: f32,
+        y: f32,
+    },
+    /// Also not from the window, but polled from a gamepad backend
+    Gamepad(GamepadEvent),
+}
+
+impl InputEvent {
+    /// Converts the input events `App::window_event` receives. Key repeats are
+    /// dropped so that holding a key only acts once.
+    pub fn from_window_event(event: &WindowEvent) -> Option<Self> {
+        match *event {
+            WindowEvent::KeyboardInput {
+                event:
+                    KeyEvent {
+                        physical_key: PhysicalKey::Code(code),
+                        state,
+                        repeat: false,
+                        ..
+                    },
+                ..
+            } => Some(Self::Key {
+                code,
+                pressed: state == ElementState::Pressed,
+            }),
+            WindowEvent::CursorMoved { position, .. } => Some(Self::CursorMoved {
+                x: position.x,
+                y: position.y,
+            }),
+            WindowEvent::MouseInput { state, button, .. } => Some(Self::MouseButton {
+                button,
+                pressed: state == ElementState::Pressed,
+            }),
+            WindowEvent::MouseWheel { delta, .. } => Some(Self::MouseWheel(delta)),
+            _ => None,
+        }
+    }
+}
+
+/// Lines scrolled horizontally and vertically, with vertical scrolling
+/// positive away from the user
+pub fn scroll_lines(delta: MouseScrollDelta) -> (f32, f32) {
+    match delta {
+        MouseScrollDelta::LineDelta(x, y) => (x, y),
+        MouseScrollDelta::PixelDelta(position) => (
+            position.x as f32 / PIXELS_PER_SCROLL_LINE,
+            position.y as f32 / PIXELS_PER_SCROLL_LINE,
+        ),
+    }
+}
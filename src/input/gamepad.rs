@@ -0,0 +1,391 @@
+use std::{
+    cell::RefCell,
+    collections::{BTreeMap, VecDeque},
+    rc::Rc,
+};
+
+use serde::{Deserialize, Serialize};
+
+/// Identifies a connected gamepad. Ids of disconnected pads may be reused.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct GamepadId(pub u32);
+
+/// Buttons named by their position on the pad, so South is A on an Xbox pad
+/// and Cross on a PlayStation one
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum GamepadButton {
+    South,
+    East,
+    West,
+    North,
+    LeftBumper,
+    RightBumper,
+    Select,
+    Start,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// Stick axes go from -1 to 1, with y positive up. Triggers go from 0 to 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+impl GamepadAxis {
+    /// The stick axis perpendicular to this one, or `None` for triggers
+    fn other_stick_axis(self) -> Option<Self> {
+        match self {
+            Self::LeftStickX => Some(Self::LeftStickY),
+            Self::LeftStickY => Some(Self::LeftStickX),
+            Self::RightStickX => Some(Self::RightStickY),
+            Self::RightStickY => Some(Self::RightStickX),
+            Self::LeftTrigger | Self::RightTrigger => None,
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum GamepadEvent {
+    Connected(GamepadId),
+    Disconnected(GamepadId),
+    Button {
+        id: GamepadId,
+        button: GamepadButton,
+        pressed: bool,
+    },
+    Axis {
+        id: GamepadId,
+        axis: GamepadAxis,
+        value: f32,
+    },
+}
+
+/// How far sticks and triggers have to move before they register, as a
+/// fraction of their full range. Worn pads rarely rest exactly at zero.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DeadZones {
+    /// Applied to the distance from the center, so diagonals aren't cut off
+    pub stick: f32,
+    pub trigger: f32,
+}
+
+impl Default for DeadZones {
+    fn default() -> Self {
+        Self {
+            stick: 0.15,
+            trigger: 0.05,
+        }
+    }
+}
+
+impl DeadZones {
+    /// Scales what is outside the dead zone back up to the full range, so
+    /// values still start from zero at its edge
+    fn rescale(magnitude: f32, dead_zone: f32) -> f32 {
+        if magnitude <= dead_zone {
+            0.0
+        } else {
+            ((magnitude - dead_zone) / (1.0 - dead_zone)).min(1.0)
+        }
+    }
+
+    /// Applies the stick dead zone to a stick's position
+    pub fn apply_to_stick(&self, x: f32, y: f32) -> (f32, f32) {
+        let magnitude = x.hypot(y);
+        if magnitude == 0.0 {
+            return (0.0, 0.0);
+        }
+        let scale = Self::rescale(magnitude, self.stick) / magnitude;
+        (x * scale, y * scale)
+    }
+
+    pub fn apply_to_trigger(&self, value: f32) -> f32 {
+        Self::rescale(value, self.trigger)
+    }
+}
+
+/// Where gamepad events come from
+pub trait GamepadBackend {
+    /// Every event since the last poll, oldest first
+    fn poll(&mut self) -> Vec<GamepadEvent>;
+}
+
+#[derive(Clone, Debug, Default)]
+struct Pad {
+    pressed: Vec<GamepadButton>,
+    /// As reported by the backend
+    raw_axes: BTreeMap<GamepadAxis, f32>,
+    /// After the dead zones, as last passed on
+    axes: BTreeMap<GamepadAxis, f32>,
+}
+
+/// Keeps track of the connected pads and turns what a backend reports into
+/// the events the game sees: axes with dead zones applied, only sent when
+/// they change, and everything a pad was holding let go when it is
+/// unplugged.
+#[derive(Clone, Debug, Default)]
+pub struct Gamepads {
+    pub dead_zones: DeadZones,
+    pads: BTreeMap<GamepadId, Pad>,
+}
+
+impl Gamepads {
+    pub fn new(dead_zones: DeadZones) -> Self {
+        Self {
+            dead_zones,
+            pads: BTreeMap::new(),
+        }
+    }
+
+    pub fn connected(&self) -> impl Iterator<Item = GamepadId> + '_ {
+        self.pads.keys().copied()
+    }
+
+    /// Processes an event from a backend, returning the events to pass on
+    pub fn handle_event(&mut self, event: GamepadEvent) -> Vec<GamepadEvent> {
+        match event {
+            GamepadEvent::Connected(id) => {
+                if self.pads.contains_key(&id) {
+                    return vec![];
+                }
+                self.pads.insert(id, Pad::default());
+                vec![event]
+            }
+            GamepadEvent::Disconnected(id) => {
+                let Some(pad) = self.pads.remove(&id) else {
+                    return vec![];
+                };
+                let mut events: Vec<_> = pad
+                    .pressed
+                    .into_iter()
+                    .map(|button| GamepadEvent::Button {
+                        id,
+                        button,
+                        pressed: false,
+                    })
+                    .collect();
+                events.extend(pad.axes.into_iter().filter(|&(_, value)| value != 0.0).map(
+                    |(axis, _)| GamepadEvent::Axis {
+                        id,
+                        axis,
+                        value: 0.0,
+                    },
+                ));
+                events.push(event);
+                events
+            }
+            GamepadEvent::Button {
+                id,
+                button,
+                pressed,
+            } => {
+                // Pads that are used without a connection event are
+                // connected implicitly
+                let mut events = self.handle_event(GamepadEvent::Connected(id));
+                let pad = self.pads.get_mut(&id).expect("Pad was just connected");
+                if pad.pressed.contains(&button) == pressed {
+                    return events;
+                }
+                if pressed {
+                    pad.pressed.push(button);
+                } else {
+                    pad.pressed.retain(|&other| other != button);
+                }
+                events.push(event);
+                events
+            }
+            GamepadEvent::Axis { id, axis, value } => {
+                let mut events = self.handle_event(GamepadEvent::Connected(id));
+                let dead_zones = self.dead_zones;
+                let pad = self.pads.get_mut(&id).expect("Pad was just connected");
+                pad.raw_axes.insert(axis, value);
+
+                let processed = match axis.other_stick_axis() {
+                    Some(other) => {
+                        let other_value = pad.raw_axes.get(&other).copied().unwrap_or(0.0);
+                        let (value, other_value) = dead_zones.apply_to_stick(value, other_value);
+                        vec![(axis, value), (other, other_value)]
+                    }
+                    None => vec![(axis, dead_zones.apply_to_trigger(value))],
+                };
+                for (axis, value) in processed {
+                    let previous = pad.axes.insert(axis, value).unwrap_or(0.0);
+                    if previous != value {
+                        events.push(GamepadEvent::Axis { id, axis, value });
+                    }
+                }
+                events
+            }
+        }
+    }
+}
+
+/// A backend whose pads are driven by code, for tests and for machines
+/// without any pads. Clones share their pads, so a test can keep one to
+/// drive the pads while the game polls another.
+#[derive(Clone, Debug, Default)]
+pub struct VirtualGamepads {
+    state: Rc<RefCell<VirtualState>>,
+}
+
+#[derive(Debug, Default)]
+struct VirtualState {
+    next_id: u32,
+    queue: VecDeque<GamepadEvent>,
+}
+
+impl VirtualGamepads {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Plugs in a new pad
+    pub fn connect(&self) -> GamepadId {
+        let mut state = self.state.borrow_mut();
+        let id = GamepadId(state.next_id);
+        state.next_id += 1;
+        state.queue.push_back(GamepadEvent::Connected(id));
+        id
+    }
+
+    pub fn disconnect(&self, id: GamepadId) {
+        self.push(GamepadEvent::Disconnected(id));
+    }
+
+    pub fn press(&self, id: GamepadId, button: GamepadButton) {
+        self.push(GamepadEvent::Button {
+            id,
+            button,
+            pressed: true,
+        });
+    }
+
+    pub fn release(&self, id: GamepadId, button: GamepadButton) {
+        self.push(GamepadEvent::Button {
+            id,
+            button,
+            pressed: false,
+        });
+    }
+
+    pub fn set_axis(&self, id: GamepadId, axis: GamepadAxis, value: f32) {
+        self.push(GamepadEvent::Axis { id, axis, value });
+    }
+
+    fn push(&self, event: GamepadEvent) {
+        self.state.borrow_mut().queue.push_back(event);
+    }
+}
+
+impl GamepadBackend for VirtualGamepads {
+    fn poll(&mut self) -> Vec<GamepadEvent> {
+        self.state.borrow_mut().queue.drain(..).collect()
+    }
+}
+
+/// Physical pads, through gilrs. Needs libudev on Linux.
+#[cfg(feature = "gilrs")]
+pub struct GilrsGamepads {
+    gilrs: gilrs::Gilrs,
+}
+
+#[cfg(feature = "gilrs")]
+impl GilrsGamepads {
+    pub fn new() -> anyhow::Result<Self> {
+        // Dead zones are applied by `Gamepads` instead, so they can be
+        // configured
+        let gilrs = gilrs::GilrsBuilder::new()
+            .with_default_filters(false)
+            .build()
+            .map_err(|error| anyhow::anyhow!("Failed to open gamepads: {}", error))?;
+        Ok(Self { gilrs })
+    }
+
+    fn button(button: gilrs::Button) -> Option<GamepadButton> {
+        use gilrs::Button;
+        Some(match button {
+            Button::South => GamepadButton::South,
+            Button::East => GamepadButton::East,
+            Button::West => GamepadButton::West,
+            Button::North => GamepadButton::North,
+            Button::LeftTrigger => GamepadButton::LeftBumper,
+            Button::RightTrigger => GamepadButton::RightBumper,
+            Button::Select => GamepadButton::Select,
+            Button::Start => GamepadButton::Start,
+            Button::LeftThumb => GamepadButton::LeftStick,
+            Button::RightThumb => GamepadButton::RightStick,
+            Button::DPadUp => GamepadButton::DPadUp,
+            Button::DPadDown => GamepadButton::DPadDown,
+            Button::DPadLeft => GamepadButton::DPadLeft,
+            Button::DPadRight => GamepadButton::DPadRight,
+            _ => return None,
+        })
+    }
+
+    fn axis(axis: gilrs::Axis) -> Option<GamepadAxis> {
+        use gilrs::Axis;
+        Some(match axis {
+            Axis::LeftStickX => GamepadAxis::LeftStickX,
+            Axis::LeftStickY => GamepadAxis::LeftStickY,
+            Axis::RightStickX => GamepadAxis::RightStickX,
+            Axis::RightStickY => GamepadAxis::RightStickY,
+            _ => return None,
+        })
+    }
+}
+
+#[cfg(feature = "gilrs")]
+impl GamepadBackend for GilrsGamepads {
+    fn poll(&mut self) -> Vec<GamepadEvent> {
+        use gilrs::{Button, EventType};
+
+        let mut events = vec![];
+        while let Some(gilrs::Event { id, event, .. }) = self.gilrs.next_event() {
+            let id = GamepadId(usize::from(id) as u32);
+            events.push(match event {
+                EventType::Connected => GamepadEvent::Connected(id),
+                EventType::Disconnected => GamepadEvent::Disconnected(id),
+                // Analog triggers are reported as buttons with a value
+                EventType::ButtonChanged(Button::LeftTrigger2, value, _) => GamepadEvent::Axis {
+                    id,
+                    axis: GamepadAxis::LeftTrigger,
+                    value,
+                },
+                EventType::ButtonChanged(Button::RightTrigger2, value, _) => GamepadEvent::Axis {
+                    id,
+                    axis: GamepadAxis::RightTrigger,
+                    value,
+                },
+                EventType::ButtonPressed(button, _) | EventType::ButtonReleased(button, _) => {
+                    let Some(button) = Self::button(button) else {
+                        continue;
+                    };
+                    GamepadEvent::Button {
+                        id,
+                        button,
+                        pressed: matches!(event, EventType::ButtonPressed(..)),
+                    }
+                }
+                EventType::AxisChanged(axis, value, _) => {
+                    let Some(axis) = Self::axis(axis) else {
+                        continue;
+                    };
+                    GamepadEvent::Axis { id, axis, value }
+                }
+                _ => continue,
+            });
+        }
+        events
+    }
+}
@@ -0,0 +1,349 @@
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+use super::{
+    event::{InputEvent, scroll_lines},
+    gamepad::{GamepadAxis, GamepadButton, GamepadEvent},
+};
+
+/// How far a stick or trigger has to be pushed to count as held
+pub const AXIS_PRESS_THRESHOLD: f32 = 0.5;
+
+/// A physical button that can be bound to an action
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Input {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// The button on any connected pad
+    Gamepad(GamepadButton),
+    /// Held while a stick or trigger on any connected pad is pushed at least
+    /// half way towards one end
+    GamepadAxis {
+        axis: GamepadAxis,
+        positive: bool,
+    },
+}
+
+impl fmt::Display for Input {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Key(code) => write!(f, "{:?}", code),
+            Self::Mouse(button) => write!(f, "Mouse{:?}", button),
+            Self::Gamepad(button) => write!(f, "Gamepad{:?}", button),
+            Self::GamepadAxis { axis, positive } => {
+                write!(f, "{:?}{}", axis, if *positive { "+" } else { "-" })
+            }
+        }
+    }
+}
+
+/// Something with a continuous value rather than pressed or not
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Axis {
+    /// Scrolled lines, positive away from the user
+    WheelY,
+    /// Scrolled lines, positive to the right
+    WheelX,
+    /// Where a stick or trigger is, the same on every tick it stays there
+    Gamepad(GamepadAxis),
+}
+
+/// One way of triggering an action
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Binding {
+    /// Held while all of the inputs are, so either a single key or button or
+    /// a chord like Ctrl+S
+    Chord(Vec<Input>),
+    /// Adds the axis' movement to the action's value
+    Axis(Axis),
+}
+
+impl Binding {
+    pub fn key(code: KeyCode) -> Self {
+        Self::Chord(vec![Input::Key(code)])
+    }
+
+    pub fn mouse(button: MouseButton) -> Self {
+        Self::Chord(vec![Input::Mouse(button)])
+    }
+
+    pub fn chord(inputs: impl IntoIterator<Item = Input>) -> Self {
+        Self::Chord(inputs.into_iter().collect())
+    }
+
+    fn uses(&self, input: Input) -> bool {
+        matches!(self, Self::Chord(inputs) if inputs.contains(&input))
+    }
+
+    /// Whether both bindings are triggered by the same thing, which for
+    /// chords means the same inputs in any order
+    pub fn overlaps(&self, other: &Binding) -> bool {
+        match (self, other) {
+            (Self::Chord(inputs), Self::Chord(others)) => {
+                inputs.len() == others.len() && inputs.iter().all(|input| others.contains(input))
+            }
+            _ => self == other,
+        }
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Chord(inputs) => {
+                for (i, input) in inputs.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, "+")?;
+                    }
+                    write!(f, "{}", input)?;
+                }
+                Ok(())
+            }
+            Self::Axis(axis) => write!(f, "{:?}", axis),
+        }
+    }
+}
+
+/// Every action's bindings, by action name
+pub type Bindings = BTreeMap<String, Vec<Binding>>;
+
+#[derive(Clone, Debug, Default)]
+struct Action {
+    bindings: Vec<Binding>,
+    pressed: bool,
+    just_pressed: bool,
+    just_released: bool,
+    value: f32,
+}
+
+/// Turns raw input events into named actions like "move_left" or "pause",
+/// so that what a key does is decided in one place and the code acting on
+/// it doesn't care which key it was.
+///
+/// Pressed and released edges are remembered until `advance`, which the
+/// owner calls once per tick or frame, so a tap shorter than a tick still
+/// registers. When one chord is held along with a longer chord containing
+/// it, only the longer one counts, so holding Ctrl+S doesn't also trigger
+/// whatever plain S is bound to.
+#[derive(Clone, Debug, Default)]
+pub struct InputMap {
+    actions: BTreeMap<String, Action>,
+    held: Vec<Input>,
+    /// Latest position of each stick and trigger, from whichever pad moved
+    /// it last
+    gamepad_axes: BTreeMap<GamepadAxis, f32>,
+}
+
+impl InputMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds another way of triggering `action`, creating the action if it
+    /// doesn't exist yet
+    pub fn bind(&mut self, action: &str, binding: Binding) {
+        let bindings = &mut self.actions.entry(action.to_string()).or_default().bindings;
+        if !bindings.contains(&binding) {
+            bindings.push(binding);
+        }
+        self.update_pressed();
+    }
+
+    /// Replaces every binding of `action`, creating the action if it doesn't
+    /// exist yet
+    pub fn set_bindings(&mut self, action: &str, bindings: Vec<Binding>) {
+        self.actions.entry(action.to_string()).or_default().bindings = bindings;
+        self.update_pressed();
+    }
+
+    /// Replaces the bindings of every action in `bindings`, leaving other
+    /// actions as they are
+    pub fn set_all_bindings(&mut self, bindings: &Bindings) {
+        for (action, bindings) in bindings {
+            self.actions.entry(action.clone()).or_default().bindings = bindings.clone();
+        }
+        self.update_pressed();
+    }
+
+    pub fn all_bindings(&self) -> Bindings {
+        self.actions
+            .iter()
+            .map(|(action, state)| (action.clone(), state.bindings.clone()))
+            .collect()
+    }
+
+    pub fn bindings(&self, action: &str) -> &[Binding] {
+        self.actions
+            .get(action)
+            .map_or(&[], |state| state.bindings.as_slice())
+    }
+
+    /// Every action with a name, in alphabetical order
+    pub fn actions(&self) -> impl Iterator<Item = &str> {
+        self.actions.keys().map(String::as_str)
+    }
+
+    /// Updates the actions from an input event. Returns whether any binding
+    /// uses the input.
+    pub fn handle_event(&mut self, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::Key { code, pressed } => self.handle_button(Input::Key(code), pressed),
+            InputEvent::MouseButton { button, pressed } => {
+                self.handle_button(Input::Mouse(button), pressed)
+            }
+            InputEvent::MouseWheel(delta) => {
+                let (x, y) = scroll_lines(delta);
+                let mut used = false;
+                for state in self.actions.values_mut() {
+                    for binding in &state.bindings {
+                        match binding {
+                            Binding::Axis(Axis::WheelX) => state.value += x,
+                            Binding::Axis(Axis::WheelY) => state.value += y,
+                            Binding::Axis(Axis::Gamepad(_)) | Binding::Chord(_) => continue,
+                        }
+                        used = true;
+                    }
+                }
+                used
+            }
+            InputEvent::Gamepad(GamepadEvent::Button {
+                button, pressed, ..
+            }) => self.handle_button(Input::Gamepad(button), pressed),
+            InputEvent::Gamepad(GamepadEvent::Axis { axis, value, .. }) => {
+                self.gamepad_axes.insert(axis, value);
+                let directions =
+                    [false, true].map(|positive| Input::GamepadAxis { axis, positive });
+                self.set_held(directions[0], value <= -AXIS_PRESS_THRESHOLD);
+                self.set_held(directions[1], value >= AXIS_PRESS_THRESHOLD);
+                self.update_pressed();
+                directions.iter().any(|&input| self.uses(input))
+                    || self
+                        .actions
+                        .values()
+                        .any(|state| state.bindings.contains(&Binding::Axis(Axis::Gamepad(axis))))
+            }
+            InputEvent::CursorMoved { .. }
+            | InputEvent::PointerMoved { .. }
+            | InputEvent::Gamepad(GamepadEvent::Connected(_) | GamepadEvent::Disconnected(_)) => {
+                false
+            }
+        }
+    }
+
+    fn handle_button(&mut self, input: Input, pressed: bool) -> bool {
+        self.set_held(input, pressed);
+        self.update_pressed();
+        self.uses(input)
+    }
+
+    fn set_held(&mut self, input: Input, pressed: bool) {
+        let held = self.held.contains(&input);
+        if pressed && !held {
+            self.held.push(input);
+        } else if !pressed && held {
+            self.held.retain(|&other| other != input);
+        }
+    }
+
+    /// Whether any binding uses `input`
+    fn uses(&self, input: Input) -> bool {
+        self.actions
+            .values()
+            .any(|state| state.bindings.iter().any(|binding| binding.uses(input)))
+    }
+
+    /// Works out which actions are held after the held inputs changed,
+    /// recording the edges
+    fn update_pressed(&mut self) {
+        let is_held = |inputs: &[Input]| {
+            !inputs.is_empty() && inputs.iter().all(|input| self.held.contains(input))
+        };
+        let active: Vec<&[Input]> = self
+            .actions
+            .values()
+            .flat_map(|state| &state.bindings)
+            .filter_map(|binding| match binding {
+                Binding::Chord(inputs) if is_held(inputs) => Some(inputs.as_slice()),
+                _ => None,
+            })
+            .collect();
+        let is_shadowed = |inputs: &[Input]| {
+            active.iter().any(|other| {
+                other.len() > inputs.len() && inputs.iter().all(|input| other.contains(input))
+            })
+        };
+
+        let pressed: Vec<bool> = self
+            .actions
+            .values()
+            .map(|state| {
+                state.bindings.iter().any(|binding| match binding {
+                    Binding::Chord(inputs) => is_held(inputs) && !is_shadowed(inputs),
+                    Binding::Axis(_) => false,
+                })
+            })
+            .collect();
+
+        for (state, pressed) in self.actions.values_mut().zip(pressed) {
+            if pressed && !state.pressed {
+                state.just_pressed = true;
+            } else if !pressed && state.pressed {
+                state.just_released = true;
+            }
+            state.pressed = pressed;
+        }
+    }
+
+    /// Forgets this tick's edges and axis movement. Call once the actions
+    /// have been acted on.
+    pub fn advance(&mut self) {
+        for state in self.actions.values_mut() {
+            state.just_pressed = false;
+            state.just_released = false;
+            state.value = 0.0;
+        }
+    }
+
+    pub fn is_pressed(&self, action: &str) -> bool {
+        self.actions.get(action).is_some_and(|state| state.pressed)
+    }
+
+    /// Whether `action` was pressed since the last `advance`, even if it has
+    /// been released again since
+    pub fn just_pressed(&self, action: &str) -> bool {
+        self.actions
+            .get(action)
+            .is_some_and(|state| state.just_pressed)
+    }
+
+    /// Whether `action` was released since the last `advance`
+    pub fn just_released(&self, action: &str) -> bool {
+        self.actions
+            .get(action)
+            .is_some_and(|state| state.just_released)
+    }
+
+    /// How far `action`'s wheel axes moved since the last `advance`, plus
+    /// where its gamepad axes are, plus 1 while it is pressed
+    pub fn value(&self, action: &str) -> f32 {
+        self.actions.get(action).map_or(0.0, |state| {
+            let gamepad: f32 = state
+                .bindings
+                .iter()
+                .filter_map(|binding| match binding {
+                    Binding::Axis(Axis::Gamepad(axis)) => self.gamepad_axes.get(axis),
+                    _ => None,
+                })
+                .sum();
+            state.value + gamepad + if state.pressed { 1.0 } else { 0.0 }
+        })
+    }
+
+    /// `positive`'s value minus `negative`'s, e.g. -1 to 1 for a pair of
+    /// opposite keys
+    pub fn axis(&self, negative: &str, positive: &str) -> f32 {
+        self.value(positive) - self.value(negative)
+    }
+}
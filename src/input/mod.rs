@@ -0,0 +1,7 @@
+pub mod actions;
+pub mod controls;
+pub mod event;
+pub mod gamepad;
+pub mod map;
+pub mod mouse;
+pub mod script;
@@ -0,0 +1,195 @@
+use cgmath::{InnerSpace, Vector2};
+use winit::event::MouseButton;
+
+use super::event::{InputEvent, scroll_lines};
+
+/// A press and drag with one button, from where the button went down to
+/// where the cursor is or was let go
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Drag {
+    pub button: MouseButton,
+    pub start: Vector2<f32>,
+    pub end: Vector2<f32>,
+}
+
+/// Where a held button went down, and whether it has moved far enough since
+/// to count as a drag
+#[derive(Copy, Clone, Debug)]
+struct Press {
+    button: MouseButton,
+    start: Vector2<f32>,
+    dragging: bool,
+}
+
+/// The mouse as of the current tick or frame, in whatever space its
+/// positions are given in. Like `InputMap`, edges are remembered until
+/// `advance`.
+#[derive(Clone, Debug)]
+pub struct Mouse {
+    /// How far the cursor has to move with a button held before it counts
+    /// as a drag rather than a click
+    drag_threshold: f32,
+    /// `None` until the cursor's position is first known
+    position: Option<Vector2<f32>>,
+    presses: Vec<Press>,
+    just_pressed: Vec<MouseButton>,
+    just_released: Vec<MouseButton>,
+    clicked: Vec<MouseButton>,
+    drags_started: Vec<Drag>,
+    drags_ended: Vec<Drag>,
+    /// Lines scrolled horizontally and vertically
+    wheel: Vector2<f32>,
+}
+
+impl Mouse {
+    pub fn new(drag_threshold: f32) -> Self {
+        Self {
+            drag_threshold,
+            position: None,
+            presses: vec![],
+            just_pressed: vec![],
+            just_released: vec![],
+            clicked: vec![],
+            drags_started: vec![],
+            drags_ended: vec![],
+            wheel: Vector2::new(0.0, 0.0),
+        }
+    }
+
+    /// Updates the buttons and wheel from an event, and the position from
+    /// `PointerMoved`. Returns whether the event was a mouse event.
+    pub fn handle_event(&mut self, event: &InputEvent) -> bool {
+        match *event {
+            InputEvent::MouseButton { button, pressed } => {
+                if pressed {
+                    self.press(button);
+                } else {
+                    self.release(button);
+                }
+            }
+            InputEvent::MouseWheel(delta) => {
+                let (x, y) = scroll_lines(delta);
+                self.wheel += Vector2::new(x, y);
+            }
+            InputEvent::PointerMoved { x, y } => self.move_to(Vector2::new(x, y)),
+            InputEvent::Key { .. } | InputEvent::CursorMoved { .. } | InputEvent::Gamepad(_) => {
+                return false;
+            }
+        }
+        true
+    }
+
+    pub fn move_to(&mut self, position: Vector2<f32>) {
+        self.position = Some(position);
+        for press in &mut self.presses {
+            if !press.dragging && (position - press.start).magnitude() > self.drag_threshold {
+                press.dragging = true;
+                self.drags_started.push(Drag {
+                    button: press.button,
+                    start: press.start,
+                    end: position,
+                });
+            }
+        }
+    }
+
+    pub fn press(&mut self, button: MouseButton) {
+        // Presses before the cursor position is known can't be placed
+        let Some(start) = self.position else {
+            return;
+        };
+        if self.is_pressed(button) {
+            return;
+        }
+        self.presses.push(Press {
+            button,
+            start,
+            dragging: false,
+        });
+        self.just_pressed.push(button);
+    }
+
+    pub fn release(&mut self, button: MouseButton) {
+        let Some(index) = self.presses.iter().position(|press| press.button == button) else {
+            return;
+        };
+        let press = self.presses.remove(index);
+        self.just_released.push(button);
+        if press.dragging {
+            self.drags_ended.push(Drag {
+                button,
+                start: press.start,
+                end: self.position.unwrap_or(press.start),
+            });
+        } else {
+            self.clicked.push(button);
+        }
+    }
+
+    /// Forgets this tick's edges and wheel movement
+    pub fn advance(&mut self) {
+        self.just_pressed.clear();
+        self.just_released.clear();
+        self.clicked.clear();
+        self.drags_started.clear();
+        self.drags_ended.clear();
+        self.wheel = Vector2::new(0.0, 0.0);
+    }
+
+    pub fn position(&self) -> Option<Vector2<f32>> {
+        self.position
+    }
+
+    pub fn is_pressed(&self, button: MouseButton) -> bool {
+        self.presses.iter().any(|press| press.button == button)
+    }
+
+    pub fn just_pressed(&self, button: MouseButton) -> bool {
+        self.just_pressed.contains(&button)
+    }
+
+    pub fn just_released(&self, button: MouseButton) -> bool {
+        self.just_released.contains(&button)
+    }
+
+    /// Whether `button` was pressed and released without dragging since the
+    /// last `advance`
+    pub fn clicked(&self, button: MouseButton) -> bool {
+        self.clicked.contains(&button)
+    }
+
+    /// Lines scrolled since the last `advance`, with vertical scrolling
+    /// positive away from the user
+    pub fn wheel(&self) -> Vector2<f32> {
+        self.wheel
+    }
+
+    /// The drag `button` is making, if it is held and has moved far enough
+    pub fn drag(&self, button: MouseButton) -> Option<Drag> {
+        let press = self
+            .presses
+            .iter()
+            .find(|press| press.button == button && press.dragging)?;
+        Some(Drag {
+            button,
+            start: press.start,
+            end: self.position?,
+        })
+    }
+
+    /// The drag `button` started since the last `advance`, if any
+    pub fn drag_started(&self, button: MouseButton) -> Option<Drag> {
+        self.drags_started
+            .iter()
+            .find(|drag| drag.button == button)
+            .copied()
+    }
+
+    /// The drag `button` finished since the last `advance`, if any
+    pub fn drag_ended(&self, button: MouseButton) -> Option<Drag> {
+        self.drags_ended
+            .iter()
+            .find(|drag| drag.button == button)
+            .copied()
+    }
+}
@@ -0,0 +1,91 @@
+use winit::keyboard::KeyCode;
+
+use super::event::InputEvent;
+
+/// An input event and the frame it arrives at
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ScriptedEvent {
+    /// Frames run since the script started playing before the event arrives
+    pub frame: u64,
+    pub event: InputEvent,
+}
+
+/// Input written out ahead of time rather than coming from the window, for
+/// driving the app in automated tests. Unlike a replay, which feeds the game
+/// directly, scripted events go through the same path as the window's, so
+/// they reach the app's actions too.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct InputScript {
+    /// Sorted by frame, with events on the same frame in the order they were
+    /// added
+    events: Vec<ScriptedEvent>,
+    next_event: usize,
+}
+
+impl InputScript {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sends `event` at `frame`, after anything already scripted for it
+    pub fn event(&mut self, frame: u64, event: InputEvent) {
+        let index = self
+            .events
+            .partition_point(|scripted| scripted.frame <= frame);
+        self.events.insert(index, ScriptedEvent { frame, event });
+    }
+
+    pub fn press(&mut self, frame: u64, code: KeyCode) {
+        self.event(
+            frame,
+            InputEvent::Key {
+                code,
+                pressed: true,
+            },
+        );
+    }
+
+    pub fn release(&mut self, frame: u64, code: KeyCode) {
+        self.event(
+            frame,
+            InputEvent::Key {
+                code,
+                pressed: false,
+            },
+        );
+    }
+
+    /// Presses `code` at `frame` and lets go of it `frames` frames later
+    pub fn hold(&mut self, frame: u64, code: KeyCode, frames: u64) {
+        self.press(frame, code);
+        self.release(frame + frames, code);
+    }
+
+    /// Presses `code` for a single frame
+    pub fn tap(&mut self, frame: u64, code: KeyCode) {
+        self.hold(frame, code, 1);
+    }
+
+    /// Frames it takes to send every event
+    pub fn frames(&self) -> u64 {
+        self.events.last().map_or(0, |scripted| scripted.frame + 1)
+    }
+
+    /// Events due at or before `frame` that haven't been returned yet
+    pub fn take_due(&mut self, frame: u64) -> &[ScriptedEvent] {
+        let start = self.next_event;
+        while self
+            .events
+            .get(self.next_event)
+            .is_some_and(|scripted| scripted.frame <= frame)
+        {
+            self.next_event += 1;
+        }
+        &self.events[start..self.next_event]
+    }
+
+    /// Whether every event has been returned by `take_due`
+    pub fn is_finished(&self) -> bool {
+        self.next_event == self.events.len()
+    }
+}
@@ -0,0 +1,24 @@
+pub mod app_state;
+pub mod camera_controller;
+pub mod components;
+pub mod determinism;
+pub mod echo;
+pub mod ecs;
+pub mod game;
+pub mod game_clock;
+pub mod golden;
+pub mod graphics;
+pub mod harness;
+pub mod input;
+pub mod options;
+pub mod prefab;
+pub mod progress;
+pub mod puzzle;
+pub mod replay;
+pub mod rewind;
+pub mod rng;
+pub mod save;
+pub mod scene;
+pub mod systems;
+pub mod time_field;
+pub mod world;
@@ -1,19 +1,30 @@
-mod app_state;
-mod camera_controller;
-mod graphics;
+use std::{path::Path, sync::Arc};
 
-use crate::app_state::AppState;
-
-use std::sync::Arc;
+use anyhow::Context;
+use time_game::{
+    app_state::AppState,
+    game::Game,
+    graphics::{GraphicsState, render_error::RenderError},
+    input::event::InputEvent,
+    options::Options,
+    replay::{self, Replay},
+};
 use winit::{
     application::ApplicationHandler,
-    event::{KeyEvent, WindowEvent},
+    event::WindowEvent,
     event_loop::{ActiveEventLoop, ControlFlow, EventLoop},
-    keyboard::{KeyCode, PhysicalKey},
     window::Window,
 };
 
+/// Size of `--screenshot` images in pixels
+const SCREENSHOT_SIZE: (u32, u32) = (1280, 720);
+
 struct App {
+    options: Options,
+    /// Handed to the app state once the window exists
+    replay: Option<Replay>,
+    /// Set when a replay ended with a different checksum than it was recorded with
+    replay_diverged: bool,
     state: Option<AppState>, // We use option at the top level so that all of app state can be initialized together
 }
 
@@ -29,37 +40,49 @@ impl ApplicationHandler for App {
             None => return,
         };
 
-        // TODO: move this to app state?
         match event {
             WindowEvent::CloseRequested => {
                 event_loop.exit();
             }
             WindowEvent::Resized(size) => state.resize(size.width, size.height),
-            WindowEvent::RedrawRequested => {
-                // TODO: should update be called somewhere else? Is redrawrequested guaranteed to be called regularly?
-                state.update();
-                // TODO: handle render errors
-                state.render().unwrap();
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                state.scale_factor_changed(scale_factor)
             }
-            WindowEvent::KeyboardInput {
-                event:
-                    KeyEvent {
-                        physical_key: PhysicalKey::Code(code),
-                        state: key_state,
-                        ..
-                    },
-                ..
-            } => match (code, key_state.is_pressed()) {
-                (KeyCode::Escape, true) => {
+            WindowEvent::RedrawRequested => {
+                // Redraws aren't guaranteed to arrive at a steady rate, so the
+                // game clock decides how many fixed updates each frame runs
+                match state.frame() {
+                    Ok(()) => (),
+                    Err(RenderError::DeviceLost(message)) => {
+                        eprintln!("Graphics device lost ({}), recreating", message);
+                        if let Err(error) = pollster::block_on(state.recreate_graphics()) {
+                            eprintln!("Failed to recreate graphics: {:#}", error);
+                            event_loop.exit();
+                        }
+                    }
+                    Err(RenderError::OutOfMemory) => {
+                        eprintln!("{}", RenderError::OutOfMemory);
+                        event_loop.exit();
+                    }
+                    Err(error @ RenderError::Surface(_)) => eprintln!("{}", error),
+                }
+
+                if let Some(checksum) = state.replay_finished() {
+                    let replay = state.replay().expect("Finished replays are still playing");
+                    self.replay_diverged = !report_replay(replay, checksum);
                     event_loop.exit();
                 }
-                _ => {
-                    state
-                        .camera_controller
-                        .handle_key(code, key_state.is_pressed());
+            }
+            // Everything else that is keyboard or mouse input goes through
+            // `InputEvent` so that it can be recorded and replayed
+            _ => {
+                if let Some(input) = InputEvent::from_window_event(&event) {
+                    state.handle_event(input);
+                    if state.quit_requested() {
+                        event_loop.exit();
+                    }
                 }
-            },
-            _ => (),
+            }
         }
     }
 
@@ -71,16 +94,90 @@ impl ApplicationHandler for App {
         );
 
         // Use pollster for lightweight blocking on async function
-        self.state = Some(pollster::block_on(AppState::resumed(window)).unwrap());
+        self.state = Some(
+            pollster::block_on(AppState::resumed(window, &self.options, self.replay.take()))
+                .unwrap(),
+        );
+    }
+
+    fn exiting(&mut self, _event_loop: &ActiveEventLoop) {
+        if let Some(state) = &mut self.state
+            && let Err(error) = state.finish_recording()
+        {
+            eprintln!("{:#}", error);
+        }
     }
 }
 
-fn main() {
+/// Prints the checksum a replay ended with. Returns false if it doesn't match
+/// the checksum it was recorded with.
+fn report_replay(replay: &Replay, checksum: u64) -> bool {
+    println!(
+        "Replay finished after {} ticks with checksum {:016x}",
+        replay.ticks, checksum
+    );
+    match replay.checksum {
+        Some(expected) if expected != checksum => {
+            eprintln!(
+                "Replay diverged: it was recorded with checksum {:016x}",
+                expected
+            );
+            false
+        }
+        _ => true,
+    }
+}
+
+/// Draws the game as it is now without a window and saves it to `path`
+fn save_screenshot(game: &mut Game, path: &Path) -> anyhow::Result<()> {
+    let (width, height) = SCREENSHOT_SIZE;
+    let mut graphics_state = pollster::block_on(GraphicsState::offscreen(width, height))?;
+    let atlas = game.load_sprites(&mut graphics_state)?;
+    game.draw(&mut graphics_state, &atlas, 1.0);
+    graphics_state.render()?;
+    graphics_state.save_png(path)?;
+    println!("Saved screenshot to {}", path.display());
+    Ok(())
+}
+
+fn main() -> anyhow::Result<()> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    let replay = options.replay.as_deref().map(Replay::load).transpose()?;
+
+    if options.headless {
+        let replay = replay.context("--headless needs a --replay file to play")?;
+        let mut game = Game::load(replay.seed, replay.scene.as_deref())?;
+        if let Some(path) = &options.checksum_log {
+            game.log_checksums_to(path, replay.seed)?;
+        }
+        replay::play_headless(&mut game, replay.clone());
+        let matched = report_replay(&replay, game.world.checksum());
+        if let Some(path) = &options.screenshot {
+            save_screenshot(&mut game, path)?;
+        }
+        drop(game); // Flushes the checksum log
+        if !matched {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     let event_loop = EventLoop::new().unwrap();
 
     // We use ControlFlow::Poll since we have regular updates without user input
     event_loop.set_control_flow(ControlFlow::Poll);
 
-    let mut app = App { state: None };
-    event_loop.run_app(&mut app).unwrap();
+    let mut app = App {
+        options,
+        replay,
+        replay_diverged: false,
+        state: None,
+    };
+    event_loop.run_app(&mut app)?;
+
+    if app.replay_diverged {
+        drop(app); // Flushes the checksum log
+        std::process::exit(1);
+    }
+    Ok(())
 }
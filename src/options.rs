@@ -0,0 +1,81 @@
+use std::{
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, bail};
+
+/// Command line options
+#[derive(Clone, Debug, PartialEq)]
+pub struct Options {
+    /// Seeds the world's random number generator. Runs with the same seed and
+    /// the same inputs are identical.
+    pub seed: u64,
+    /// File to write every tick's world checksum to, for comparing runs
+    pub checksum_log: Option<PathBuf>,
+    /// File to save every input event to on exit
+    pub record: Option<PathBuf>,
+    /// Replay file to play back instead of taking live input
+    pub replay: Option<PathBuf>,
+    /// Play the replay without a window, as fast as possible
+    pub headless: bool,
+    /// PNG file to render the end of a headless replay to
+    pub screenshot: Option<PathBuf>,
+    /// Scene file to start in instead of the built in sandbox
+    pub scene: Option<PathBuf>,
+}
+
+impl Default for Options {
+    /// Picks a fresh seed from the system clock
+    fn default() -> Self {
+        let seed = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_nanos() as u64)
+            .unwrap_or_default();
+        Self {
+            seed,
+            checksum_log: None,
+            record: None,
+            replay: None,
+            headless: false,
+            screenshot: None,
+            scene: None,
+        }
+    }
+}
+
+impl Options {
+    /// Parses arguments, not including the program name
+    pub fn parse(args: impl IntoIterator<Item = String>) -> anyhow::Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("{} needs a value", arg))
+            };
+            match arg.as_str() {
+                "--seed" => {
+                    let seed = value()?;
+                    options.seed = seed
+                        .parse()
+                        .with_context(|| format!("Invalid seed {:?}", seed))?;
+                }
+                "--checksum-log" => options.checksum_log = Some(value()?.into()),
+                "--record" => options.record = Some(value()?.into()),
+                "--replay" => options.replay = Some(value()?.into()),
+                "--headless" => options.headless = true,
+                "--screenshot" => options.screenshot = Some(value()?.into()),
+                "--scene" => options.scene = Some(value()?.into()),
+                _ => bail!("Unknown argument {:?}", arg),
+            }
+        }
+        if options.headless && options.replay.is_none() {
+            bail!("--headless needs a --replay file to play");
+        }
+        if options.screenshot.is_some() && !options.headless {
+            bail!("--screenshot only works with --headless");
+        }
+        Ok(options)
+    }
+}
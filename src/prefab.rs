@@ -0,0 +1,111 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{Context, bail};
+
+use crate::{
+    components::Transform2D,
+    ecs::entity::Entity,
+    scene::{Scene, SceneTexture},
+    systems,
+    world::World,
+};
+
+/// Prefabs that ship with the game, by name
+const BUILT_IN: &[(&str, &str)] = &[
+    ("door", include_str!("../data/prefabs/door.ron")),
+    (
+        "time-crystal",
+        include_str!("../data/prefabs/time-crystal.ron"),
+    ),
+];
+
+/// Reusable entity templates, looked up by name. Each prefab is a scene
+/// whose first entity is the root the rest are usually attached to. Scenes
+/// and other prefabs use one with an entity like `(prefab: "door", ...)`.
+#[derive(Clone, Debug, Default)]
+pub struct PrefabLibrary {
+    prefabs: BTreeMap<String, Scene>,
+}
+
+impl PrefabLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The prefabs in `data/prefabs`, as of when the game was built
+    pub fn built_in() -> Self {
+        let mut library = Self::new();
+        for (name, text) in BUILT_IN {
+            let prefab =
+                Scene::parse(text, &format!("{}.ron", name)).expect("Built in prefabs are valid");
+            library.insert(name, prefab);
+        }
+        library
+    }
+
+    /// Adds a prefab, replacing any with the same name
+    pub fn insert(&mut self, name: &str, prefab: Scene) {
+        self.prefabs.insert(name.to_string(), prefab);
+    }
+
+    /// Adds every `.ron` file in `dir`, named after the file without its
+    /// extension
+    pub fn load_dir(&mut self, dir: &Path) -> anyhow::Result<()> {
+        let entries =
+            fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+        let mut paths = vec![];
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "ron") {
+                paths.push(path);
+            }
+        }
+        // Sorted so that a failure is reported the same way every time
+        paths.sort();
+
+        for path in paths {
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                bail!("Prefab file name {} isn't valid UTF-8", path.display());
+            };
+            self.insert(name, Scene::load(&path)?);
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Scene> {
+        self.prefabs.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.prefabs.keys().map(String::as_str)
+    }
+
+    /// Every texture any prefab draws with
+    pub fn textures(&self) -> impl Iterator<Item = &SceneTexture> {
+        self.prefabs.values().flat_map(|prefab| &prefab.textures)
+    }
+
+    /// Spawns an instance of the prefab `name` with its root placed by
+    /// `transform`, which may attach it to a parent. Returns the root.
+    pub fn spawn(
+        &self,
+        world: &mut World,
+        name: &str,
+        transform: Transform2D,
+    ) -> anyhow::Result<Entity> {
+        let prefab = self
+            .get(name)
+            .with_context(|| format!("No prefab named {:?}", name))?;
+        let root = *prefab
+            .instantiate(world, self)?
+            .first()
+            .with_context(|| format!("Prefab {:?} has no entities", name))?;
+
+        // Placed where it belongs from the start, rather than sliding there
+        // from the prefab's own position
+        world.transforms.insert(root, transform);
+        world.previous_transforms.insert(root, transform);
+        systems::hierarchy::propagate(world);
+        Ok(root)
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+/// Rewind charges a fresh game starts with
+pub const STARTING_REWIND_CHARGES: u32 = 10;
+
+/// What the player has achieved so far, as opposed to where things are in
+/// the world. Kept across saves.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Progress {
+    /// Item counts by item name. Items the player has none of are left out.
+    pub inventory: BTreeMap<String, u32>,
+    /// Story and puzzle flags that have been set, e.g. "door_1_opened"
+    pub flags: BTreeSet<String>,
+    /// Rewinds left. Kept by saves, but rewinding doesn't spend them.
+    pub rewind_charges: u32,
+    /// Simulated seconds played, including rewound ones
+    pub play_seconds: f64,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self {
+            inventory: BTreeMap::new(),
+            flags: BTreeSet::new(),
+            rewind_charges: STARTING_REWIND_CHARGES,
+            play_seconds: 0.0,
+        }
+    }
+}
+
+impl Progress {
+    pub fn item_count(&self, item: &str) -> u32 {
+        self.inventory.get(item).copied().unwrap_or(0)
+    }
+
+    pub fn add_item(&mut self, item: &str, count: u32) {
+        if count > 0 {
+            *self.inventory.entry(item.to_string()).or_default() += count;
+        }
+    }
+
+    /// Takes `count` of `item` if the player has that many. Returns whether
+    /// they did.
+    pub fn remove_item(&mut self, item: &str, count: u32) -> bool {
+        let held = self.item_count(item);
+        if held < count {
+            return false;
+        }
+        if held == count {
+            self.inventory.remove(item);
+        } else {
+            self.inventory.insert(item.to_string(), held - count);
+        }
+        true
+    }
+
+    pub fn has_flag(&self, flag: &str) -> bool {
+        self.flags.contains(flag)
+    }
+
+    pub fn set_flag(&mut self, flag: &str) {
+        self.flags.insert(flag.to_string());
+    }
+}
@@ -0,0 +1,60 @@
+use cgmath::Vector2;
+
+use crate::{
+    ecs::entity::Entity,
+    graphics::{GraphicsState, blend::BlendMode, color::Color},
+};
+
+/// A pressure plate that is held down while any controllable entity, player
+/// or echo, overlaps its collider
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct Switch {
+    pub pressed: bool,
+}
+
+impl Switch {
+    pub fn draw(
+        &self,
+        position: Vector2<f32>,
+        size: Vector2<f32>,
+        graphics_state: &mut GraphicsState,
+    ) {
+        let color = if self.pressed {
+            Color::rgb(0.2, 0.9, 0.3)
+        } else {
+            Color::rgb(0.5, 0.1, 0.1)
+        };
+        graphics_state.push_debug_square(position, size, 0.0, color, BlendMode::Opaque);
+    }
+}
+
+/// Blocks controllable entities with its collider until every one of its
+/// switches is pressed at the same time. A door without switches never opens.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Door {
+    pub switches: Vec<Entity>,
+    pub open: bool,
+}
+
+impl Door {
+    pub fn new(switches: Vec<Entity>) -> Self {
+        Self {
+            switches,
+            open: false,
+        }
+    }
+
+    pub fn draw(
+        &self,
+        position: Vector2<f32>,
+        size: Vector2<f32>,
+        graphics_state: &mut GraphicsState,
+    ) {
+        let color = if self.open {
+            Color::rgba(0.6, 0.4, 0.2, 0.25)
+        } else {
+            Color::rgb(0.6, 0.4, 0.2)
+        };
+        graphics_state.push_debug_square(position, size, 0.0, color, BlendMode::Alpha);
+    }
+}
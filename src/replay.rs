@@ -0,0 +1,158 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    game::{Game, TICKS_PER_SECOND},
+    game_clock::GameClock,
+    input::{event::InputEvent, map::Bindings},
+};
+
+/// Bumped whenever the replay format or the simulation changes in a way that
+/// would make old replays play back differently
+pub const REPLAY_VERSION: u32 = 1;
+
+/// An input event and the tick it arrived before
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RecordedEvent {
+    /// Number of ticks simulated before the event arrived. Counts every tick
+    /// run, including rewound ones, so it never goes backwards.
+    pub tick: u64,
+    pub event: InputEvent,
+}
+
+/// Everything needed to reproduce a run: the seed, every input event and
+/// when it happened. Stored as RON.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub version: u32,
+    pub seed: u64,
+    /// Scene file the run started in, or `None` for the built in sandbox
+    #[serde(default)]
+    pub scene: Option<PathBuf>,
+    /// How the game's actions were bound during the run, or `None` for the
+    /// default bindings
+    #[serde(default)]
+    pub bindings: Option<Bindings>,
+    pub events: Vec<RecordedEvent>,
+    /// Total ticks simulated by the recorded run
+    pub ticks: u64,
+    /// World checksum after the last tick of the recorded run
+    pub checksum: Option<u64>,
+}
+
+impl Replay {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            seed,
+            scene: None,
+            bindings: None,
+            events: vec![],
+            ticks: 0,
+            checksum: None,
+        }
+    }
+
+    pub fn record(&mut self, tick: u64, event: InputEvent) {
+        self.events.push(RecordedEvent { tick, event });
+    }
+
+    /// Marks where the recorded run ended so playback can stop there
+    pub fn finish(&mut self, ticks: u64, checksum: u64) {
+        self.ticks = ticks;
+        self.checksum = Some(checksum);
+    }
+
+    /// Binds the game's actions the way they were during the run, so the
+    /// recorded keys do the same things again
+    pub fn apply_bindings(&self, game: &mut Game) {
+        if let Some(bindings) = &self.bindings {
+            game.input.set_all_bindings(bindings);
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read replay {}", path.display()))?;
+        let replay: Replay = ron::from_str(&text)
+            .with_context(|| format!("Failed to parse replay {}", path.display()))?;
+        if replay.version != REPLAY_VERSION {
+            bail!(
+                "Replay {} has version {}, but this build plays version {}",
+                path.display(),
+                replay.version,
+                REPLAY_VERSION
+            );
+        }
+        Ok(replay)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        fs::write(path, text).with_context(|| format!("Failed to write replay {}", path.display()))
+    }
+}
+
+/// Feeds a replay's events back in at the ticks they were recorded on
+pub struct ReplayPlayer {
+    replay: Replay,
+    next_event: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> Self {
+        Self {
+            replay,
+            next_event: 0,
+        }
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// Events that arrived before tick `tick` was simulated and haven't been
+    /// returned yet
+    pub fn take_due(&mut self, tick: u64) -> &[RecordedEvent] {
+        let start = self.next_event;
+        while self
+            .replay
+            .events
+            .get(self.next_event)
+            .is_some_and(|recorded| recorded.tick <= tick)
+        {
+            self.next_event += 1;
+        }
+        &self.replay.events[start..self.next_event]
+    }
+
+    /// Applies the events due before the game's next tick
+    pub fn feed(&mut self, game: &mut Game) {
+        let tick = game.ticks();
+        for recorded in self.take_due(tick) {
+            game.handle_event(&recorded.event);
+        }
+    }
+
+    /// Whether `tick` ticks cover the whole recorded run
+    pub fn is_finished(&self, tick: u64) -> bool {
+        tick >= self.replay.ticks
+    }
+}
+
+/// Plays a replay on `game` to its end as fast as possible, without a window.
+/// `game` should be freshly created with the replay's seed.
+pub fn play_headless(game: &mut Game, replay: Replay) {
+    let step_seconds = GameClock::new(TICKS_PER_SECOND).step_seconds();
+    replay.apply_bindings(game);
+    let mut player = ReplayPlayer::new(replay);
+    while !player.is_finished(game.ticks()) {
+        player.feed(game);
+        game.update(step_seconds);
+    }
+}
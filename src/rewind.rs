@@ -0,0 +1,103 @@
+use std::collections::VecDeque;
+
+use crate::world::{World, WorldSnapshot};
+
+/// Bounded history of per-tick snapshots. Once full, recording a new snapshot
+/// forgets the oldest one.
+pub struct RewindBuffer<T> {
+    snapshots: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> RewindBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            snapshots: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    /// Creates a buffer that holds `seconds` of history at the given tick rate
+    pub fn with_seconds(seconds: f32, ticks_per_second: u32) -> Self {
+        Self::new((seconds * ticks_per_second as f32).ceil() as usize)
+    }
+
+    pub fn record(&mut self, snapshot: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.snapshots.len() == self.capacity {
+            self.snapshots.pop_front();
+        }
+        self.snapshots.push_back(snapshot);
+    }
+
+    /// Removes and returns the most recent snapshot. Stepping back through
+    /// history consumes it, so resuming forward simulation afterwards
+    /// naturally discards the future that was rewound over.
+    pub fn rewind(&mut self) -> Option<T> {
+        self.snapshots.pop_back()
+    }
+
+    pub fn len(&self) -> usize {
+        self.snapshots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.snapshots.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn clear(&mut self) {
+        self.snapshots.clear();
+    }
+}
+
+/// Drives a `World` forwards or backwards one tick at a time, recording
+/// history while going forwards and consuming it while rewinding
+pub struct Rewind {
+    history: RewindBuffer<WorldSnapshot>,
+    rewinding: bool,
+}
+
+impl Rewind {
+    /// Keeps `seconds` of history at the given tick rate
+    pub fn new(seconds: f32, ticks_per_second: u32) -> Self {
+        Self {
+            history: RewindBuffer::with_seconds(seconds, ticks_per_second),
+            rewinding: false,
+        }
+    }
+
+    pub fn is_rewinding(&self) -> bool {
+        self.rewinding
+    }
+
+    /// Plays the world backwards while set. Clearing it resumes forward
+    /// simulation from wherever the rewind stopped.
+    pub fn set_rewinding(&mut self, rewinding: bool) {
+        self.rewinding = rewinding;
+    }
+
+    /// Ticks of history available to rewind through
+    pub fn history_len(&self) -> usize {
+        self.history.len()
+    }
+
+    /// Advances `world` by one tick of `dt` seconds, or steps it back one
+    /// tick while rewinding
+    pub fn update(&mut self, world: &mut World, dt: f32) {
+        if self.rewinding {
+            // Hold on the oldest recorded state once history runs out
+            if let Some(snapshot) = self.history.rewind() {
+                world.restore(&snapshot);
+            }
+        } else {
+            self.history.record(world.snapshot());
+            world.update(dt);
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, bail};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::{determinism::Checksum, progress::Progress};
+
+/// Bumped whenever `SaveData` changes shape. Each bump needs a migration in
+/// `MIGRATIONS` that turns saves of the previous schema into the new one.
+pub const SAVE_SCHEMA: u32 = 1;
+
+/// Slots are numbered from 1 up to this
+pub const SLOT_COUNT: u32 = 9;
+
+/// First line of every save file, followed by the schema and checksum
+const MAGIC: &str = "time_game save";
+
+/// Upgrades a save from `from` to `from + 1`, working on the parsed RON
+/// since the old `SaveData` no longer exists to parse it into
+pub struct Migration {
+    pub from: u32,
+    pub migrate: fn(&mut ron::Value) -> anyhow::Result<()>,
+}
+
+/// Every migration ever needed, oldest first
+const MIGRATIONS: &[Migration] = &[];
+
+/// Everything a save slot keeps about a game in progress
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SaveData {
+    /// Scene file the game was playing, or `None` for the built in sandbox
+    pub scene: Option<PathBuf>,
+    pub player_position: (f32, f32),
+    pub progress: Progress,
+    /// Seconds since the Unix epoch when the save was written
+    pub saved_at: u64,
+}
+
+impl SaveData {
+    /// Stamps the data with the current time
+    pub fn new(scene: Option<PathBuf>, player_position: (f32, f32), progress: Progress) -> Self {
+        let saved_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        Self {
+            scene,
+            player_position,
+            progress,
+            saved_at,
+        }
+    }
+}
+
+/// Numbered save files in one directory. Saves are written to a temporary
+/// file and then renamed over the old one, so a crash mid-save leaves the
+/// previous save intact. Each file carries a checksum of its contents so
+/// corruption is noticed on load.
+pub struct SaveSlots {
+    dir: PathBuf,
+    /// Bring saves of older schemas up to date on load
+    migrations: &'static [Migration],
+}
+
+impl SaveSlots {
+    /// Slots stored in `dir`, which is created on the first save
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            migrations: MIGRATIONS,
+        }
+    }
+
+    /// Loads old saves with `migrations` instead of the built in ones, e.g.
+    /// to try out a migration before the schema bump that needs it
+    pub fn with_migrations(self, migrations: &'static [Migration]) -> Self {
+        Self { migrations, ..self }
+    }
+
+    /// Slots in the `time_game/saves` directory under the user's data
+    /// directory, e.g. `~/.local/share` on Linux
+    pub fn in_user_data_dir() -> anyhow::Result<Self> {
+        let data_dir = dirs::data_dir().context("Couldn't find the user data directory")?;
+        Ok(Self::new(data_dir.join("time_game").join("saves")))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn path(&self, slot: u32) -> PathBuf {
+        self.dir.join(format!("slot{}.sav", slot))
+    }
+
+    fn check_slot(slot: u32) -> anyhow::Result<()> {
+        if !(1..=SLOT_COUNT).contains(&slot) {
+            bail!("There is no save slot {}, only 1 to {}", slot, SLOT_COUNT);
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, slot: u32, data: &SaveData) -> anyhow::Result<()> {
+        Self::check_slot(slot)?;
+        fs::create_dir_all(&self.dir)
+            .with_context(|| format!("Failed to create {}", self.dir.display()))?;
+
+        let path = self.path(slot);
+        let temporary = path.with_extension("sav.tmp");
+        let write = || -> anyhow::Result<()> {
+            let mut file = File::create(&temporary)?;
+            file.write_all(encode(data)?.as_bytes())?;
+            // Make sure the data is on disk before it replaces the old save
+            file.sync_all()?;
+            fs::rename(&temporary, &path)?;
+            Ok(())
+        };
+        write().with_context(|| format!("Failed to write save slot {}", slot))
+    }
+
+    /// The save in `slot`, or `None` if it is empty
+    pub fn load(&self, slot: u32) -> anyhow::Result<Option<SaveData>> {
+        Self::check_slot(slot)?;
+        let path = self.path(slot);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read save slot {}", slot))?;
+        decode_with(&text, self.migrations)
+            .map(Some)
+            .with_context(|| format!("Save slot {} ({}) can't be loaded", slot, path.display()))
+    }
+
+    /// Every slot that holds a save that loads, in slot order
+    pub fn list(&self) -> Vec<(u32, SaveData)> {
+        (1..=SLOT_COUNT)
+            .filter_map(|slot| Some((slot, self.load(slot).ok()??)))
+            .collect()
+    }
+
+    /// Empties `slot`. Deleting an empty slot is fine.
+    pub fn delete(&self, slot: u32) -> anyhow::Result<()> {
+        Self::check_slot(slot)?;
+        match fs::remove_file(self.path(slot)) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                Err(error).with_context(|| format!("Failed to delete save slot {}", slot))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+fn checksum(body: &str) -> u64 {
+    let mut checksum = Checksum::new();
+    checksum.write_bytes(body.as_bytes());
+    checksum.finish()
+}
+
+/// The contents of a save file: a header line with the schema and a checksum
+/// of the rest, then the data as RON
+pub fn encode(data: &SaveData) -> anyhow::Result<String> {
+    let body = ron::ser::to_string_pretty(data, PrettyConfig::default())?;
+    Ok(format!(
+        "{} {} {:016x}\n{}",
+        MAGIC,
+        SAVE_SCHEMA,
+        checksum(&body),
+        body
+    ))
+}
+
+/// Reads a save file's contents, checking the checksum and migrating saves
+/// of older schemas
+pub fn decode(text: &str) -> anyhow::Result<SaveData> {
+    decode_with(text, MIGRATIONS)
+}
+
+/// `decode`, migrating older schemas with `migrations`
+pub fn decode_with(text: &str, migrations: &[Migration]) -> anyhow::Result<SaveData> {
+    let (header, body) = text.split_once('\n').context("Save file is truncated")?;
+    let Some(fields) = header.strip_prefix(MAGIC) else {
+        bail!("Not a save file");
+    };
+    let (schema, expected) = match fields.split_whitespace().collect::<Vec<_>>()[..] {
+        [schema, expected] => (schema, expected),
+        _ => bail!("Save file header is malformed"),
+    };
+    let schema: u32 = schema.parse().context("Save file schema is malformed")?;
+    let expected = u64::from_str_radix(expected, 16).context("Save file checksum is malformed")?;
+    if checksum(body) != expected {
+        bail!("Save file is corrupted: its checksum doesn't match");
+    }
+
+    if schema > SAVE_SCHEMA {
+        bail!(
+            "Save file has schema {}, but this build reads up to schema {}",
+            schema,
+            SAVE_SCHEMA
+        );
+    }
+    if schema == SAVE_SCHEMA {
+        return Ok(ron::from_str(body)?);
+    }
+    let mut value: ron::Value = ron::from_str(body)?;
+    migrate(&mut value, schema, SAVE_SCHEMA, migrations)?;
+    Ok(value.into_rust()?)
+}
+
+/// Runs the migrations that take `value` from schema `from` to schema `to`,
+/// in order
+pub fn migrate(
+    value: &mut ron::Value,
+    from: u32,
+    to: u32,
+    migrations: &[Migration],
+) -> anyhow::Result<()> {
+    for schema in from..to {
+        let migration = migrations
+            .iter()
+            .find(|migration| migration.from == schema)
+            .with_context(|| format!("No migration from save schema {}", schema))?;
+        (migration.migrate)(value)
+            .with_context(|| format!("Failed to migrate save from schema {}", schema))?;
+    }
+    Ok(())
+}
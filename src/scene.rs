@@ -0,0 +1,657 @@
+use std::{collections::HashMap, fs, path::Path, path::PathBuf};
+
+use anyhow::{Context, bail};
+use cgmath::Vector2;
+use ron::{extensions::Extensions, ser::PrettyConfig};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    components::{Collider, Shape, ShapeKind, Sprite, TimeScale, Transform2D, Velocity},
+    echo::Controller,
+    ecs::entity::Entity,
+    graphics::{blend::BlendMode, color::Color},
+    prefab::PrefabLibrary,
+    puzzle::{Door, Switch},
+    systems,
+    time_field::TimeField,
+    world::{Bundle, World},
+};
+
+/// Bumped whenever the scene format changes in a way old scenes can't be
+/// read with
+pub const SCENE_VERSION: u32 = 1;
+
+/// The level the game starts in unless another scene is given
+pub const DEFAULT_SCENE: &str = include_str!("../data/scenes/sandbox.ron");
+
+/// A level: the textures it draws with and the entities it starts with.
+/// Stored as RON with `implicit_some`, so optional components are written
+/// without `Some(...)`.
+///
+/// Misspelled fields are errors rather than silently ignored.
+///
+/// Entities refer to each other by name, e.g. a door lists the names of the
+/// switches that open it. Names only need to be given to entities something
+/// refers to. Sprites draw with textures listed in the same file.
+///
+/// Prefabs are stored in the same format, with the first entity as the
+/// root that instances are placed by.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scene {
+    pub version: u32,
+    #[serde(default)]
+    pub textures: Vec<SceneTexture>,
+    #[serde(default)]
+    pub entities: Vec<SceneEntity>,
+}
+
+/// An image file to add to the sprite atlas under `name`. Paths are relative
+/// to the working directory.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneTexture {
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// An entity and its components. Anything left out is omitted.
+///
+/// With `prefab` set, this is an instance of the named prefab instead and
+/// expands to all of the prefab's entities. Components given alongside it
+/// replace the prefab root's, e.g. `transform` places the instance. Names
+/// inside a prefab are private to each instance; the instance's own `name`
+/// names its root.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SceneEntity {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefab: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub transform: Option<SceneTransform>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub velocity: Option<SceneVelocity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_scale: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sprite: Option<SceneSprite>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shape: Option<SceneShape>,
+    /// Half extents of the entity's collider
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub collider: Option<(f32, f32)>,
+    /// Controlled by the player. The game expects exactly one.
+    #[serde(skip_serializing_if = "is_false")]
+    pub player: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_field: Option<SceneTimeField>,
+    #[serde(skip_serializing_if = "is_false")]
+    pub switch: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub door: Option<SceneDoor>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneTransform {
+    pub position: (f32, f32),
+    /// Radians, counterclockwise
+    #[serde(default)]
+    pub rotation: f32,
+    #[serde(default = "unit_scale")]
+    pub scale: (f32, f32),
+    /// Name of the entity this one is attached to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneVelocity {
+    #[serde(default)]
+    pub linear: (f32, f32),
+    /// Radians per second
+    #[serde(default)]
+    pub angular: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneSprite {
+    /// Name of one of the scene's textures
+    pub texture: String,
+    pub size: (f32, f32),
+    #[serde(default)]
+    pub layer: u32,
+    #[serde(default = "white")]
+    pub tint: Color,
+    #[serde(default = "premultiplied_alpha")]
+    pub blend: BlendMode,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneShape {
+    pub kind: ShapeKind,
+    pub size: (f32, f32),
+    pub color: Color,
+    #[serde(default = "opaque")]
+    pub blend: BlendMode,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneTimeField {
+    pub half_extents: (f32, f32),
+    pub multiplier: f32,
+    /// Name of the entity the field follows
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneDoor {
+    /// Names of the switches that all have to be pressed to open the door
+    pub switches: Vec<String>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn unit_scale() -> (f32, f32) {
+    (1.0, 1.0)
+}
+
+fn white() -> Color {
+    Color::WHITE
+}
+
+fn premultiplied_alpha() -> BlendMode {
+    BlendMode::PremultipliedAlpha
+}
+
+fn opaque() -> BlendMode {
+    BlendMode::Opaque
+}
+
+fn vector((x, y): (f32, f32)) -> Vector2<f32> {
+    Vector2::new(x, y)
+}
+
+fn pair(vector: Vector2<f32>) -> (f32, f32) {
+    (vector.x, vector.y)
+}
+
+/// Just enough of a scene to check its version before parsing the rest
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+/// A problem with a scene that parsed fine
+struct Invalid {
+    message: String,
+    /// Where in the text the problem is
+    at: Location,
+}
+
+/// Identifies the offending line of a scene that parsed fine by the name
+/// involved, since parsed scenes don't remember where anything came from
+enum Location {
+    /// The second definition of a name
+    Redefinition(String),
+    /// The first use of a name other than a definition
+    Reference(String),
+}
+
+impl Location {
+    /// 1-based line number in `text`. Whitespace is ignored when matching
+    /// so hand formatted scenes are found too.
+    fn line_in(&self, text: &str) -> Option<usize> {
+        let strip = |text: &str| text.split_whitespace().collect::<String>();
+        let lines = text.lines().map(strip).enumerate();
+        let found = match self {
+            Location::Redefinition(name) => {
+                let definition = strip(&format!("name: {:?}", name));
+                lines.filter(|(_, line)| line.contains(&definition)).nth(1)
+            }
+            Location::Reference(name) => {
+                let definition = strip(&format!("name: {:?}", name));
+                let quoted = strip(&format!("{:?}", name));
+                lines
+                    .filter(|(_, line)| !line.contains(&definition))
+                    .find(|(_, line)| line.contains(&quoted))
+            }
+        };
+        found.map(|(index, _)| index + 1)
+    }
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Self {
+            version: SCENE_VERSION,
+            textures: vec![],
+            entities: vec![],
+        }
+    }
+
+    pub fn load(path: &Path) -> anyhow::Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read scene {}", path.display()))?;
+        Self::parse(&text, &path.display().to_string())
+    }
+
+    /// Parses a scene from RON text. Errors start with `origin:line:column`
+    /// of the offending part, where `origin` is e.g. the file name.
+    pub fn parse(text: &str, origin: &str) -> anyhow::Result<Self> {
+        let options = ron::Options::default().with_default_extension(Extensions::IMPLICIT_SOME);
+        let located = |error: ron::error::SpannedError| {
+            let start = error.span.start;
+            anyhow::anyhow!("{}:{}:{}: {}", origin, start.line, start.col, error.code)
+        };
+
+        let header: Header = options.from_str(text).map_err(located)?;
+        if header.version != SCENE_VERSION {
+            bail!(
+                "{}: Scene has version {}, but this build reads version {}",
+                origin,
+                header.version,
+                SCENE_VERSION
+            );
+        }
+
+        let scene: Scene = options.from_str(text).map_err(located)?;
+        if let Err(invalid) = scene.check() {
+            match invalid.at.line_in(text) {
+                Some(line) => bail!("{}:{}: {}", origin, line, invalid.message),
+                None => bail!("{}: {}", origin, invalid.message),
+            }
+        }
+        Ok(scene)
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        fs::write(path, self.to_ron()?)
+            .with_context(|| format!("Failed to write scene {}", path.display()))
+    }
+
+    pub fn to_ron(&self) -> anyhow::Result<String> {
+        let config = PrettyConfig::default().extensions(Extensions::IMPLICIT_SOME);
+        Ok(ron::ser::to_string_pretty(self, config)?)
+    }
+
+    /// Checks that names are unique and every reference names an entity of
+    /// the right kind
+    fn check(&self) -> Result<(), Invalid> {
+        let mut names = HashMap::new();
+        for entity in &self.entities {
+            if let Some(name) = &entity.name
+                && names.insert(name.as_str(), entity).is_some()
+            {
+                return Err(Invalid {
+                    message: format!("Entity name {:?} is used more than once", name),
+                    at: Location::Redefinition(name.clone()),
+                });
+            }
+        }
+        let textures: Vec<&str> = self.textures.iter().map(|t| t.name.as_str()).collect();
+
+        let missing = |kind: &str, name: &str| Invalid {
+            message: format!("No {} named {:?}", kind, name),
+            at: Location::Reference(name.to_string()),
+        };
+        for entity in &self.entities {
+            if let Some(parent) = entity.transform.as_ref().and_then(|t| t.parent.as_ref()) {
+                match names.get(parent.as_str()) {
+                    // What prefab instances contain is checked once expanded
+                    Some(parent) if parent.transform.is_some() || parent.prefab.is_some() => (),
+                    Some(_) => {
+                        return Err(Invalid {
+                            message: format!("Parent {:?} has no transform", parent),
+                            at: Location::Reference(parent.clone()),
+                        });
+                    }
+                    None => return Err(missing("entity", parent)),
+                }
+            }
+            if let Some(anchor) = entity.time_field.as_ref().and_then(|f| f.anchor.as_ref())
+                && !names.contains_key(anchor.as_str())
+            {
+                return Err(missing("entity", anchor));
+            }
+            for switch in entity.door.iter().flat_map(|door| &door.switches) {
+                match names.get(switch.as_str()) {
+                    Some(switch) if switch.switch || switch.prefab.is_some() => (),
+                    Some(_) => {
+                        return Err(Invalid {
+                            message: format!("Entity {:?} is not a switch", switch),
+                            at: Location::Reference(switch.clone()),
+                        });
+                    }
+                    None => return Err(missing("switch", switch)),
+                }
+            }
+            if let Some(sprite) = &entity.sprite
+                && !textures.contains(&sprite.texture.as_str())
+            {
+                return Err(missing("texture", &sprite.texture));
+            }
+        }
+        Ok(())
+    }
+
+    /// Spawns every entity of the scene into `world`, expanding prefab
+    /// instances from `prefabs`. Returns the spawned entities in the order
+    /// they are listed, with each prefab instance replaced by the prefab's
+    /// entities, root first.
+    pub fn instantiate(
+        &self,
+        world: &mut World,
+        prefabs: &PrefabLibrary,
+    ) -> anyhow::Result<Vec<Entity>> {
+        if let Err(invalid) = self.check() {
+            bail!(invalid.message);
+        }
+        let mut expanded = vec![];
+        expand(
+            &self.entities,
+            0,
+            prefabs,
+            &mut vec![],
+            &mut 0,
+            &mut expanded,
+        )?;
+
+        // Spawn everything first so that references can point forwards
+        let entities: Vec<Entity> = expanded
+            .iter()
+            .map(|expanded| world.spawn(expanded.entity.bundle()))
+            .collect();
+        let mut named = HashMap::new();
+        for (expanded, &id) in expanded.iter().zip(&entities) {
+            for (scope, name) in &expanded.names {
+                named.insert((*scope, name.as_str()), id);
+            }
+        }
+        let find = |scope: usize, name: &str| {
+            named
+                .get(&(scope, name))
+                .copied()
+                .with_context(|| format!("No entity named {:?}", name))
+        };
+
+        for (expanded, &id) in expanded.iter().zip(&entities) {
+            let entity = &expanded.entity;
+            if let Some(name) = entity.transform.as_ref().and_then(|t| t.parent.as_ref()) {
+                let parent = find(expanded.parent_scope, name)?;
+                if !world.transforms.contains(parent) {
+                    bail!("Parent {:?} has no transform", name);
+                }
+                world.transforms.get_mut(id).unwrap().parent = Some(parent);
+            }
+            if let Some(anchor) = entity.time_field.as_ref().and_then(|f| f.anchor.as_ref()) {
+                world.time_fields.get_mut(id).unwrap().anchor =
+                    Some(find(expanded.anchor_scope, anchor)?);
+            }
+            if let Some(door) = &entity.door {
+                let switches = door
+                    .switches
+                    .iter()
+                    .map(|switch| {
+                        let found = find(expanded.door_scope, switch)?;
+                        match world.switches.contains(found) {
+                            true => Ok(found),
+                            false => Err(anyhow::anyhow!("Entity {:?} is not a switch", switch)),
+                        }
+                    })
+                    .collect::<anyhow::Result<_>>()?;
+                world.doors.get_mut(id).unwrap().switches = switches;
+            }
+        }
+        systems::hierarchy::propagate(world);
+        Ok(entities)
+    }
+
+    /// Captures the live entities of `world`, drawing with `textures`. Echoes
+    /// are left out since they only make sense with the history that made
+    /// them. Prefab instances are saved as the entities they expanded to.
+    /// Entities something refers to are named after their position in the
+    /// scene.
+    pub fn from_world(world: &World, textures: &[SceneTexture]) -> Self {
+        let saved: Vec<Entity> = world
+            .entities()
+            .filter(|&entity| !matches!(world.controllers.get(entity), Some(Controller::Echo(_))))
+            .collect();
+
+        let mut referenced = vec![];
+        for &entity in &saved {
+            referenced.extend(world.transforms.get(entity).and_then(|t| t.parent));
+            referenced.extend(world.time_fields.get(entity).and_then(|f| f.anchor));
+            referenced.extend(
+                world
+                    .doors
+                    .get(entity)
+                    .into_iter()
+                    .flat_map(|d| &d.switches),
+            );
+        }
+        let names: HashMap<Entity, String> = saved
+            .iter()
+            .enumerate()
+            .filter(|(_, entity)| referenced.contains(entity))
+            .map(|(i, &entity)| (entity, format!("entity{}", i)))
+            .collect();
+        let name = |entity: Entity| names.get(&entity).cloned();
+
+        let entities = saved
+            .iter()
+            .map(|&entity| SceneEntity {
+                name: name(entity),
+                prefab: None,
+                transform: world.transforms.get(entity).map(|t| SceneTransform {
+                    position: pair(t.position),
+                    rotation: t.rotation,
+                    scale: pair(t.scale),
+                    parent: t.parent.and_then(name),
+                }),
+                velocity: world.velocities.get(entity).map(|v| SceneVelocity {
+                    linear: pair(v.linear),
+                    angular: v.angular,
+                }),
+                time_scale: world.time_scales.get(entity).map(|scale| scale.0),
+                sprite: world.sprites.get(entity).map(|sprite| SceneSprite {
+                    texture: sprite.name.clone(),
+                    size: pair(sprite.dimensions),
+                    layer: sprite.layer,
+                    tint: sprite.tint,
+                    blend: sprite.blend,
+                }),
+                shape: world.shapes.get(entity).map(|shape| SceneShape {
+                    kind: shape.kind,
+                    size: pair(shape.scale),
+                    color: shape.color,
+                    blend: shape.blend,
+                }),
+                collider: world.colliders.get(entity).map(|c| pair(c.half_extents)),
+                player: matches!(world.controllers.get(entity), Some(Controller::Player)),
+                time_field: world.time_fields.get(entity).map(|field| SceneTimeField {
+                    half_extents: pair(field.half_extents),
+                    multiplier: field.multiplier,
+                    anchor: field.anchor.and_then(name),
+                }),
+                switch: world.switches.contains(entity),
+                door: world.doors.get(entity).map(|door| SceneDoor {
+                    switches: door.switches.iter().filter_map(|&s| name(s)).collect(),
+                }),
+            })
+            .collect();
+
+        Self {
+            version: SCENE_VERSION,
+            textures: textures.to_vec(),
+            entities,
+        }
+    }
+}
+
+impl Default for Scene {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A scene entity with prefab instances replaced by what they expand to
+struct Expanded {
+    entity: SceneEntity,
+    /// Every name the entity goes by and the namespace it is in. The root of
+    /// a prefab instance is known by its name in the prefab and, if the
+    /// instance is named, by that name in the surrounding file.
+    names: Vec<(usize, String)>,
+    /// Namespaces the references in the entity's components are looked up
+    /// in. Components overridden by an instance refer to entities around the
+    /// instance rather than inside the prefab.
+    parent_scope: usize,
+    anchor_scope: usize,
+    door_scope: usize,
+}
+
+/// Appends `entities` to `expanded`, recursively replacing prefab instances
+/// with the prefab's entities. Each prefab instance gets a fresh namespace
+/// so its names can't clash with anything else. `stack` holds the prefabs
+/// being expanded, to catch prefabs that contain themselves.
+fn expand(
+    entities: &[SceneEntity],
+    scope: usize,
+    prefabs: &PrefabLibrary,
+    stack: &mut Vec<String>,
+    scopes: &mut usize,
+    expanded: &mut Vec<Expanded>,
+) -> anyhow::Result<()> {
+    for entity in entities {
+        let Some(prefab_name) = &entity.prefab else {
+            expanded.push(Expanded {
+                entity: entity.clone(),
+                names: entity
+                    .name
+                    .iter()
+                    .map(|name| (scope, name.clone()))
+                    .collect(),
+                parent_scope: scope,
+                anchor_scope: scope,
+                door_scope: scope,
+            });
+            continue;
+        };
+
+        if stack.contains(prefab_name) {
+            bail!(
+                "Prefab {:?} contains itself via {}",
+                prefab_name,
+                stack.join(" -> ")
+            );
+        }
+        let prefab = prefabs
+            .get(prefab_name)
+            .with_context(|| format!("No prefab named {:?}", prefab_name))?;
+        if prefab.entities.is_empty() {
+            bail!("Prefab {:?} has no entities", prefab_name);
+        }
+
+        *scopes += 1;
+        let inner = *scopes;
+        let root = expanded.len();
+        stack.push(prefab_name.clone());
+        expand(&prefab.entities, inner, prefabs, stack, scopes, expanded)?;
+        stack.pop();
+
+        let root = &mut expanded[root];
+        root.names
+            .extend(entity.name.iter().map(|name| (scope, name.clone())));
+        root.apply_overrides(entity, scope);
+    }
+    Ok(())
+}
+
+impl Expanded {
+    /// Replaces the components `instance` gives with the instance's own
+    fn apply_overrides(&mut self, instance: &SceneEntity, scope: usize) {
+        let entity = &mut self.entity;
+        if let Some(transform) = &instance.transform {
+            entity.transform = Some(transform.clone());
+            self.parent_scope = scope;
+        }
+        if let Some(velocity) = &instance.velocity {
+            entity.velocity = Some(velocity.clone());
+        }
+        if let Some(time_scale) = instance.time_scale {
+            entity.time_scale = Some(time_scale);
+        }
+        if let Some(sprite) = &instance.sprite {
+            entity.sprite = Some(sprite.clone());
+        }
+        if let Some(shape) = &instance.shape {
+            entity.shape = Some(shape.clone());
+        }
+        if let Some(collider) = instance.collider {
+            entity.collider = Some(collider);
+        }
+        entity.player |= instance.player;
+        if let Some(time_field) = &instance.time_field {
+            entity.time_field = Some(time_field.clone());
+            self.anchor_scope = scope;
+        }
+        entity.switch |= instance.switch;
+        if let Some(door) = &instance.door {
+            entity.door = Some(door.clone());
+            self.door_scope = scope;
+        }
+    }
+}
+
+impl SceneEntity {
+    /// The entity's components, minus references to other entities
+    fn bundle(&self) -> Bundle {
+        Bundle {
+            transform: self.transform.as_ref().map(|t| Transform2D {
+                position: vector(t.position),
+                rotation: t.rotation,
+                scale: vector(t.scale),
+                parent: None,
+            }),
+            velocity: self.velocity.as_ref().map(|v| Velocity {
+                linear: vector(v.linear),
+                angular: v.angular,
+            }),
+            time_scale: self.time_scale.map(TimeScale),
+            sprite: self.sprite.as_ref().map(|sprite| Sprite {
+                name: sprite.texture.clone(),
+                dimensions: vector(sprite.size),
+                layer: sprite.layer,
+                tint: sprite.tint,
+                blend: sprite.blend,
+            }),
+            shape: self.shape.as_ref().map(|shape| Shape {
+                kind: shape.kind,
+                scale: vector(shape.size),
+                color: shape.color,
+                blend: shape.blend,
+            }),
+            collider: self
+                .collider
+                .map(|half_extents| Collider::new(vector(half_extents))),
+            controller: self.player.then_some(Controller::Player),
+            time_field: self
+                .time_field
+                .as_ref()
+                .map(|field| TimeField::new(vector(field.half_extents), field.multiplier)),
+            switch: self.switch.then(Switch::default),
+            door: self.door.as_ref().map(|_| Door::new(vec![])),
+            ..Bundle::default()
+        }
+    }
+}
@@ -0,0 +1,38 @@
+use crate::{
+    components::Velocity,
+    echo::{Controller, WALK_SPEED},
+    world::World,
+};
+
+/// Sets the velocity of every controllable entity from its input for `tick`,
+/// recording the input of player controlled ones
+pub fn run(world: &mut World, tick: u64) {
+    for (entity, controller) in world.controllers.iter_mut() {
+        let input = match controller {
+            Controller::Player => {
+                if let (Some(timeline), Some(transform)) = (
+                    world.timelines.get_mut(entity),
+                    world.transforms.get(entity),
+                ) {
+                    timeline.record(tick, *transform, world.player_input);
+                }
+                world.player_input
+            }
+            Controller::Echo(playback) => playback.next_input(),
+        };
+
+        let linear = input.direction() * WALK_SPEED;
+        match world.velocities.get_mut(entity) {
+            Some(velocity) => velocity.linear = linear,
+            None => {
+                world.velocities.insert(
+                    entity,
+                    Velocity {
+                        linear,
+                        ..Velocity::ZERO
+                    },
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,74 @@
+use cgmath::{Matrix3, SquareMatrix, Vector2};
+
+use crate::{
+    components::Transform2D,
+    ecs::{entity::Entity, storage::Storage},
+    world::World,
+};
+
+/// Recomputes the world matrix of every entity with a transform from its
+/// local transform and its parent's world matrix
+pub fn propagate(world: &mut World) {
+    world.world_matrices = world_matrices(&world.transforms, |_, transform| *transform);
+}
+
+/// World matrices `alpha` of the way from the previous tick's transforms to
+/// the current ones, for rendering between ticks. Local transforms are
+/// interpolated before they are combined so that rotating parents swing their
+/// children along an arc rather than cutting across it.
+pub fn interpolated(world: &World, alpha: f32) -> Storage<Matrix3<f32>> {
+    world_matrices(&world.transforms, |entity, transform| {
+        world
+            .previous_transforms
+            .get(entity)
+            .map_or(*transform, |previous| previous.lerp(*transform, alpha))
+    })
+}
+
+/// The translation part of a world matrix
+pub fn translation(matrix: &Matrix3<f32>) -> Vector2<f32> {
+    Vector2::new(matrix.z.x, matrix.z.y)
+}
+
+/// Combines the local transforms produced by `local` down every parent
+/// chain. Each matrix is computed once, visiting entities in index order. A
+/// chain that loops back on itself is cut where the loop closes.
+fn world_matrices(
+    transforms: &Storage<Transform2D>,
+    local: impl Fn(Entity, &Transform2D) -> Transform2D,
+) -> Storage<Matrix3<f32>> {
+    let mut matrices = Storage::new();
+    let mut chain = Vec::new();
+
+    for (entity, _) in transforms.iter() {
+        if matrices.contains(entity) {
+            continue;
+        }
+
+        // Walk up to the nearest ancestor that is already placed, or to a root
+        let mut parent_matrix = Matrix3::identity();
+        let mut current = Some(entity);
+        while let Some(ancestor) = current {
+            if let Some(matrix) = matrices.get(ancestor) {
+                parent_matrix = *matrix;
+                break;
+            }
+            let Some(transform) = transforms.get(ancestor) else {
+                break;
+            };
+            if chain.contains(&ancestor) {
+                break;
+            }
+            chain.push(ancestor);
+            current = transform.parent;
+        }
+
+        // Then place everything on the way back down
+        for descendant in chain.drain(..).rev() {
+            let transform = local(descendant, transforms.get(descendant).unwrap());
+            parent_matrix = parent_matrix * transform.local_matrix();
+            matrices.insert(descendant, parent_matrix);
+        }
+    }
+    matrices
+}
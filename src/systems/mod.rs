@@ -0,0 +1,6 @@
+pub mod control;
+pub mod hierarchy;
+pub mod movement;
+pub mod puzzle;
+pub mod render;
+pub mod time_fields;
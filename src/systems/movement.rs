@@ -0,0 +1,64 @@
+use cgmath::{Vector2, Vector3};
+
+use crate::{
+    components::Collider, ecs::entity::Entity, systems::hierarchy::translation, world::World,
+};
+
+/// Integrates every entity's velocity over `dt` scaled by its effective time
+/// scale. Controllable entities are stopped by closed doors.
+pub fn run(world: &mut World, dt: f32) {
+    let steps: Vec<(Entity, f32)> = world
+        .transforms
+        .iter()
+        .map(|(entity, _)| (entity, dt * world.effective_time_scale(entity)))
+        .collect();
+
+    for (entity, entity_dt) in steps {
+        let transform = world.transforms.get(entity).copied().unwrap();
+        world.previous_transforms.insert(entity, transform);
+
+        let Some(velocity) = world.velocities.get(entity) else {
+            continue;
+        };
+        let mut moved = transform;
+        moved.position += velocity.linear * entity_dt;
+        moved.rotation += velocity.angular * entity_dt;
+
+        if world.controllers.contains(entity)
+            && blocked_by_door(
+                world,
+                entity,
+                world_position(world, moved.parent, moved.position),
+            )
+        {
+            moved.position = transform.position;
+        }
+        world.transforms.insert(entity, moved);
+    }
+}
+
+/// Where a point at `position` relative to `parent` ends up in the world,
+/// using the parent's placement from the end of the previous tick
+fn world_position(world: &World, parent: Option<Entity>, position: Vector2<f32>) -> Vector2<f32> {
+    match parent.and_then(|parent| world.world_matrices.get(parent)) {
+        Some(matrix) => (matrix * Vector3::new(position.x, position.y, 1.0)).truncate(),
+        None => position,
+    }
+}
+
+/// Whether `entity` would overlap a closed door at `position`
+fn blocked_by_door(world: &World, entity: Entity, position: Vector2<f32>) -> bool {
+    let collider = world.colliders.get(entity).unwrap_or(&Collider::POINT);
+    world
+        .doors
+        .iter()
+        .filter(|(_, door)| !door.open)
+        .any(
+            |(door, _)| match (world.colliders.get(door), world.world_matrices.get(door)) {
+                (Some(door_collider), Some(door_matrix)) => {
+                    collider.overlaps(position, door_collider, translation(door_matrix))
+                }
+                _ => false,
+            },
+        )
+}
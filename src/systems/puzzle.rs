@@ -0,0 +1,37 @@
+use crate::{components::Collider, systems::hierarchy::translation, world::World};
+
+/// Presses every switch overlapped by a controllable entity, then opens the
+/// doors whose switches are all pressed. Doors without switches stay shut.
+pub fn run(world: &mut World) {
+    let matrices = &world.world_matrices;
+    for (switch_entity, switch) in world.switches.iter_mut() {
+        let (Some(switch_collider), Some(switch_matrix)) = (
+            world.colliders.get(switch_entity),
+            matrices.get(switch_entity),
+        ) else {
+            switch.pressed = false;
+            continue;
+        };
+
+        switch.pressed = world.controllers.iter().any(|(entity, _)| {
+            let collider = world.colliders.get(entity).unwrap_or(&Collider::POINT);
+            matrices.get(entity).is_some_and(|matrix| {
+                collider.overlaps(
+                    translation(matrix),
+                    switch_collider,
+                    translation(switch_matrix),
+                )
+            })
+        });
+    }
+
+    for (_, door) in world.doors.iter_mut() {
+        door.open = !door.switches.is_empty()
+            && door.switches.iter().all(|&switch| {
+                world
+                    .switches
+                    .get(switch)
+                    .is_some_and(|switch| switch.pressed)
+            });
+    }
+}
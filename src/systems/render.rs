@@ -0,0 +1,70 @@
+use crate::{
+    components::{Collider, ShapeKind},
+    graphics::{GraphicsState, atlas::Atlas, textured_pipeline::TexturedQuad},
+    systems::hierarchy::{self, translation},
+    world::World,
+};
+
+/// Pushes every drawable entity to the renderer, `alpha` of the way from the
+/// previous tick's state to the current one. Sprites become textured quads,
+/// shapes become debug shapes, and sprites missing from `atlas` are skipped.
+/// Both are placed by their entity's interpolated world matrix.
+pub fn draw(world: &World, graphics_state: &mut GraphicsState, atlas: &Atlas, alpha: f32) {
+    for (entity, switch, matrix) in world.switches.join(&world.world_matrices) {
+        let collider = world.colliders.get(entity).unwrap_or(&Collider::POINT);
+        switch.draw(
+            translation(matrix),
+            collider.half_extents * 2.0,
+            graphics_state,
+        );
+    }
+    for (entity, door, matrix) in world.doors.join(&world.world_matrices) {
+        let collider = world.colliders.get(entity).unwrap_or(&Collider::POINT);
+        door.draw(
+            translation(matrix),
+            collider.half_extents * 2.0,
+            graphics_state,
+        );
+    }
+
+    let matrices = hierarchy::interpolated(world, alpha);
+
+    for (_, shape, &matrix) in world.shapes.join(&matrices) {
+        match shape.kind {
+            ShapeKind::Square => graphics_state.push_debug_square_transformed(
+                matrix,
+                shape.scale,
+                shape.color,
+                shape.blend,
+            ),
+            ShapeKind::Triangle => graphics_state.push_debug_triangle_transformed(
+                matrix,
+                shape.scale,
+                shape.color,
+                shape.blend,
+            ),
+        }
+    }
+
+    for (_, sprite, &matrix) in world.sprites.join(&matrices) {
+        let Some(region) = atlas.region(&sprite.name) else {
+            continue;
+        };
+        graphics_state.push_textured_quad(TexturedQuad {
+            transform: matrix,
+            dimensions: sprite.dimensions,
+            layer: sprite.layer,
+            texture: region.texture,
+            uv: region.uv,
+            tint: sprite.tint,
+            blend: sprite.blend,
+        });
+    }
+}
+
+/// Debug overlay showing the boundary of every time field
+pub fn draw_time_fields(world: &World, graphics_state: &mut GraphicsState) {
+    for (_, field, matrix) in world.time_fields.join(&world.world_matrices) {
+        field.draw_debug(translation(matrix), graphics_state);
+    }
+}
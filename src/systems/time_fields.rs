@@ -0,0 +1,22 @@
+use crate::{ecs::entity::Entity, systems::hierarchy::translation, world::World};
+
+/// Centers anchored time fields on where their anchors ended up this tick.
+/// Fields whose anchor no longer exists stay where they were last. Anchored
+/// fields are placed in world space, so they shouldn't have a parent.
+pub fn follow_anchors(world: &mut World) {
+    let moves: Vec<(Entity, _)> = world
+        .time_fields
+        .iter()
+        .filter_map(|(field, time_field)| {
+            let anchor = world.world_matrices.get(time_field.anchor?)?;
+            Some((field, translation(anchor)))
+        })
+        .collect();
+
+    for (field, position) in moves {
+        if let Some(transform) = world.transforms.get_mut(field) {
+            transform.position = position;
+            world.world_matrices.insert(field, transform.local_matrix());
+        }
+    }
+}
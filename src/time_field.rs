@@ -0,0 +1,87 @@
+use cgmath::Vector2;
+
+use crate::{
+    ecs::entity::Entity,
+    graphics::{GraphicsState, blend::BlendMode, color::Color},
+};
+
+/// Thickness of the outline drawn by the debug overlay, in world units
+const OUTLINE_THICKNESS: f32 = 2.0;
+
+/// An axis aligned region, centered on its entity, that changes how fast time
+/// passes for the entities inside it, e.g. 0.25 for a slow field, 0.0 to
+/// freeze or 2.0 to accelerate. Overlapping fields multiply together.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TimeField {
+    pub half_extents: Vector2<f32>,
+    pub multiplier: f32,
+    /// Entity that the field is centered on, e.g. a bubble around the
+    /// player. The anchor itself isn't affected by its field.
+    pub anchor: Option<Entity>,
+}
+
+impl TimeField {
+    pub fn new(half_extents: Vector2<f32>, multiplier: f32) -> Self {
+        Self {
+            half_extents,
+            multiplier,
+            anchor: None,
+        }
+    }
+
+    /// Creates a field that follows `anchor` around
+    pub fn anchored(anchor: Entity, half_extents: Vector2<f32>, multiplier: f32) -> Self {
+        Self {
+            half_extents,
+            multiplier,
+            anchor: Some(anchor),
+        }
+    }
+
+    /// Whether `point` is inside the field when it is centered on `center`
+    pub fn contains(&self, center: Vector2<f32>, point: Vector2<f32>) -> bool {
+        let offset = point - center;
+        offset.x.abs() <= self.half_extents.x && offset.y.abs() <= self.half_extents.y
+    }
+
+    /// Blue for slow fields, red for fast ones and white for frozen ones
+    fn debug_color(&self, alpha: f32) -> Color {
+        if self.multiplier <= 0.0 {
+            Color::rgba(1.0, 1.0, 1.0, alpha)
+        } else if self.multiplier < 1.0 {
+            Color::rgba(0.2, 0.4, 1.0, alpha)
+        } else {
+            Color::rgba(1.0, 0.3, 0.2, alpha)
+        }
+    }
+
+    /// Draws a faint fill with a solid outline at the field's boundary
+    pub fn draw_debug(&self, center: Vector2<f32>, graphics_state: &mut GraphicsState) {
+        let size = self.half_extents * 2.0;
+        graphics_state.push_debug_square(
+            center,
+            size,
+            0.0,
+            self.debug_color(0.15),
+            BlendMode::Alpha,
+        );
+
+        let outline = self.debug_color(0.8);
+        let horizontal = Vector2::new(size.x + OUTLINE_THICKNESS, OUTLINE_THICKNESS);
+        let vertical = Vector2::new(OUTLINE_THICKNESS, size.y + OUTLINE_THICKNESS);
+        for (offset, scale) in [
+            (Vector2::new(0.0, self.half_extents.y), horizontal),
+            (Vector2::new(0.0, -self.half_extents.y), horizontal),
+            (Vector2::new(self.half_extents.x, 0.0), vertical),
+            (Vector2::new(-self.half_extents.x, 0.0), vertical),
+        ] {
+            graphics_state.push_debug_square(
+                center + offset,
+                scale,
+                0.0,
+                outline,
+                BlendMode::Alpha,
+            );
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use cgmath::{Matrix3, SquareMatrix, Vector2};
+
+use crate::{
+    components::{Collider, Shape, Sprite, TimeScale, Transform2D, Velocity},
+    determinism::Checksum,
+    echo::{Controller, InputTimeline, PlayerInput},
+    ecs::{
+        entity::{Entities, Entity},
+        storage::Storage,
+    },
+    graphics::color::Color,
+    puzzle::{Door, Switch},
+    rng::Rng,
+    systems::{self, hierarchy::translation},
+    time_field::TimeField,
+};
+
+/// Components to spawn an entity with. Anything left as `None` is omitted.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Bundle {
+    pub transform: Option<Transform2D>,
+    pub velocity: Option<Velocity>,
+    pub time_scale: Option<TimeScale>,
+    pub sprite: Option<Sprite>,
+    pub shape: Option<Shape>,
+    pub collider: Option<Collider>,
+    pub controller: Option<Controller>,
+    pub timeline: Option<InputTimeline>,
+    pub time_field: Option<TimeField>,
+    pub switch: Option<Switch>,
+    pub door: Option<Door>,
+}
+
+/// Spawns and despawns requested while systems are iterating the world.
+/// Applied at the end of each tick: all spawns in the order they were
+/// requested, then all despawns. Spawning first means an entity spawned in
+/// a tick never reuses the id of one despawned in the same tick.
+#[derive(Default)]
+pub struct Commands {
+    spawns: Vec<Bundle>,
+    despawns: Vec<Entity>,
+}
+
+impl Commands {
+    pub fn spawn(&mut self, bundle: Bundle) {
+        self.spawns.push(bundle);
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.despawns.push(entity);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.spawns.is_empty() && self.despawns.is_empty()
+    }
+}
+
+/// The simulation state of a world at the start of a tick. Input timelines
+/// are history rather than state and aren't included.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorldSnapshot {
+    tick: u64,
+    rng: Rng,
+    entities: Entities,
+    transforms: Storage<Transform2D>,
+    velocities: Storage<Velocity>,
+    time_scales: Storage<TimeScale>,
+    sprites: Storage<Sprite>,
+    shapes: Storage<Shape>,
+    colliders: Storage<Collider>,
+    controllers: Storage<Controller>,
+    time_fields: Storage<TimeField>,
+    switches: Storage<Switch>,
+    doors: Storage<Door>,
+}
+
+impl WorldSnapshot {
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+}
+
+/// Every game object and everything that is simulated at the fixed tick rate.
+///
+/// Updates are deterministic: systems visit entities in index order, entity
+/// ids are reused in a fixed order, and all randomness comes from the world's
+/// seeded generator. Given the same seed and the same inputs on the same
+/// ticks, two runs produce bit for bit identical states, which `checksum`
+/// can confirm.
+#[derive(Default)]
+pub struct World {
+    /// Number of ticks simulated so far. Goes backwards when rewinding.
+    tick: u64,
+    rng: Rng,
+    entities: Entities,
+    pub transforms: Storage<Transform2D>,
+    /// Transforms at the start of the last tick, used to interpolate rendering
+    pub(crate) previous_transforms: Storage<Transform2D>,
+    /// Where each entity with a transform ends up once its parents are
+    /// applied. Updated after movement each tick and whenever an entity is
+    /// spawned; derived from `transforms`, so never snapshotted.
+    pub world_matrices: Storage<Matrix3<f32>>,
+    pub velocities: Storage<Velocity>,
+    pub time_scales: Storage<TimeScale>,
+    pub sprites: Storage<Sprite>,
+    pub shapes: Storage<Shape>,
+    pub colliders: Storage<Collider>,
+    pub controllers: Storage<Controller>,
+    pub timelines: Storage<InputTimeline>,
+    pub time_fields: Storage<TimeField>,
+    pub switches: Storage<Switch>,
+    pub doors: Storage<Door>,
+    pub commands: Commands,
+    /// Applied to every player controlled entity on the next tick
+    pub player_input: PlayerInput,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rng: Rng::new(seed),
+            ..Self::default()
+        }
+    }
+
+    pub fn tick(&self) -> u64 {
+        self.tick
+    }
+
+    /// The only source of randomness simulation code may use
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    pub fn entities(&self) -> impl Iterator<Item = Entity> + '_ {
+        self.entities.iter()
+    }
+
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        self.entities.is_alive(entity)
+    }
+
+    /// Position of `entity` in world space, as of the last propagation
+    pub fn world_position(&self, entity: Entity) -> Option<Vector2<f32>> {
+        self.world_matrices.get(entity).map(translation)
+    }
+
+    /// Entities whose collider contains the world space `point`, in index
+    /// order, e.g. to find what was clicked on
+    pub fn entities_at(&self, point: Vector2<f32>) -> impl Iterator<Item = Entity> + '_ {
+        self.colliders
+            .iter()
+            .filter(move |&(entity, collider)| {
+                self.world_position(entity)
+                    .is_some_and(|position| collider.overlaps(position, &Collider::POINT, point))
+            })
+            .map(|(entity, _)| entity)
+    }
+
+    /// Entities attached directly to `parent`, in index order
+    pub fn children(&self, parent: Entity) -> impl Iterator<Item = Entity> + '_ {
+        self.transforms
+            .iter()
+            .filter(move |(_, transform)| transform.parent == Some(parent))
+            .map(|(entity, _)| entity)
+    }
+
+    /// Adds an entity with the components in `bundle`. An entity with a
+    /// parent is placed relative to wherever its parent currently is.
+    pub fn spawn(&mut self, bundle: Bundle) -> Entity {
+        let entity = self.entities.allocate();
+        let Bundle {
+            transform,
+            velocity,
+            time_scale,
+            sprite,
+            shape,
+            collider,
+            controller,
+            timeline,
+            time_field,
+            switch,
+            door,
+        } = bundle;
+
+        fn insert<T>(storage: &mut Storage<T>, entity: Entity, value: Option<T>) {
+            if let Some(value) = value {
+                storage.insert(entity, value);
+            }
+        }
+        insert(&mut self.transforms, entity, transform);
+        insert(&mut self.previous_transforms, entity, transform);
+        insert(&mut self.velocities, entity, velocity);
+        insert(&mut self.time_scales, entity, time_scale);
+        insert(&mut self.sprites, entity, sprite);
+        insert(&mut self.shapes, entity, shape);
+        insert(&mut self.colliders, entity, collider);
+        insert(&mut self.controllers, entity, controller);
+        insert(&mut self.timelines, entity, timeline);
+        insert(&mut self.time_fields, entity, time_field);
+        insert(&mut self.switches, entity, switch);
+        insert(&mut self.doors, entity, door);
+
+        if let Some(transform) = transform {
+            let parent_matrix = transform
+                .parent
+                .and_then(|parent| self.world_matrices.get(parent))
+                .copied()
+                .unwrap_or_else(Matrix3::identity);
+            self.world_matrices
+                .insert(entity, parent_matrix * transform.local_matrix());
+        }
+        entity
+    }
+
+    /// Removes the entity, all of its components and everything attached to
+    /// it. Returns false if it was already gone.
+    pub fn despawn(&mut self, entity: Entity) -> bool {
+        if !self.entities.free(entity) {
+            return false;
+        }
+        let children: Vec<Entity> = self.children(entity).collect();
+        for child in children {
+            self.despawn(child);
+        }
+
+        self.transforms.remove(entity);
+        self.previous_transforms.remove(entity);
+        self.world_matrices.remove(entity);
+        self.velocities.remove(entity);
+        self.time_scales.remove(entity);
+        self.sprites.remove(entity);
+        self.shapes.remove(entity);
+        self.colliders.remove(entity);
+        self.controllers.remove(entity);
+        self.timelines.remove(entity);
+        self.time_fields.remove(entity);
+        self.switches.remove(entity);
+        self.doors.remove(entity);
+        true
+    }
+
+    /// Runs the spawns and despawns queued in `commands`
+    pub fn apply_commands(&mut self) {
+        let Commands { spawns, despawns } = std::mem::take(&mut self.commands);
+        for bundle in spawns {
+            self.spawn(bundle);
+        }
+        for entity in despawns {
+            self.despawn(entity);
+        }
+    }
+
+    /// Hash of the entire simulation state, for comparing runs. Like
+    /// snapshots, it leaves out input timelines, which are history, and
+    /// world matrices, which are derived from the transforms.
+    pub fn checksum(&self) -> u64 {
+        let mut checksum = Checksum::new();
+        checksum.write_u64(self.tick);
+        checksum.write_u64(self.rng.state());
+
+        for entity in self.entities.iter() {
+            checksum.write_u64(entity.index() as u64);
+            checksum.write_u64(entity.generation() as u64);
+        }
+        for (entity, transform) in self.transforms.iter() {
+            checksum.write_u64(entity.index() as u64);
+            checksum.write_f32(transform.position.x);
+            checksum.write_f32(transform.position.y);
+            checksum.write_f32(transform.rotation);
+            checksum.write_f32(transform.scale.x);
+            checksum.write_f32(transform.scale.y);
+            write_optional_entity(&mut checksum, transform.parent);
+        }
+        for (entity, velocity) in self.velocities.iter() {
+            checksum.write_u64(entity.index() as u64);
+            checksum.write_f32(velocity.linear.x);
+            checksum.write_f32(velocity.linear.y);
+            checksum.write_f32(velocity.angular);
+        }
+        for (entity, time_scale) in self.time_scales.iter() {
+            checksum.write_u64(entity.index() as u64);
+            checksum.write_f32(time_scale.0);
+        }
+        for (entity, sprite) in self.sprites.iter() {
+            checksum.write_u64(entity.index() as u64);
+            checksum.write_str(&sprite.name);
+            checksum.write_f32(sprite.dimensions.x);
+            checksum.write_f32(sprite.dimensions.y);
+            checksum.write_u64(sprite.layer as u64);
+            write_color(&mut checksum, sprite.tint);
+            checksum.write_u64(sprite.blend as u64);
+        }
+        for (entity, shape) in self.shapes.iter() {
+            checksum.write_u64(entity.index() as u64);
+            checksum.write_u64(shape.kind as u64);
+            checksum.write_f32(shape.scale.x);
+            checksum.write_f32(shape.scale.y);
+            write_color(&mut checksum, shape.color);
+            checksum.write_u64(shape.blend as u64);
+        }
+        for (entity, collider) in self.colliders.iter() {
+            checksum.write_u64(entity.index() as u64);
+            checksum.write_f32(collider.half_extents.x);
+            checksum.write_f32(collider.half_extents.y);
+        }
+        for (entity, field) in self.time_fields.iter() {
+            checksum.write_u64(entity.index() as u64);
+            checksum.write_f32(field.half_extents.x);
+            checksum.write_f32(field.half_extents.y);
+            checksum.write_f32(field.multiplier);
+            write_optional_entity(&mut checksum, field.anchor);
+        }
+        for (entity, controller) in self.controllers.iter() {
+            checksum.write_u64(entity.index() as u64);
+            match controller {
+                Controller::Player => checksum.write_u64(1),
+                Controller::Echo(playback) => {
+                    checksum.write_u64(2);
+                    checksum.write_u64(playback.cursor() as u64);
+                }
+            }
+        }
+        for (entity, switch) in self.switches.iter() {
+            checksum.write_u64(entity.index() as u64);
+            checksum.write_bool(switch.pressed);
+        }
+        for (entity, door) in self.doors.iter() {
+            checksum.write_u64(entity.index() as u64);
+            checksum.write_bool(door.open);
+            checksum.write_u64(door.switches.len() as u64);
+            for &switch in &door.switches {
+                write_optional_entity(&mut checksum, Some(switch));
+            }
+        }
+        checksum.finish()
+    }
+
+    pub fn snapshot(&self) -> WorldSnapshot {
+        WorldSnapshot {
+            tick: self.tick,
+            rng: self.rng,
+            entities: self.entities.clone(),
+            transforms: self.transforms.clone(),
+            velocities: self.velocities.clone(),
+            time_scales: self.time_scales.clone(),
+            sprites: self.sprites.clone(),
+            shapes: self.shapes.clone(),
+            colliders: self.colliders.clone(),
+            controllers: self.controllers.clone(),
+            time_fields: self.time_fields.clone(),
+            switches: self.switches.clone(),
+            doors: self.doors.clone(),
+        }
+    }
+
+    /// Returns the world to the state captured by `snapshot`. Entities
+    /// spawned after the snapshot was taken are removed and ones despawned
+    /// since are brought back.
+    pub fn restore(&mut self, snapshot: &WorldSnapshot) {
+        // Interpolate from where entities are now back to the restored state
+        let mut previous_transforms = Storage::new();
+        for (entity, transform) in snapshot.transforms.iter() {
+            let previous = self.transforms.get(entity).unwrap_or(transform);
+            previous_transforms.insert(entity, *previous);
+        }
+        self.previous_transforms = previous_transforms;
+
+        self.tick = snapshot.tick;
+        self.rng = snapshot.rng;
+        self.entities = snapshot.entities.clone();
+        self.transforms = snapshot.transforms.clone();
+        self.velocities = snapshot.velocities.clone();
+        self.time_scales = snapshot.time_scales.clone();
+        self.sprites = snapshot.sprites.clone();
+        self.shapes = snapshot.shapes.clone();
+        self.colliders = snapshot.colliders.clone();
+        self.controllers = snapshot.controllers.clone();
+        self.time_fields = snapshot.time_fields.clone();
+        self.switches = snapshot.switches.clone();
+        self.doors = snapshot.doors.clone();
+
+        // Timelines of entities that no longer exist are dropped, the rest
+        // forget the ticks that were undone
+        let stale: Vec<Entity> = self
+            .timelines
+            .iter()
+            .map(|(entity, _)| entity)
+            .filter(|&entity| !self.entities.is_alive(entity))
+            .collect();
+        for entity in stale {
+            self.timelines.remove(entity);
+        }
+        for (_, timeline) in self.timelines.iter_mut() {
+            timeline.truncate_from(snapshot.tick);
+        }
+        self.commands = Commands::default();
+        systems::hierarchy::propagate(self);
+    }
+
+    /// Spawns an echo of the player controlled entity `source` that replays
+    /// its last `ticks` ticks of input, starting from where it was back then.
+    /// The echo copies the source's look, faded. Returns `None` if `source`
+    /// has recorded nothing.
+    pub fn spawn_echo(&mut self, source: Entity, ticks: usize) -> Option<Entity> {
+        let playback = self.timelines.get(source)?.segment(ticks)?;
+        Some(self.spawn(Bundle {
+            transform: Some(playback.start()),
+            velocity: Some(Velocity::ZERO),
+            time_scale: self.time_scales.get(source).copied(),
+            sprite: self.sprites.get(source).map(Sprite::echo),
+            shape: self.shapes.get(source).map(Shape::echo),
+            collider: self.colliders.get(source).copied(),
+            controller: Some(Controller::Echo(playback)),
+            ..Bundle::default()
+        }))
+    }
+
+    /// How fast time passes for `entity`: its own time scale multiplied by
+    /// every time field it is inside
+    pub fn effective_time_scale(&self, entity: Entity) -> f32 {
+        let base = self.time_scales.get(entity).map_or(1.0, |scale| scale.0);
+        let Some(position) = self.world_position(entity) else {
+            return base;
+        };
+        self.time_fields
+            .join(&self.world_matrices)
+            .filter(|(_, field, _)| field.anchor != Some(entity))
+            .filter(|(_, field, center)| field.contains(translation(center), position))
+            .fold(base, |scale, (_, field, _)| scale * field.multiplier)
+    }
+
+    /// Advances the simulation by one tick of `dt` seconds
+    pub fn update(&mut self, dt: f32) {
+        let tick = self.tick;
+        self.tick += 1;
+
+        systems::control::run(self, tick);
+        systems::movement::run(self, dt);
+        systems::hierarchy::propagate(self);
+        systems::time_fields::follow_anchors(self);
+        systems::puzzle::run(self);
+
+        self.apply_commands();
+    }
+}
+
+fn write_optional_entity(checksum: &mut Checksum, entity: Option<Entity>) {
+    match entity {
+        Some(entity) => {
+            checksum.write_bool(true);
+            checksum.write_u64(entity.index() as u64);
+            checksum.write_u64(entity.generation() as u64);
+        }
+        None => checksum.write_bool(false),
+    }
+}
+
+fn write_color(checksum: &mut Checksum, color: Color) {
+    for channel in [color.r, color.g, color.b, color.a] {
+        checksum.write_f32(channel);
+    }
+}
@@ -0,0 +1,121 @@
+use image::{Rgba, RgbaImage};
+use time_game::graphics::atlas::{AtlasBuilder, PADDING, PackedRegion};
+
+const SIZES: [(u32, u32); 7] = [(32, 8), (5, 17), (1, 1), (16, 16), (9, 3), (40, 2), (7, 30)];
+
+/// An opaque image whose every pixel says which image and where it is
+fn numbered(index: usize, width: u32, height: u32) -> RgbaImage {
+    RgbaImage::from_fn(width, height, |x, y| {
+        Rgba([index as u8, x as u8, y as u8, 255])
+    })
+}
+
+fn packed_regions(max_size: u32) -> (RgbaImage, Vec<PackedRegion>) {
+    let mut builder = AtlasBuilder::new();
+    for (index, &(width, height)) in SIZES.iter().enumerate() {
+        builder.add_image(&format!("image-{}", index), numbered(index, width, height));
+    }
+    let packed = builder.pack(max_size).unwrap();
+    (packed.image, packed.regions)
+}
+
+/// The region's pixel rectangle grown by its padding, as (left, top, right, bottom)
+fn padded(region: &PackedRegion) -> (u32, u32, u32, u32) {
+    (
+        region.x - PADDING,
+        region.y - PADDING,
+        region.x + region.width + PADDING,
+        region.y + region.height + PADDING,
+    )
+}
+
+#[test]
+fn padded_regions_do_not_overlap() {
+    let (_, regions) = packed_regions(1024);
+    assert_eq!(regions.len(), SIZES.len());
+    for (i, a) in regions.iter().enumerate() {
+        for b in &regions[i + 1..] {
+            let (a_left, a_top, a_right, a_bottom) = padded(a);
+            let (b_left, b_top, b_right, b_bottom) = padded(b);
+            let overlaps =
+                a_left < b_right && b_left < a_right && a_top < b_bottom && b_top < a_bottom;
+            assert!(!overlaps, "{} overlaps {}", a.name, b.name);
+        }
+    }
+}
+
+#[test]
+fn regions_are_inside_the_atlas() {
+    let (image, regions) = packed_regions(1024);
+    for (region, &(width, height)) in regions.iter().zip(&SIZES) {
+        assert_eq!((region.width, region.height), (width, height));
+        let (_, _, right, bottom) = padded(region);
+        assert!(region.x >= PADDING && region.y >= PADDING);
+        assert!(right <= image.width() && bottom <= image.height());
+    }
+}
+
+#[test]
+fn uv_rects_map_back_to_the_source_pixels() {
+    let (image, regions) = packed_regions(1024);
+    let (atlas_width, atlas_height) = (image.width() as f32, image.height() as f32);
+    for (index, region) in regions.iter().enumerate() {
+        let x = (region.uv.offset.x * atlas_width).round() as u32;
+        let y = (region.uv.offset.y * atlas_height).round() as u32;
+        let width = (region.uv.scale.x * atlas_width).round() as u32;
+        let height = (region.uv.scale.y * atlas_height).round() as u32;
+        assert_eq!((x, y), (region.x, region.y));
+        assert_eq!((width, height), SIZES[index]);
+
+        let source = numbered(index, width, height);
+        for (px, py, &pixel) in source.enumerate_pixels() {
+            assert_eq!(*image.get_pixel(x + px, y + py), pixel);
+        }
+        // The padding repeats the edge, so filtering at the border stays
+        // inside the image
+        assert_eq!(
+            *image.get_pixel(x - PADDING, y - PADDING),
+            *source.get_pixel(0, 0)
+        );
+    }
+}
+
+#[test]
+fn images_too_big_for_the_limit_fail_to_pack() {
+    let mut builder = AtlasBuilder::new();
+    builder.add_image("huge", numbered(0, 64, 64));
+    assert!(builder.pack(64).is_err());
+}
+
+#[test]
+fn premultiplying_happens_in_linear_space() {
+    let mut builder = AtlasBuilder::premultiplied();
+    builder.add_image(
+        "half",
+        RgbaImage::from_pixel(1, 1, Rgba([200, 100, 0, 128])),
+    );
+    builder.add_image(
+        "opaque",
+        RgbaImage::from_pixel(1, 1, Rgba([200, 100, 0, 255])),
+    );
+    let packed = builder.pack(1024).unwrap();
+
+    // What sampling the sRGB texture gives, premultiplied, encoded again
+    let alpha = 128.0 / 255.0;
+    let expected = |value: f32| {
+        let linear = (value / 255.0 + 0.055) / 1.055;
+        let scaled = linear.powf(2.4) * alpha;
+        ((1.055 * scaled.powf(1.0 / 2.4) - 0.055) * 255.0).round() as u8
+    };
+    let half = &packed.regions[0];
+    let texel = packed.image.get_pixel(half.x, half.y);
+    assert_eq!(*texel, Rgba([expected(200.0), expected(100.0), 0, 128]));
+    // Scaling the encoded bytes would have given 100 and 50
+    assert_eq!(texel[0], 147);
+
+    let opaque = &packed.regions[1];
+    assert_eq!(
+        *packed.image.get_pixel(opaque.x, opaque.y),
+        Rgba([200, 100, 0, 255])
+    );
+}
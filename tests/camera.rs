@@ -0,0 +1,108 @@
+use cgmath::{Rad, Vector2, Vector4};
+use time_game::graphics::camera::Camera2D;
+
+fn assert_close(actual: Vector2<f32>, expected: Vector2<f32>) {
+    assert!(
+        (actual.x - expected.x).abs() < 1e-3 && (actual.y - expected.y).abs() < 1e-3,
+        "expected {:?} but got {:?}",
+        expected,
+        actual
+    );
+}
+
+/// Panned, zoomed and rotated over a wide viewport
+fn cameras() -> Vec<Camera2D> {
+    let viewport = Vector2::new(800.0, 450.0);
+    let mut cameras = vec![Camera2D::new(viewport)];
+    for (position, zoom, rotation) in [
+        (Vector2::new(-120.0, 35.5), 1.0, 0.0),
+        (Vector2::new(0.0, 0.0), 2.5, 0.0),
+        (Vector2::new(300.0, -80.0), 0.25, 0.0),
+        (Vector2::new(12.0, 900.0), 3.0, 0.7),
+    ] {
+        cameras.push(Camera2D {
+            position,
+            zoom,
+            rotation: Rad(rotation),
+            viewport,
+        });
+    }
+    cameras
+}
+
+const POINTS: [Vector2<f32>; 5] = [
+    Vector2::new(0.0, 0.0),
+    Vector2::new(800.0, 450.0),
+    Vector2::new(13.0, 440.0),
+    Vector2::new(-50.0, 1000.0),
+    Vector2::new(399.5, 17.25),
+];
+
+#[test]
+fn screen_points_survive_a_round_trip() {
+    for camera in cameras() {
+        for screen in POINTS {
+            let world = camera.screen_to_world(screen);
+            assert_close(camera.world_to_screen(world), screen);
+        }
+    }
+}
+
+#[test]
+fn world_points_survive_a_round_trip() {
+    for camera in cameras() {
+        for world in POINTS {
+            let screen = camera.world_to_screen(world);
+            assert_close(camera.screen_to_world(screen), world);
+        }
+    }
+}
+
+#[test]
+fn the_camera_position_is_the_center_of_the_screen() {
+    for camera in cameras() {
+        assert_close(
+            camera.world_to_screen(camera.position),
+            Vector2::new(400.0, 225.0),
+        );
+    }
+}
+
+#[test]
+fn new_cameras_put_the_world_origin_at_the_bottom_left() {
+    let camera = Camera2D::new(Vector2::new(800.0, 450.0));
+    assert_close(
+        camera.screen_to_world(Vector2::new(0.0, 450.0)),
+        Vector2::new(0.0, 0.0),
+    );
+    // Screen y points down and world y points up
+    assert_close(
+        camera.screen_to_world(Vector2::new(0.0, 0.0)),
+        Vector2::new(0.0, 450.0),
+    );
+}
+
+#[test]
+fn zoom_scales_screen_distances() {
+    let camera = Camera2D {
+        zoom: 4.0,
+        ..Camera2D::new(Vector2::new(800.0, 450.0))
+    };
+    let a = camera.world_to_screen(Vector2::new(10.0, 10.0));
+    let b = camera.world_to_screen(Vector2::new(20.0, 15.0));
+    assert_close(b - a, Vector2::new(40.0, -20.0));
+}
+
+#[test]
+fn screen_mapping_matches_what_the_gpu_draws() {
+    for camera in cameras() {
+        for world in POINTS {
+            let clip = camera.view_projection_matrix() * Vector4::new(world.x, world.y, 0.0, 1.0);
+            let drawn_at = Vector2::new(
+                (clip.x / clip.w + 1.0) / 2.0 * camera.viewport.x,
+                (1.0 - clip.y / clip.w) / 2.0 * camera.viewport.y,
+            );
+            assert_close(camera.world_to_screen(world), drawn_at);
+        }
+    }
+}
@@ -0,0 +1,203 @@
+use std::fs;
+
+use time_game::{
+    game::Game,
+    input::{
+        actions::{self, CAMERA_DOWN, MOVE_UP, QUICKSAVE, REWIND},
+        controls::{CONTROLS_VERSION, Capture, Controls, Rebind, RebindProgress},
+        event::InputEvent,
+        map::{Binding, Input},
+    },
+    replay::{self, Replay},
+};
+use winit::{event::MouseButton, keyboard::KeyCode};
+
+fn key(code: KeyCode, pressed: bool) -> InputEvent {
+    InputEvent::Key { code, pressed }
+}
+
+/// Presses and releases `code`, returning how the rebind went
+fn tap(rebind: &mut Rebind, controls: &mut Controls, code: KeyCode) -> RebindProgress {
+    let progress = rebind.handle_event(&key(code, true), controls).unwrap();
+    assert_eq!(
+        rebind.handle_event(&key(code, false), controls).unwrap(),
+        RebindProgress::Waiting
+    );
+    progress
+}
+
+#[test]
+fn defaults_have_no_conflicts() {
+    assert_eq!(Controls::default().conflicts(), vec![]);
+}
+
+#[test]
+fn conflicts_are_found_across_actions() {
+    let mut controls = Controls::default();
+    controls
+        .game
+        .get_mut(MOVE_UP)
+        .unwrap()
+        .push(Binding::key(KeyCode::KeyW));
+    let conflicts = controls.conflicts();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].binding, Binding::key(KeyCode::KeyW));
+    assert_eq!(conflicts[0].actions, ["move_up", "camera_up"]);
+}
+
+#[test]
+fn rebinding_refuses_conflicts() {
+    let mut controls = Controls::default();
+    let old = Binding::key(KeyCode::KeyR);
+    let error = controls
+        .rebind(REWIND, Some(&old), Binding::key(KeyCode::KeyS))
+        .unwrap_err();
+    assert_eq!(error.to_string(), "KeyS is already bound to camera_down");
+    assert_eq!(controls, Controls::default());
+
+    controls
+        .rebind(REWIND, Some(&old), Binding::key(KeyCode::KeyQ))
+        .unwrap();
+    assert!(controls.game[REWIND].contains(&Binding::key(KeyCode::KeyQ)));
+    assert_eq!(controls.action_for(&Binding::key(KeyCode::KeyR)), None);
+
+    controls.reset();
+    assert_eq!(controls, Controls::default());
+}
+
+#[test]
+fn controls_survive_saving_and_loading() {
+    let mut controls = Controls::default();
+    controls
+        .rebind(CAMERA_DOWN, None, Binding::mouse(MouseButton::Right))
+        .unwrap();
+
+    let path = std::env::temp_dir()
+        .join(format!("time_game_controls_{}", std::process::id()))
+        .join("controls.ron");
+    controls.save(&path).unwrap();
+    let loaded = Controls::load(&path);
+    fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    assert_eq!(loaded.unwrap(), controls);
+
+    // A file that doesn't exist yet means the defaults
+    assert_eq!(Controls::load(&path).unwrap(), Controls::default());
+}
+
+#[test]
+fn missing_actions_keep_their_defaults() {
+    let text = format!(
+        "(version: {}, game: {{ \"rewind\": [Chord([Key(KeyT)])] }}, app: {{}})",
+        CONTROLS_VERSION
+    );
+    let controls = Controls::parse(&text).unwrap();
+    assert_eq!(controls.game[REWIND], [Binding::key(KeyCode::KeyT)]);
+    assert_eq!(controls.app, Controls::default().app);
+
+    let unknown = format!(
+        "(version: {}, game: {{ \"fly\": [] }}, app: {{}})",
+        CONTROLS_VERSION
+    );
+    let error = Controls::parse(&unknown).unwrap_err();
+    assert_eq!(error.to_string(), "There is no action named \"fly\"");
+
+    let newer = format!("(version: {}, game: {{}}, app: {{}})", CONTROLS_VERSION + 1);
+    assert!(Controls::parse(&newer).is_err());
+}
+
+#[test]
+fn capture_includes_held_modifiers() {
+    let mut capture = Capture::new();
+    assert_eq!(capture.handle_event(&key(KeyCode::ControlLeft, true)), None);
+    assert_eq!(
+        capture.handle_event(&key(KeyCode::KeyK, true)),
+        Some(Binding::chord([
+            Input::Key(KeyCode::ControlLeft),
+            Input::Key(KeyCode::KeyK)
+        ]))
+    );
+
+    // A modifier on its own is bound when it is let go
+    let mut capture = Capture::new();
+    assert_eq!(capture.handle_event(&key(KeyCode::ShiftRight, false)), None);
+    capture.handle_event(&key(KeyCode::ShiftLeft, true));
+    assert_eq!(
+        capture.handle_event(&key(KeyCode::ShiftLeft, false)),
+        Some(Binding::key(KeyCode::ShiftLeft))
+    );
+}
+
+#[test]
+fn rebind_replaces_the_binding_it_was_chosen_by() {
+    let mut controls = Controls::default();
+    let mut rebind = Rebind::new();
+
+    assert!(
+        rebind
+            .handle_event(&key(KeyCode::KeyZ, true), &mut controls)
+            .is_err()
+    );
+    assert_eq!(rebind.action(), None);
+
+    assert_eq!(
+        tap(&mut rebind, &mut controls, KeyCode::F6),
+        RebindProgress::Chose {
+            action: QUICKSAVE.to_string(),
+            old: Binding::key(KeyCode::F6)
+        }
+    );
+    // Taken by another action, so the step is retried
+    assert!(
+        rebind
+            .handle_event(&key(KeyCode::KeyW, true), &mut controls)
+            .is_err()
+    );
+    assert_eq!(rebind.action(), Some(QUICKSAVE));
+    assert_eq!(
+        tap(&mut rebind, &mut controls, KeyCode::F7),
+        RebindProgress::Rebound {
+            action: QUICKSAVE.to_string(),
+            binding: Binding::key(KeyCode::F7)
+        }
+    );
+
+    // The Ctrl+S chords are untouched
+    let bindings = &controls.app[QUICKSAVE];
+    assert_eq!(bindings.len(), 3);
+    assert!(bindings.contains(&Binding::key(KeyCode::F7)));
+    assert!(!bindings.contains(&Binding::key(KeyCode::F6)));
+
+    let mut rebind = Rebind::new();
+    assert_eq!(
+        tap(&mut rebind, &mut controls, KeyCode::Escape),
+        RebindProgress::Cancelled
+    );
+}
+
+#[test]
+fn replays_keep_their_bindings() {
+    let mut controls = Controls::default();
+    controls
+        .rebind(
+            MOVE_UP,
+            Some(&Binding::key(KeyCode::ArrowUp)),
+            Binding::key(KeyCode::KeyI),
+        )
+        .unwrap();
+
+    let mut replay = Replay::new(8);
+    replay.bindings = Some(controls.game.clone());
+    replay.record(0, key(KeyCode::KeyI, true));
+    replay.ticks = 30;
+
+    let mut rebound = Game::new(8);
+    replay::play_headless(&mut rebound, replay.clone());
+    let mut defaults = Game::new(8);
+    replay.bindings = None;
+    replay::play_headless(&mut defaults, replay);
+    assert_ne!(rebound.world.checksum(), defaults.world.checksum());
+
+    let mut expected = actions::game_defaults();
+    expected.set_all_bindings(&controls.game);
+    assert_eq!(rebound.input.all_bindings(), expected.all_bindings());
+}
@@ -0,0 +1,229 @@
+use cgmath::Vector2;
+use time_game::{
+    components::{Collider, Shape, ShapeKind, Sprite, TimeScale, Transform2D, Velocity},
+    determinism::{ChecksumLog, run_inputs},
+    echo::{Controller, InputTimeline, PlayerInput},
+    ecs::entity::Entity,
+    graphics::{blend::BlendMode, color::Color},
+    puzzle::{Door, Switch},
+    rewind::Rewind,
+    time_field::TimeField,
+    world::{Bundle, World},
+};
+
+const DT: f32 = 1.0 / 60.0;
+const TICKS: usize = 600;
+
+fn shape() -> Option<Shape> {
+    Some(Shape {
+        kind: ShapeKind::Square,
+        scale: Vector2::new(10.0, 10.0),
+        color: Color::rgb(1.0, 1.0, 1.0),
+        blend: BlendMode::Opaque,
+    })
+}
+
+/// A world exercising every system, with entities scattered by the seeded
+/// generator. Returns the world and its player.
+fn scenario(seed: u64) -> (World, Entity) {
+    let mut world = World::with_seed(seed);
+
+    let player = world.spawn(Bundle {
+        transform: Some(Transform2D::from_position(Vector2::new(0.0, 0.0))),
+        velocity: Some(Velocity::ZERO),
+        shape: shape(),
+        sprite: Some(Sprite {
+            name: "player".to_string(),
+            dimensions: Vector2::new(10.0, 10.0),
+            layer: 1,
+            tint: Color::WHITE,
+            blend: BlendMode::PremultipliedAlpha,
+        }),
+        collider: Some(Collider::new(Vector2::new(5.0, 5.0))),
+        controller: Some(Controller::Player),
+        timeline: Some(InputTimeline::with_seconds(5.0, 60)),
+        ..Bundle::default()
+    });
+
+    for _ in 0..20 {
+        let position = Vector2::new(
+            world.rng().range_f32(-200.0, 200.0),
+            world.rng().range_f32(-200.0, 200.0),
+        );
+        let velocity = Velocity {
+            linear: Vector2::new(
+                world.rng().range_f32(-50.0, 50.0),
+                world.rng().range_f32(-50.0, 50.0),
+            ),
+            angular: world.rng().range_f32(-3.0, 3.0),
+        };
+        let time_scale = TimeScale(world.rng().range_f32(0.0, 2.0));
+        world.spawn(Bundle {
+            transform: Some(Transform2D::from_position(position)),
+            velocity: Some(velocity),
+            time_scale: Some(time_scale),
+            shape: shape(),
+            ..Bundle::default()
+        });
+    }
+
+    world.spawn(Bundle {
+        transform: Some(Transform2D::from_position(Vector2::new(0.0, 0.0))),
+        time_field: Some(TimeField::anchored(player, Vector2::new(50.0, 50.0), 0.3)),
+        ..Bundle::default()
+    });
+    world.spawn(Bundle {
+        transform: Some(Transform2D::from_position(Vector2::new(100.0, 0.0))),
+        time_field: Some(TimeField::new(Vector2::new(60.0, 60.0), 1.7)),
+        ..Bundle::default()
+    });
+    let switch = world.spawn(Bundle {
+        transform: Some(Transform2D::from_position(Vector2::new(80.0, 0.0))),
+        collider: Some(Collider::new(Vector2::new(10.0, 10.0))),
+        switch: Some(Switch::default()),
+        ..Bundle::default()
+    });
+    world.spawn(Bundle {
+        transform: Some(Transform2D::from_position(Vector2::new(0.0, 80.0))),
+        collider: Some(Collider::new(Vector2::new(40.0, 5.0))),
+        door: Some(Door::new(vec![switch])),
+        ..Bundle::default()
+    });
+
+    (world, player)
+}
+
+/// A fixed, varied input sequence
+fn inputs() -> Vec<PlayerInput> {
+    (0..TICKS)
+        .map(|tick| PlayerInput {
+            up: tick % 90 < 30,
+            down: tick % 150 > 120,
+            left: tick % 70 < 20,
+            right: tick % 50 < 35,
+        })
+        .collect()
+}
+
+/// Runs the scenario, spawning an echo part way through
+fn run(seed: u64) -> ChecksumLog {
+    let (mut world, player) = scenario(seed);
+    let inputs = inputs();
+    let (before, after) = inputs.split_at(TICKS / 2);
+    let mut log = run_inputs(&mut world, DT, before.iter().copied());
+    world.spawn_echo(player, 120).unwrap();
+    for (tick, checksum) in run_inputs(&mut world, DT, after.iter().copied()).entries() {
+        log.record(*tick, *checksum);
+    }
+    log
+}
+
+#[test]
+fn same_seed_and_inputs_give_same_checksums() {
+    let first = run(7);
+    let second = run(7);
+    assert_eq!(first.len(), TICKS);
+    if let Err(divergence) = second.verify(&first) {
+        panic!("{}", divergence);
+    }
+}
+
+#[test]
+fn different_seeds_diverge() {
+    let divergence = run(8).verify(&run(7)).unwrap_err();
+    assert_eq!(divergence.tick, 1);
+}
+
+#[test]
+fn changed_input_diverges_at_that_tick() {
+    let (mut world, _) = scenario(7);
+    let expected = run_inputs(&mut world, DT, inputs());
+
+    let mut changed = inputs();
+    changed[100].up = !changed[100].up;
+    let (mut world, _) = scenario(7);
+    let actual = run_inputs(&mut world, DT, changed);
+
+    assert_eq!(actual.verify(&expected).unwrap_err().tick, 101);
+}
+
+#[test]
+fn rewinding_and_replaying_reproduces_checksums() {
+    let inputs = inputs();
+    let (mut world, _) = scenario(7);
+    let expected = run_inputs(&mut world, DT, inputs.iter().copied());
+
+    let (mut world, _) = scenario(7);
+    let mut rewind = Rewind::new(10.0, 60);
+    for input in &inputs[..400] {
+        world.player_input = *input;
+        rewind.update(&mut world, DT);
+    }
+    rewind.set_rewinding(true);
+    for _ in 0..150 {
+        rewind.update(&mut world, DT);
+    }
+    rewind.set_rewinding(false);
+    assert_eq!(world.tick(), 250);
+
+    let mut actual = ChecksumLog::new();
+    for (tick, checksum) in &expected.entries()[..250] {
+        actual.record(*tick, *checksum);
+    }
+    for input in &inputs[250..] {
+        world.player_input = *input;
+        rewind.update(&mut world, DT);
+        actual.record(world.tick(), world.checksum());
+    }
+
+    if let Err(divergence) = actual.verify(&expected) {
+        panic!("{}", divergence);
+    }
+}
+
+/// Checksum of the scenario after `change` was made to every entity it
+/// applies to, which it returns whether it did
+fn checksum_after(change: impl Fn(&mut World, Entity) -> bool) -> u64 {
+    let (mut world, _) = scenario(7);
+    let entities: Vec<Entity> = world.entities().collect();
+    let changed = entities
+        .into_iter()
+        .filter(|&entity| change(&mut world, entity))
+        .count();
+    assert!(changed > 0, "nothing in the scenario to change");
+    world.checksum()
+}
+
+#[test]
+fn every_component_is_part_of_the_checksum() {
+    let (world, _) = scenario(7);
+    let expected = world.checksum();
+
+    let shape = checksum_after(|world, entity| {
+        let shape = world.shapes.get_mut(entity);
+        shape.map(|shape| shape.color.g = 0.5).is_some()
+    });
+    assert_ne!(shape, expected, "shapes");
+    let sprite = checksum_after(|world, entity| {
+        let sprite = world.sprites.get_mut(entity);
+        sprite.map(|sprite| sprite.layer += 1).is_some()
+    });
+    assert_ne!(sprite, expected, "sprites");
+    let collider = checksum_after(|world, entity| {
+        let collider = world.colliders.get_mut(entity);
+        collider
+            .map(|collider| collider.half_extents.x += 1.0)
+            .is_some()
+    });
+    assert_ne!(collider, expected, "colliders");
+    let time_field = checksum_after(|world, entity| {
+        let field = world.time_fields.get_mut(entity);
+        field.map(|field| field.multiplier *= 2.0).is_some()
+    });
+    assert_ne!(time_field, expected, "time fields");
+    let door = checksum_after(|world, entity| {
+        let door = world.doors.get_mut(entity);
+        door.map(|door| door.switches.clear()).is_some()
+    });
+    assert_ne!(door, expected, "doors");
+}
@@ -0,0 +1,176 @@
+use cgmath::Vector2;
+use time_game::{
+    components::{Collider, Transform2D, Velocity},
+    echo::{Controller, InputTimeline, PlayerInput},
+    ecs::entity::Entity,
+    puzzle::{Door, Switch},
+    rewind::Rewind,
+    world::{Bundle, World},
+};
+
+const DT: f32 = 1.0 / 60.0;
+
+const RIGHT: PlayerInput = PlayerInput {
+    up: false,
+    down: false,
+    left: false,
+    right: true,
+};
+
+/// A world with a player at the origin
+fn world_with_player() -> (World, Entity) {
+    let mut world = World::new();
+    let player = world.spawn(Bundle {
+        transform: Some(Transform2D::from_position(Vector2::new(0.0, 0.0))),
+        velocity: Some(Velocity::ZERO),
+        controller: Some(Controller::Player),
+        timeline: Some(InputTimeline::with_seconds(5.0, 60)),
+        ..Bundle::default()
+    });
+    (world, player)
+}
+
+fn position(world: &World, entity: Entity) -> Vector2<f32> {
+    world.transforms.get(entity).unwrap().position
+}
+
+fn add_switch(world: &mut World, x: f32, y: f32) -> Entity {
+    world.spawn(Bundle {
+        transform: Some(Transform2D::from_position(Vector2::new(x, y))),
+        collider: Some(Collider::new(Vector2::new(5.0, 5.0))),
+        switch: Some(Switch::default()),
+        ..Bundle::default()
+    })
+}
+
+fn add_door(
+    world: &mut World,
+    center: Vector2<f32>,
+    half_extents: Vector2<f32>,
+    switches: Vec<Entity>,
+) -> Entity {
+    world.spawn(Bundle {
+        transform: Some(Transform2D::from_position(center)),
+        collider: Some(Collider::new(half_extents)),
+        door: Some(Door::new(switches)),
+        ..Bundle::default()
+    })
+}
+
+fn run(world: &mut World, input: PlayerInput, ticks: usize) {
+    world.player_input = input;
+    for _ in 0..ticks {
+        world.update(DT);
+    }
+}
+
+#[test]
+fn echo_retraces_recorded_path() {
+    let (mut world, player) = world_with_player();
+    run(&mut world, RIGHT, 30);
+    let recorded_end = position(&world, player);
+    run(&mut world, PlayerInput::default(), 30);
+
+    let echo = world.spawn_echo(player, 60).unwrap();
+    assert_eq!(position(&world, echo), Vector2::new(0.0, 0.0));
+
+    run(&mut world, PlayerInput::default(), 60);
+    assert_eq!(position(&world, echo), recorded_end);
+
+    // Out of inputs, the echo stands still
+    run(&mut world, RIGHT, 10);
+    assert_eq!(position(&world, echo), recorded_end);
+}
+
+#[test]
+fn echo_needs_recorded_input() {
+    let (mut world, player) = world_with_player();
+    assert_eq!(world.spawn_echo(player, 60), None);
+}
+
+#[test]
+fn player_and_echo_hold_switches_to_open_door() {
+    let (mut world, player) = world_with_player();
+    let near = add_switch(&mut world, 0.0, 0.0);
+    let far = add_switch(&mut world, 50.0, 0.0);
+    let door = add_door(
+        &mut world,
+        Vector2::new(0.0, 100.0),
+        Vector2::new(10.0, 10.0),
+        vec![near, far],
+    );
+
+    // Walk to the far switch
+    run(&mut world, RIGHT, 20);
+    assert!(world.switches.get(far).unwrap().pressed);
+    assert!(!world.doors.get(door).unwrap().open);
+
+    // Walk back while the echo repeats the trip to the far switch
+    world.spawn_echo(player, 20).unwrap();
+    let left = PlayerInput {
+        left: true,
+        ..Default::default()
+    };
+    run(&mut world, left, 20);
+    assert!(world.switches.get(near).unwrap().pressed);
+    assert!(world.switches.get(far).unwrap().pressed);
+    assert!(world.doors.get(door).unwrap().open);
+}
+
+#[test]
+fn closed_door_blocks_player() {
+    let (mut world, player) = world_with_player();
+    let switch = add_switch(&mut world, -100.0, 0.0);
+    add_door(
+        &mut world,
+        Vector2::new(20.0, 0.0),
+        Vector2::new(5.0, 50.0),
+        vec![switch],
+    );
+
+    run(&mut world, RIGHT, 60);
+    assert!(position(&world, player).x < 15.0);
+}
+
+#[test]
+fn doors_without_switches_stay_shut() {
+    let (mut world, player) = world_with_player();
+    let door = add_door(
+        &mut world,
+        Vector2::new(20.0, 0.0),
+        Vector2::new(5.0, 50.0),
+        vec![],
+    );
+
+    run(&mut world, RIGHT, 60);
+    assert!(!world.doors.get(door).unwrap().open);
+    assert!(position(&world, player).x < 15.0);
+}
+
+#[test]
+fn rewinding_past_an_echo_removes_it() {
+    let (mut world, player) = world_with_player();
+    let mut rewind = Rewind::new(5.0, 60);
+
+    world.player_input = RIGHT;
+    for _ in 0..30 {
+        rewind.update(&mut world, DT);
+    }
+    let echo = world.spawn_echo(player, 30).unwrap();
+    for _ in 0..10 {
+        rewind.update(&mut world, DT);
+    }
+    assert!(world.is_alive(echo));
+
+    rewind.set_rewinding(true);
+    for _ in 0..20 {
+        rewind.update(&mut world, DT);
+    }
+    assert!(!world.is_alive(echo));
+    assert_eq!(world.tick(), 20);
+
+    // The timeline forgot the rewound ticks, so the last 20 recorded ticks
+    // start at the very beginning
+    let echo = world.spawn_echo(player, 20).unwrap();
+    assert_eq!(position(&world, echo), Vector2::new(0.0, 0.0));
+}
@@ -0,0 +1,84 @@
+use cgmath::Vector2;
+use time_game::{
+    components::{Transform2D, Velocity},
+    ecs::{entity::Entities, storage::Storage},
+    world::{Bundle, World},
+};
+
+#[test]
+fn freed_ids_are_reused_with_a_new_generation() {
+    let mut entities = Entities::new();
+    let a = entities.allocate();
+    let b = entities.allocate();
+    assert!(entities.free(a));
+    assert!(!entities.free(a));
+
+    let c = entities.allocate();
+    assert_eq!(c.index(), a.index());
+    assert_ne!(c, a);
+    assert!(!entities.is_alive(a));
+    assert!(entities.is_alive(b));
+    assert!(entities.is_alive(c));
+    assert_eq!(entities.len(), 2);
+}
+
+#[test]
+fn storage_ignores_stale_ids() {
+    let mut entities = Entities::new();
+    let mut storage = Storage::new();
+    let old = entities.allocate();
+    storage.insert(old, 1);
+    entities.free(old);
+    let new = entities.allocate();
+
+    assert_eq!(storage.get(new), None);
+    assert_eq!(storage.insert(new, 2), None);
+    assert_eq!(storage.get(old), None);
+    assert_eq!(storage.remove(old), None);
+    assert_eq!(storage.get(new), Some(&2));
+}
+
+#[test]
+fn join_visits_entities_with_both_components_in_index_order() {
+    let mut entities = Entities::new();
+    let ids: Vec<_> = (0..4).map(|_| entities.allocate()).collect();
+    let mut numbers = Storage::new();
+    let mut names = Storage::new();
+    for (i, &entity) in ids.iter().enumerate() {
+        numbers.insert(entity, i);
+    }
+    names.insert(ids[3], "d");
+    names.insert(ids[1], "b");
+
+    let joined: Vec<_> = numbers
+        .join(&names)
+        .map(|(entity, &number, &name)| (entity, number, name))
+        .collect();
+    assert_eq!(joined, vec![(ids[1], 1, "b"), (ids[3], 3, "d")]);
+}
+
+#[test]
+fn commands_are_applied_at_the_end_of_the_tick() {
+    let mut world = World::new();
+    let mover = world.spawn(Bundle {
+        transform: Some(Transform2D::from_position(Vector2::new(0.0, 0.0))),
+        velocity: Some(Velocity {
+            linear: Vector2::new(1.0, 0.0),
+            angular: 0.0,
+        }),
+        ..Bundle::default()
+    });
+
+    world.commands.despawn(mover);
+    world.commands.spawn(Bundle::default());
+    assert!(world.is_alive(mover));
+
+    world.update(1.0);
+    assert!(!world.is_alive(mover));
+    assert!(world.commands.is_empty());
+    let spawned: Vec<_> = world.entities().collect();
+    assert_eq!(spawned.len(), 1);
+    // The spawn ran before the despawn, even though it was requested after,
+    // so it didn't get the despawned entity's id
+    assert_ne!(spawned[0].index(), mover.index());
+}
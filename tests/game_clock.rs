@@ -0,0 +1,138 @@
+use std::time::{Duration, Instant};
+
+use time_game::game_clock::GameClock;
+
+/// Ticks of 125ms, which floats represent exactly
+const TICKS_PER_SECOND: u32 = 8;
+
+fn millis(millis: f64) -> Duration {
+    Duration::from_secs_f64(millis / 1000.0)
+}
+
+fn assert_close(actual: f32, expected: f32) {
+    assert!(
+        (actual - expected).abs() < 1e-4,
+        "expected {} but got {}",
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn first_frame_only_starts_the_clock() {
+    let mut clock = GameClock::new(TICKS_PER_SECOND);
+    let start = Instant::now();
+    assert_eq!(clock.advance_to(start), 0);
+    assert_eq!(clock.advance_to(start + millis(125.0)), 1);
+}
+
+#[test]
+fn partial_steps_accumulate() {
+    let mut clock = GameClock::new(TICKS_PER_SECOND);
+    assert_eq!(clock.advance(millis(100.0)), 0);
+    assert_eq!(clock.advance(millis(100.0)), 1);
+    // 75ms are left over
+    assert_eq!(clock.advance(millis(25.0)), 0);
+    assert_eq!(clock.advance(millis(25.0)), 1);
+    assert_close(clock.alpha(), 0.0);
+}
+
+#[test]
+fn alpha_is_how_far_into_the_next_tick() {
+    let mut clock = GameClock::new(TICKS_PER_SECOND);
+    assert_close(clock.alpha(), 0.0);
+    clock.advance(millis(31.25));
+    assert_close(clock.alpha(), 0.25);
+    clock.advance(millis(62.5));
+    assert_close(clock.alpha(), 0.75);
+    assert_eq!(clock.advance(millis(62.5)), 1);
+    assert_close(clock.alpha(), 0.25);
+}
+
+#[test]
+fn each_advance_runs_every_whole_tick_due() {
+    let mut clock = GameClock::new(TICKS_PER_SECOND);
+    assert_eq!(clock.advance(Duration::ZERO), 0);
+    assert_eq!(clock.advance(millis(125.0)), 1);
+    assert_eq!(clock.advance(millis(250.0)), 2);
+    assert_eq!(clock.advance(clock.step_duration()), 1);
+    assert_close(clock.step_seconds(), 0.125);
+}
+
+#[test]
+fn long_frames_are_clamped() {
+    let mut clock = GameClock::new(TICKS_PER_SECOND);
+    // A stall of ten seconds runs no more ticks than a quarter second would
+    assert_eq!(clock.advance(Duration::from_secs(10)), 2);
+    assert_close(clock.frame_seconds(), 0.25);
+    assert_close(clock.alpha(), 0.0);
+    assert_eq!(clock.advance(millis(125.0)), 1);
+    assert_close(clock.frame_seconds(), 0.125);
+}
+
+#[test]
+fn time_scale_changes_how_many_ticks_run() {
+    let mut clock = GameClock::new(TICKS_PER_SECOND);
+    clock.set_time_scale(2.0);
+    assert_eq!(clock.advance(millis(125.0)), 2);
+    // Real time is still reported as it passed
+    assert_close(clock.frame_seconds(), 0.125);
+
+    clock.set_time_scale(0.5);
+    assert_eq!(clock.advance(millis(125.0)), 0);
+    assert_close(clock.alpha(), 0.5);
+    assert_eq!(clock.advance(millis(125.0)), 1);
+}
+
+#[test]
+fn paused_clocks_run_no_ticks() {
+    let mut clock = GameClock::new(TICKS_PER_SECOND);
+    clock.advance(millis(62.5));
+    clock.set_paused(true);
+    assert!(clock.is_paused());
+    assert_eq!(clock.advance(millis(250.0)), 0);
+    assert_eq!(clock.advance(millis(250.0)), 0);
+    // The partial tick from before pausing is kept for later
+    assert_close(clock.alpha(), 0.5);
+
+    clock.set_paused(false);
+    assert_eq!(clock.advance(millis(62.5)), 1);
+}
+
+#[test]
+fn stepping_runs_one_tick_while_paused() {
+    let mut clock = GameClock::new(TICKS_PER_SECOND);
+    clock.step();
+    // Unpaused, stepping does nothing
+    assert_eq!(clock.advance(Duration::ZERO), 0);
+
+    clock.set_paused(true);
+    clock.step();
+    assert_eq!(clock.advance(Duration::ZERO), 1);
+    assert_eq!(clock.advance(millis(250.0)), 0);
+
+    clock.step();
+    clock.step();
+    assert_eq!(clock.advance(millis(10.0)), 2);
+}
+
+#[test]
+fn changing_the_scale_keeps_partial_ticks() {
+    let mut clock = GameClock::new(TICKS_PER_SECOND);
+    clock.advance(millis(62.5));
+    clock.set_time_scale(4.0);
+    assert_close(clock.alpha(), 0.5);
+    // Half a tick was left, which now takes a quarter of the real time
+    assert_eq!(clock.advance(millis(15.625)), 1);
+    assert_close(clock.alpha(), 0.0);
+
+    clock.set_time_scale(1.0);
+    assert_eq!(clock.advance(millis(125.0)), 1);
+    assert_close(clock.alpha(), 0.0);
+
+    // Scales are kept within a sensible range
+    clock.set_time_scale(1000.0);
+    assert_eq!(clock.time_scale(), 16.0);
+    clock.set_time_scale(0.0);
+    assert_eq!(clock.time_scale(), 1.0 / 16.0);
+}
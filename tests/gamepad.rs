@@ -0,0 +1,226 @@
+use time_game::{
+    game::Game,
+    input::{
+        actions::{self, CAMERA_RIGHT, MOVE_LEFT, MOVE_RIGHT, REWIND},
+        controls::{Capture, Controls, Rebind, RebindProgress},
+        event::InputEvent,
+        gamepad::{
+            DeadZones, GamepadAxis, GamepadBackend, GamepadButton, GamepadEvent, GamepadId,
+            Gamepads, VirtualGamepads,
+        },
+        map::{Axis, Binding, Input},
+    },
+};
+
+/// Polls `backend` through `gamepads`, as the app does every frame
+fn poll(backend: &mut VirtualGamepads, gamepads: &mut Gamepads) -> Vec<InputEvent> {
+    backend
+        .poll()
+        .into_iter()
+        .flat_map(|event| gamepads.handle_event(event))
+        .map(InputEvent::Gamepad)
+        .collect()
+}
+
+#[test]
+fn dead_zones_rescale_what_is_left() {
+    let dead_zones = DeadZones {
+        stick: 0.2,
+        trigger: 0.1,
+    };
+    assert_eq!(dead_zones.apply_to_stick(0.1, 0.1), (0.0, 0.0));
+    let (x, y) = dead_zones.apply_to_stick(0.6, 0.0);
+    assert!((x - 0.5).abs() < 1e-6);
+    assert_eq!(y, 0.0);
+    assert_eq!(dead_zones.apply_to_stick(1.0, 0.0), (1.0, 0.0));
+
+    assert_eq!(dead_zones.apply_to_trigger(0.05), 0.0);
+    assert!((dead_zones.apply_to_trigger(0.55) - 0.5).abs() < 1e-6);
+}
+
+#[test]
+fn sticks_only_report_changes() {
+    let mut backend = VirtualGamepads::new();
+    let mut gamepads = Gamepads::new(DeadZones::default());
+    let pad = backend.connect();
+    backend.set_axis(pad, GamepadAxis::LeftStickX, 0.05);
+    assert_eq!(
+        poll(&mut backend, &mut gamepads),
+        [InputEvent::Gamepad(GamepadEvent::Connected(pad))]
+    );
+
+    backend.set_axis(pad, GamepadAxis::LeftStickX, 1.0);
+    assert_eq!(
+        poll(&mut backend, &mut gamepads),
+        [InputEvent::Gamepad(GamepadEvent::Axis {
+            id: pad,
+            axis: GamepadAxis::LeftStickX,
+            value: 1.0
+        })]
+    );
+}
+
+#[test]
+fn unplugging_lets_go_of_everything() {
+    let mut backend = VirtualGamepads::new();
+    let mut gamepads = Gamepads::new(DeadZones::default());
+    let first = backend.connect();
+    let second = backend.connect();
+    backend.press(second, GamepadButton::South);
+    backend.set_axis(second, GamepadAxis::RightTrigger, 1.0);
+    poll(&mut backend, &mut gamepads);
+    assert_eq!(gamepads.connected().collect::<Vec<_>>(), [first, second]);
+
+    backend.disconnect(second);
+    assert_eq!(
+        poll(&mut backend, &mut gamepads),
+        [
+            GamepadEvent::Button {
+                id: second,
+                button: GamepadButton::South,
+                pressed: false
+            },
+            GamepadEvent::Axis {
+                id: second,
+                axis: GamepadAxis::RightTrigger,
+                value: 0.0
+            },
+            GamepadEvent::Disconnected(second),
+        ]
+        .map(InputEvent::Gamepad)
+    );
+    assert_eq!(gamepads.connected().collect::<Vec<_>>(), [first]);
+
+    // Pads that show up without connecting first are connected for them
+    backend.press(GamepadId(7), GamepadButton::East);
+    assert_eq!(poll(&mut backend, &mut gamepads).len(), 2);
+}
+
+#[test]
+fn pads_drive_the_same_actions_as_keys() {
+    let mut backend = VirtualGamepads::new();
+    let mut gamepads = Gamepads::new(DeadZones::default());
+    let mut map = actions::game_defaults();
+    let pad = backend.connect();
+
+    backend.set_axis(pad, GamepadAxis::LeftStickX, -0.9);
+    for event in poll(&mut backend, &mut gamepads) {
+        map.handle_event(&event);
+    }
+    assert!(map.just_pressed(MOVE_LEFT));
+    assert!(!map.is_pressed(MOVE_RIGHT));
+
+    backend.set_axis(pad, GamepadAxis::LeftStickX, 0.0);
+    backend.set_axis(pad, GamepadAxis::RightTrigger, 0.8);
+    for event in poll(&mut backend, &mut gamepads) {
+        map.handle_event(&event);
+    }
+    assert!(map.just_released(MOVE_LEFT));
+    assert!(map.is_pressed(REWIND));
+}
+
+#[test]
+fn analog_axes_hold_their_value() {
+    let mut backend = VirtualGamepads::new();
+    let mut gamepads = Gamepads::new(DeadZones {
+        stick: 0.0,
+        trigger: 0.0,
+    });
+    let mut map = actions::app_defaults();
+    map.bind(
+        CAMERA_RIGHT,
+        Binding::Axis(Axis::Gamepad(GamepadAxis::LeftStickX)),
+    );
+    let pad = backend.connect();
+
+    backend.set_axis(pad, GamepadAxis::LeftStickX, 0.25);
+    for event in poll(&mut backend, &mut gamepads) {
+        map.handle_event(&event);
+    }
+    map.advance();
+    assert_eq!(map.value(CAMERA_RIGHT), 0.25);
+}
+
+#[test]
+fn pads_move_the_player() {
+    let mut backend = VirtualGamepads::new();
+    let mut gamepads = Gamepads::new(DeadZones::default());
+    let mut game = Game::new(4);
+    let start = game.save_data().player_position;
+
+    let pad = backend.connect();
+    backend.press(pad, GamepadButton::DPadRight);
+    for event in poll(&mut backend, &mut gamepads) {
+        game.handle_event(&event);
+    }
+    for _ in 0..10 {
+        game.update(1.0 / 60.0);
+    }
+    assert!(game.save_data().player_position.0 > start.0);
+}
+
+#[test]
+fn held_sticks_only_press_once_while_rebinding() {
+    let mut controls = Controls::default();
+    let mut rebind = Rebind::new();
+    let mut stick = |value| {
+        let event = InputEvent::Gamepad(GamepadEvent::Axis {
+            id: GamepadId(0),
+            axis: GamepadAxis::LeftStickX,
+            value,
+        });
+        rebind.handle_event(&event, &mut controls).unwrap()
+    };
+    let right = Binding::chord([Input::GamepadAxis {
+        axis: GamepadAxis::LeftStickX,
+        positive: true,
+    }]);
+
+    assert_eq!(
+        stick(0.9),
+        RebindProgress::Chose {
+            action: MOVE_RIGHT.to_string(),
+            old: right.clone()
+        }
+    );
+    // Jitter of the stick held at its limit is not a new press
+    assert_eq!(stick(1.0), RebindProgress::Waiting);
+    assert_eq!(stick(0.1), RebindProgress::Waiting);
+    assert_eq!(
+        stick(0.8),
+        RebindProgress::Rebound {
+            action: MOVE_RIGHT.to_string(),
+            binding: right
+        }
+    );
+}
+
+#[test]
+fn pads_can_be_rebound() {
+    let mut capture = Capture::new();
+    let push = InputEvent::Gamepad(GamepadEvent::Axis {
+        id: GamepadId(0),
+        axis: GamepadAxis::LeftTrigger,
+        value: 0.7,
+    });
+    assert_eq!(
+        capture.handle_event(&push),
+        Some(Binding::chord([Input::GamepadAxis {
+            axis: GamepadAxis::LeftTrigger,
+            positive: true
+        }]))
+    );
+
+    // Dead zones are kept with the bindings, and old files without them
+    // get the defaults
+    let text = "(version: 1, game: {}, app: {}, dead_zones: (stick: 0.3))";
+    let controls = Controls::parse(text).unwrap();
+    assert_eq!(controls.dead_zones.stick, 0.3);
+    assert_eq!(controls.dead_zones.trigger, DeadZones::default().trigger);
+    assert_eq!(
+        Controls::parse("(version: 1, game: {}, app: {})")
+            .unwrap()
+            .dead_zones,
+        DeadZones::default()
+    );
+}
@@ -0,0 +1,214 @@
+use std::path::PathBuf;
+
+use cgmath::{Matrix3, Rad, Vector2};
+use image::{Rgba, RgbaImage};
+use time_game::{
+    golden::{self, GoldenImages, Tolerance, UPDATE_ENV_VAR},
+    graphics::{
+        GraphicsState, atlas::AtlasBuilder, blend::BlendMode, color::Color,
+        textured_pipeline::TexturedQuad,
+    },
+};
+
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 96;
+
+/// Software rasterizers may round differently or disagree about a few edge
+/// pixels, but anything wrong with a shader or matrix changes far more
+const TOLERANCE: Tolerance = Tolerance {
+    channel: 4,
+    pixels: 24,
+};
+
+fn golden_images() -> GoldenImages {
+    GoldenImages::new(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/tests/golden"),
+        PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden"),
+        TOLERANCE,
+    )
+}
+
+fn offscreen() -> GraphicsState {
+    pollster::block_on(GraphicsState::offscreen(WIDTH, HEIGHT)).unwrap()
+}
+
+/// Renders what was pushed to `graphics_state` and checks it against the
+/// reference called `name`
+fn check(graphics_state: &mut GraphicsState, name: &str) {
+    graphics_state.render().unwrap();
+    let image = graphics_state.read_pixels().unwrap();
+    if let Err(error) = golden_images().check(name, &image) {
+        panic!("{:#}", error);
+    }
+}
+
+fn checkerboard() -> RgbaImage {
+    RgbaImage::from_fn(8, 8, |x, y| {
+        if (x + y) % 2 == 0 {
+            Rgba([240, 200, 40, 255])
+        } else {
+            Rgba([40, 40, 160, 255])
+        }
+    })
+}
+
+/// Fades from opaque on the left to transparent on the right
+fn fade() -> RgbaImage {
+    RgbaImage::from_fn(16, 4, |x, _| Rgba([200, 60, 60, (255 - x * 17) as u8]))
+}
+
+#[test]
+fn textured_quads_draw_in_layer_order() {
+    let mut graphics_state = offscreen();
+    let mut builder = AtlasBuilder::premultiplied();
+    builder.add_image("checker", checkerboard());
+    builder.add_image("fade", fade());
+    let atlas = graphics_state.build_atlas(builder).unwrap();
+    let checker = atlas.region("checker").unwrap();
+    let fade = atlas.region("fade").unwrap();
+
+    let quad = |position: Vector2<f32>, rotation: f32, size: f32, layer: u32| TexturedQuad {
+        transform: Matrix3::from_translation(position) * Matrix3::from_angle_z(Rad(rotation)),
+        dimensions: Vector2::new(size, size),
+        layer,
+        texture: checker.texture,
+        uv: checker.uv,
+        tint: Color::WHITE,
+        blend: BlendMode::PremultipliedAlpha,
+    };
+    // Pushed back to front so that only sorting by layer gets them right
+    graphics_state.push_textured_quad(TexturedQuad {
+        texture: fade.texture,
+        uv: fade.uv,
+        dimensions: Vector2::new(96.0, 24.0),
+        ..quad(Vector2::new(64.0, 48.0), 0.0, 0.0, 2)
+    });
+    graphics_state.push_textured_quad(TexturedQuad {
+        tint: Color::rgba(0.5, 1.0, 0.5, 0.75),
+        ..quad(Vector2::new(80.0, 56.0), 0.6, 40.0, 1)
+    });
+    graphics_state.push_textured_quad(quad(Vector2::new(40.0, 40.0), 0.0, 48.0, 0));
+
+    check(&mut graphics_state, "textured_layers");
+}
+
+#[test]
+fn debug_shapes_rotate_about_their_centers() {
+    let mut graphics_state = offscreen();
+    let shapes = [
+        (Vector2::new(24.0, 72.0), 0.0, BlendMode::Opaque),
+        (Vector2::new(64.0, 72.0), 0.4, BlendMode::Alpha),
+        (Vector2::new(104.0, 72.0), 1.2, BlendMode::Additive),
+    ];
+    for (position, rotation, blend) in shapes {
+        graphics_state.push_debug_square(
+            position,
+            Vector2::new(28.0, 16.0),
+            rotation,
+            Color::rgba(0.9, 0.3, 0.1, 0.6),
+            blend,
+        );
+        graphics_state.push_debug_triangle(
+            position - Vector2::new(0.0, 44.0),
+            Vector2::new(24.0, 30.0),
+            -rotation,
+            Color::rgba(0.2, 0.8, 0.4, 0.6),
+            blend,
+        );
+    }
+    // Darkens everything under it
+    graphics_state.push_debug_square(
+        Vector2::new(64.0, 48.0),
+        Vector2::new(120.0, 8.0),
+        0.0,
+        Color::rgb(0.5, 0.5, 0.5),
+        BlendMode::Multiply,
+    );
+
+    check(&mut graphics_state, "debug_shapes");
+}
+
+#[test]
+fn camera_and_parent_transforms_compose() {
+    let mut graphics_state = offscreen();
+    let camera = graphics_state.camera_mut();
+    camera.position = Vector2::new(10.0, 5.0);
+    camera.zoom = 2.0;
+    camera.rotation = Rad(0.3);
+
+    let parent =
+        Matrix3::from_translation(Vector2::new(4.0, -6.0)) * Matrix3::from_angle_z(Rad(0.8));
+    graphics_state.push_debug_square_transformed(
+        parent,
+        Vector2::new(20.0, 10.0),
+        Color::rgb(0.9, 0.9, 0.2),
+        BlendMode::Opaque,
+    );
+    let child = parent * Matrix3::from_translation(Vector2::new(16.0, 0.0));
+    graphics_state.push_debug_triangle_transformed(
+        child,
+        Vector2::new(8.0, 8.0),
+        Color::rgb(0.2, 0.6, 0.9),
+        BlendMode::Opaque,
+    );
+    // Marks the camera's position, which stays at the center
+    graphics_state.push_debug_square(
+        Vector2::new(10.0, 5.0),
+        Vector2::new(2.0, 2.0),
+        0.0,
+        Color::WHITE,
+        BlendMode::Opaque,
+    );
+
+    check(&mut graphics_state, "camera_transforms");
+}
+
+#[test]
+fn diffs_count_pixels_past_the_tolerance() {
+    let expected = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255]));
+    let mut actual = expected.clone();
+    actual.put_pixel(0, 0, Rgba([103, 100, 100, 255]));
+    actual.put_pixel(3, 3, Rgba([100, 100, 160, 255]));
+
+    let diff = golden::compare(&actual, &expected, 3).unwrap();
+    assert_eq!(diff.mismatched, 1);
+    assert_eq!(diff.max_difference, 60);
+    assert_eq!(*diff.image.get_pixel(3, 3), Rgba([255, 0, 0, 255]));
+    assert_eq!(*diff.image.get_pixel(0, 0), Rgba([255, 255, 0, 255]));
+    assert!(!diff.is_within(Tolerance::EXACT));
+    assert!(diff.is_within(Tolerance {
+        channel: 3,
+        pixels: 1
+    }));
+
+    assert!(golden::compare(&actual, &RgbaImage::new(4, 5), 255).is_err());
+}
+
+#[test]
+fn failed_checks_write_the_render_and_a_diff() {
+    if std::env::var_os(UPDATE_ENV_VAR).is_some() {
+        return;
+    }
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden_failures");
+    let golden_images = GoldenImages::new(dir.join("references"), dir.join("failures"), TOLERANCE);
+
+    let expected = RgbaImage::from_pixel(8, 8, Rgba([0, 0, 0, 255]));
+    let actual = RgbaImage::from_pixel(8, 8, Rgba([255, 255, 255, 255]));
+    std::fs::create_dir_all(dir.join("references")).unwrap();
+    expected
+        .save(golden_images.reference_path("square"))
+        .unwrap();
+
+    assert!(golden_images.check("square", &expected).is_ok());
+    assert!(golden_images.check("square", &actual).is_err());
+    let diff = image::open(dir.join("failures/square.diff.png"))
+        .unwrap()
+        .to_rgba8();
+    assert!(diff.pixels().all(|&pixel| pixel == Rgba([255, 0, 0, 255])));
+    let rendered = image::open(dir.join("failures/square.actual.png"))
+        .unwrap()
+        .to_rgba8();
+    assert_eq!(rendered, actual);
+
+    assert!(golden_images.check("missing", &actual).is_err());
+}
@@ -0,0 +1,101 @@
+use time_game::{
+    app_state::AppState,
+    game::Game,
+    harness::{assert_near, assert_player_at, finish_script, run_until},
+    input::{
+        actions::{MOVE_UP, PAUSE},
+        gamepad::{GamepadButton, VirtualGamepads},
+        map::{Binding, Input},
+        script::InputScript,
+    },
+    progress::STARTING_REWIND_CHARGES,
+};
+use winit::keyboard::KeyCode;
+
+#[test]
+fn scripts_send_events_on_their_frames() {
+    let mut script = InputScript::new();
+    script.tap(5, KeyCode::KeyE);
+    script.hold(0, KeyCode::ArrowRight, 10);
+    assert_eq!(script.frames(), 11);
+
+    let due: Vec<_> = script.take_due(5).iter().map(|due| due.frame).collect();
+    assert_eq!(due, [0, 5]);
+    assert!(script.take_due(5).is_empty());
+    assert_eq!(script.take_due(100).len(), 2);
+    assert!(script.is_finished());
+}
+
+#[test]
+fn rewinding_returns_the_player_to_spawn() {
+    let mut app = AppState::headless(Game::new(1));
+    let spawn = app.game.player_position();
+
+    let mut script = InputScript::new();
+    script.hold(0, KeyCode::ArrowRight, 90);
+    script.hold(90, KeyCode::KeyR, 120);
+    app.play_script(script);
+
+    app.run_frames(90);
+    assert!(app.game.player_position().x > spawn.x + 10.0);
+
+    finish_script(&mut app, 0);
+    assert_player_at(&app.game, spawn, 1e-3);
+    assert_eq!(app.game.progress.rewind_charges, STARTING_REWIND_CHARGES);
+}
+
+#[test]
+fn scripts_reach_the_app_actions_too() {
+    let mut app = AppState::headless(Game::new(1));
+    let mut script = InputScript::new();
+    script.tap(10, KeyCode::KeyP);
+    script.press(10, KeyCode::ArrowUp);
+    app.play_script(script);
+
+    let spawn = app.game.player_position();
+    app.run_frames(30);
+    assert!(app.clock.is_paused());
+    // Pausing takes effect before the frame's tick, so the game never saw
+    // the key it was pressed with
+    assert_eq!(app.game.ticks(), 10);
+    assert_near(app.game.player_position(), spawn, 0.0);
+}
+
+#[test]
+fn app_actions_see_keys_the_game_binds() {
+    let mut app = AppState::headless(Game::new(1));
+    app.input.bind(
+        PAUSE,
+        Binding::chord([Input::Key(KeyCode::ShiftLeft), Input::Key(KeyCode::ArrowUp)]),
+    );
+    let mut script = InputScript::new();
+    script.press(0, KeyCode::ShiftLeft);
+    script.hold(5, KeyCode::ArrowUp, 5);
+    app.play_script(script);
+
+    app.run_frames(6);
+    assert!(app.clock.is_paused());
+    assert!(app.game.input.is_pressed(MOVE_UP));
+    assert!(app.input.is_pressed(PAUSE));
+
+    // Letting go reaches both, so neither is left held
+    app.run_frames(5);
+    assert!(!app.game.input.is_pressed(MOVE_UP));
+    assert!(!app.input.is_pressed(PAUSE));
+}
+
+#[test]
+fn virtual_pads_drive_a_headless_app() {
+    let pads = VirtualGamepads::new();
+    let mut app = AppState::headless(Game::new(1));
+    app.set_gamepad_backend(Box::new(pads.clone()));
+    let spawn = app.game.player_position();
+
+    let pad = pads.connect();
+    pads.press(pad, GamepadButton::DPadLeft);
+    let frames = run_until(&mut app, 60, "the player to move left", |game| {
+        game.player_position().x < spawn.x - 20.0
+    });
+    assert!(frames > 0);
+    assert_eq!(app.game.player_position().y, spawn.y);
+}
@@ -0,0 +1,138 @@
+use std::f32::consts::FRAC_PI_2;
+
+use cgmath::{Vector2, assert_abs_diff_eq};
+use time_game::{
+    components::{Transform2D, Velocity},
+    ecs::entity::Entity,
+    rewind::Rewind,
+    world::{Bundle, World},
+};
+
+const DT: f32 = 1.0 / 60.0;
+
+fn spawn(world: &mut World, transform: Transform2D) -> Entity {
+    world.spawn(Bundle {
+        transform: Some(transform),
+        ..Bundle::default()
+    })
+}
+
+/// A character at (100, 0) holding a hand 10 units to its right, which holds
+/// a weapon 5 units further out
+fn character(world: &mut World) -> (Entity, Entity, Entity) {
+    let character = spawn(world, Transform2D::from_position(Vector2::new(100.0, 0.0)));
+    let hand = spawn(
+        world,
+        Transform2D::from_position(Vector2::new(10.0, 0.0)).with_parent(character),
+    );
+    let weapon = spawn(
+        world,
+        Transform2D::from_position(Vector2::new(5.0, 0.0)).with_parent(hand),
+    );
+    (character, hand, weapon)
+}
+
+#[test]
+fn children_are_placed_relative_to_their_parents() {
+    let mut world = World::new();
+    let (_, hand, weapon) = character(&mut world);
+
+    assert_eq!(world.world_position(hand), Some(Vector2::new(110.0, 0.0)));
+    assert_eq!(world.world_position(weapon), Some(Vector2::new(115.0, 0.0)));
+}
+
+#[test]
+fn children_follow_moving_and_rotating_parents() {
+    let mut world = World::new();
+    let (character, _, weapon) = character(&mut world);
+    world.velocities.insert(
+        character,
+        Velocity {
+            linear: Vector2::new(0.0, 60.0),
+            angular: FRAC_PI_2 * 60.0,
+        },
+    );
+
+    world.update(DT);
+    // Moved up by one and turned a quarter, so the arm now points up
+    assert_abs_diff_eq!(
+        world.world_position(weapon).unwrap(),
+        Vector2::new(100.0, 16.0),
+        epsilon = 1e-4
+    );
+}
+
+#[test]
+fn scale_applies_to_children_positions() {
+    let mut world = World::new();
+    let parent = spawn(
+        &mut world,
+        Transform2D {
+            scale: Vector2::new(2.0, 3.0),
+            ..Transform2D::default()
+        },
+    );
+    let child = spawn(
+        &mut world,
+        Transform2D::from_position(Vector2::new(1.0, 1.0)).with_parent(parent),
+    );
+    assert_eq!(world.world_position(child), Some(Vector2::new(2.0, 3.0)));
+}
+
+#[test]
+fn despawning_a_parent_despawns_its_children() {
+    let mut world = World::new();
+    let (character, hand, weapon) = character(&mut world);
+    let bystander = spawn(&mut world, Transform2D::default());
+
+    world.despawn(character);
+    assert!(!world.is_alive(hand));
+    assert!(!world.is_alive(weapon));
+    assert!(world.is_alive(bystander));
+    assert_eq!(world.world_matrices.len(), 1);
+}
+
+#[test]
+fn parent_cycles_do_not_hang() {
+    let mut world = World::new();
+    let a = spawn(
+        &mut world,
+        Transform2D::from_position(Vector2::new(1.0, 0.0)),
+    );
+    let b = spawn(
+        &mut world,
+        Transform2D::from_position(Vector2::new(1.0, 0.0)).with_parent(a),
+    );
+    world.transforms.get_mut(a).unwrap().parent = Some(b);
+
+    world.update(DT);
+    assert!(world.world_position(a).is_some());
+    assert!(world.world_position(b).is_some());
+}
+
+#[test]
+fn rewinding_restores_world_matrices() {
+    let mut world = World::new();
+    let (character, _, weapon) = character(&mut world);
+    world.velocities.insert(
+        character,
+        Velocity {
+            linear: Vector2::new(60.0, 0.0),
+            angular: 0.0,
+        },
+    );
+    let mut rewind = Rewind::new(1.0, 60);
+
+    for _ in 0..10 {
+        rewind.update(&mut world, DT);
+    }
+    rewind.set_rewinding(true);
+    for _ in 0..10 {
+        rewind.update(&mut world, DT);
+    }
+    assert_abs_diff_eq!(
+        world.world_position(weapon).unwrap(),
+        Vector2::new(115.0, 0.0),
+        epsilon = 1e-4
+    );
+}
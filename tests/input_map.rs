@@ -0,0 +1,127 @@
+use time_game::input::{
+    actions,
+    event::InputEvent,
+    map::{Axis, Binding, Input, InputMap},
+};
+use winit::{
+    event::{MouseButton, MouseScrollDelta},
+    keyboard::KeyCode,
+};
+
+fn key(code: KeyCode, pressed: bool) -> InputEvent {
+    InputEvent::Key { code, pressed }
+}
+
+#[test]
+fn edges_last_until_advance() {
+    let mut map = InputMap::new();
+    map.bind("jump", Binding::key(KeyCode::Space));
+
+    assert!(map.handle_event(&key(KeyCode::Space, true)));
+    assert!(map.is_pressed("jump"));
+    assert!(map.just_pressed("jump"));
+    map.advance();
+    assert!(map.is_pressed("jump"));
+    assert!(!map.just_pressed("jump"));
+
+    map.handle_event(&key(KeyCode::Space, false));
+    assert!(!map.is_pressed("jump"));
+    assert!(map.just_released("jump"));
+    map.advance();
+    assert!(!map.just_released("jump"));
+
+    // A tap within one tick still registers
+    map.handle_event(&key(KeyCode::Space, true));
+    map.handle_event(&key(KeyCode::Space, false));
+    assert!(!map.is_pressed("jump"));
+    assert!(map.just_pressed("jump"));
+    assert!(map.just_released("jump"));
+
+    assert!(!map.handle_event(&key(KeyCode::KeyQ, true)));
+    assert!(!map.is_pressed("unknown"));
+}
+
+#[test]
+fn any_binding_triggers_an_action() {
+    let mut map = InputMap::new();
+    map.bind("fire", Binding::key(KeyCode::KeyF));
+    map.bind("fire", Binding::mouse(MouseButton::Left));
+    assert_eq!(map.bindings("fire").len(), 2);
+
+    map.handle_event(&InputEvent::MouseButton {
+        button: MouseButton::Left,
+        pressed: true,
+    });
+    assert!(map.just_pressed("fire"));
+    map.advance();
+
+    // Still held through the key while the button is released
+    map.handle_event(&key(KeyCode::KeyF, true));
+    map.handle_event(&InputEvent::MouseButton {
+        button: MouseButton::Left,
+        pressed: false,
+    });
+    assert!(map.is_pressed("fire"));
+    assert!(!map.just_pressed("fire"));
+    assert!(!map.just_released("fire"));
+}
+
+#[test]
+fn chords_shadow_their_parts() {
+    let mut map = InputMap::new();
+    map.bind("down", Binding::key(KeyCode::KeyS));
+    map.bind(
+        "save",
+        Binding::chord([Input::Key(KeyCode::ControlLeft), Input::Key(KeyCode::KeyS)]),
+    );
+
+    map.handle_event(&key(KeyCode::ControlLeft, true));
+    assert!(!map.is_pressed("save"));
+    map.handle_event(&key(KeyCode::KeyS, true));
+    assert!(map.just_pressed("save"));
+    assert!(!map.is_pressed("down"));
+
+    // Letting go of Ctrl leaves plain S held
+    map.advance();
+    map.handle_event(&key(KeyCode::ControlLeft, false));
+    assert!(map.just_released("save"));
+    assert!(map.just_pressed("down"));
+}
+
+#[test]
+fn axes_add_up_until_advance() {
+    let mut map = InputMap::new();
+    map.bind("zoom", Binding::Axis(Axis::WheelY));
+    map.bind("left", Binding::key(KeyCode::KeyA));
+    map.bind("right", Binding::key(KeyCode::KeyD));
+
+    map.handle_event(&InputEvent::MouseWheel(MouseScrollDelta::LineDelta(
+        0.0, 1.0,
+    )));
+    map.handle_event(&InputEvent::MouseWheel(MouseScrollDelta::LineDelta(
+        0.0, 2.0,
+    )));
+    assert_eq!(map.value("zoom"), 3.0);
+    assert!(!map.is_pressed("zoom"));
+    map.advance();
+    assert_eq!(map.value("zoom"), 0.0);
+
+    map.handle_event(&key(KeyCode::KeyA, true));
+    assert_eq!(map.axis("left", "right"), -1.0);
+    map.handle_event(&key(KeyCode::KeyD, true));
+    assert_eq!(map.axis("left", "right"), 0.0);
+}
+
+#[test]
+fn default_actions_have_bindings() {
+    for map in [actions::game_defaults(), actions::app_defaults()] {
+        for action in map.actions() {
+            assert!(!map.bindings(action).is_empty(), "{} is unbound", action);
+        }
+    }
+    assert!(
+        actions::app_defaults()
+            .bindings(actions::QUICKSAVE)
+            .contains(&Binding::key(KeyCode::F6))
+    );
+}
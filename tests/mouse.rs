@@ -0,0 +1,133 @@
+use cgmath::Vector2;
+use time_game::{
+    game::Game,
+    graphics::camera::Camera2D,
+    input::{
+        event::InputEvent,
+        mouse::{Drag, Mouse},
+    },
+};
+use winit::event::{MouseButton, MouseScrollDelta};
+
+fn button(pressed: bool) -> InputEvent {
+    InputEvent::MouseButton {
+        button: MouseButton::Left,
+        pressed,
+    }
+}
+
+fn pointer(x: f32, y: f32) -> InputEvent {
+    InputEvent::PointerMoved { x, y }
+}
+
+#[test]
+fn small_movements_still_click() {
+    let mut mouse = Mouse::new(4.0);
+    mouse.handle_event(&pointer(10.0, 10.0));
+    mouse.handle_event(&button(true));
+    assert!(mouse.just_pressed(MouseButton::Left));
+    mouse.handle_event(&pointer(12.0, 11.0));
+    mouse.handle_event(&button(false));
+
+    assert!(mouse.clicked(MouseButton::Left));
+    assert!(mouse.just_released(MouseButton::Left));
+    assert_eq!(mouse.drag_ended(MouseButton::Left), None);
+    mouse.advance();
+    assert!(!mouse.clicked(MouseButton::Left));
+    assert!(!mouse.just_pressed(MouseButton::Left));
+}
+
+#[test]
+fn drags_start_past_the_threshold() {
+    let mut mouse = Mouse::new(4.0);
+    mouse.handle_event(&pointer(0.0, 0.0));
+    mouse.handle_event(&button(true));
+    mouse.handle_event(&pointer(3.0, 0.0));
+    assert_eq!(mouse.drag(MouseButton::Left), None);
+
+    mouse.handle_event(&pointer(10.0, 0.0));
+    let started = mouse.drag_started(MouseButton::Left).unwrap();
+    assert_eq!(started.start, Vector2::new(0.0, 0.0));
+    mouse.advance();
+    assert_eq!(mouse.drag_started(MouseButton::Left), None);
+
+    mouse.handle_event(&pointer(20.0, 5.0));
+    assert_eq!(
+        mouse.drag(MouseButton::Left).unwrap().end,
+        Vector2::new(20.0, 5.0)
+    );
+    mouse.handle_event(&button(false));
+    assert_eq!(
+        mouse.drag_ended(MouseButton::Left),
+        Some(Drag {
+            button: MouseButton::Left,
+            start: Vector2::new(0.0, 0.0),
+            end: Vector2::new(20.0, 5.0),
+        })
+    );
+    assert!(!mouse.clicked(MouseButton::Left));
+    assert_eq!(mouse.drag(MouseButton::Left), None);
+}
+
+#[test]
+fn presses_need_a_position() {
+    let mut mouse = Mouse::new(4.0);
+    mouse.handle_event(&button(true));
+    assert!(!mouse.is_pressed(MouseButton::Left));
+    mouse.handle_event(&pointer(1.0, 1.0));
+    mouse.handle_event(&button(false));
+    assert!(!mouse.just_released(MouseButton::Left));
+}
+
+#[test]
+fn wheel_adds_up_in_lines() {
+    let mut mouse = Mouse::new(4.0);
+    mouse.handle_event(&InputEvent::MouseWheel(MouseScrollDelta::LineDelta(
+        0.0, 1.0,
+    )));
+    mouse.handle_event(&InputEvent::MouseWheel(MouseScrollDelta::LineDelta(
+        0.0, 2.0,
+    )));
+    assert_eq!(mouse.wheel(), Vector2::new(0.0, 3.0));
+    mouse.advance();
+    assert_eq!(mouse.wheel(), Vector2::new(0.0, 0.0));
+}
+
+#[test]
+fn cursor_maps_to_world_through_the_camera() {
+    let mut camera = Camera2D::new(Vector2::new(800.0, 600.0));
+    camera.position = Vector2::new(100.0, 50.0);
+    camera.zoom = 2.0;
+    // The center of the window shows the camera's position, y flipped
+    assert_eq!(
+        camera.screen_to_world(Vector2::new(400.0, 300.0)),
+        Vector2::new(100.0, 50.0)
+    );
+    assert_eq!(
+        camera.screen_to_world(Vector2::new(420.0, 280.0)),
+        Vector2::new(110.0, 60.0)
+    );
+}
+
+#[test]
+fn game_can_find_what_was_clicked() {
+    let mut game = Game::new(1);
+    let switch = game.world.switches.iter().next().unwrap().0;
+    let position = game.world.world_position(switch).unwrap();
+
+    game.handle_event(&pointer(position.x, position.y));
+    game.handle_event(&button(true));
+    game.handle_event(&button(false));
+    assert!(game.mouse.clicked(MouseButton::Left));
+    let clicked = game.mouse.position().unwrap();
+    assert!(
+        game.world
+            .entities_at(clicked)
+            .any(|entity| entity == switch)
+    );
+
+    // Clicks last until the next tick
+    game.update(1.0 / 60.0);
+    assert!(!game.mouse.clicked(MouseButton::Left));
+    assert_eq!(game.world.entities_at(Vector2::new(-1e6, -1e6)).count(), 0);
+}
@@ -0,0 +1,167 @@
+use cgmath::Vector2;
+use image::Rgba;
+use time_game::{
+    game::Game,
+    graphics::{
+        GraphicsState, atlas::AtlasBuilder, blend::BlendMode, color::Color,
+        render_error::RenderError,
+    },
+};
+use wgpu::SurfaceError;
+use winit::dpi::PhysicalSize;
+
+/// The clear color, as it reads back from an sRGB target
+const BACKGROUND: Rgba<u8> = Rgba([89, 124, 149, 255]);
+
+fn offscreen(width: u32, height: u32) -> GraphicsState {
+    pollster::block_on(GraphicsState::offscreen(width, height)).unwrap()
+}
+
+#[test]
+fn squares_read_back_where_they_were_drawn() {
+    let mut graphics_state = offscreen(64, 48);
+    // World y points up and the camera starts with the origin at the bottom
+    // left, so this is near the top left of the image
+    graphics_state.push_debug_square(
+        Vector2::new(16.0, 36.0),
+        Vector2::new(8.0, 8.0),
+        0.0,
+        Color::rgb(1.0, 0.0, 0.0),
+        BlendMode::Opaque,
+    );
+    graphics_state.render().unwrap();
+
+    let image = graphics_state.read_pixels().unwrap();
+    assert_eq!(image.dimensions(), (64, 48));
+    assert_eq!(*image.get_pixel(16, 12), Rgba([255, 0, 0, 255]));
+    assert_eq!(*image.get_pixel(48, 36), BACKGROUND);
+}
+
+#[test]
+fn odd_widths_survive_row_padding() {
+    let mut graphics_state = offscreen(37, 5);
+    graphics_state.render().unwrap();
+    let image = graphics_state.read_pixels().unwrap();
+    assert!(image.pixels().all(|&pixel| pixel == BACKGROUND));
+
+    graphics_state.resize(3, 70);
+    graphics_state.render().unwrap();
+    assert_eq!(graphics_state.read_pixels().unwrap().dimensions(), (3, 70));
+}
+
+#[test]
+fn frames_can_be_saved_as_png() {
+    let mut graphics_state = offscreen(40, 30);
+    graphics_state.push_debug_triangle(
+        Vector2::new(20.0, 15.0),
+        Vector2::new(20.0, 20.0),
+        0.5,
+        Color::rgba(0.0, 1.0, 0.5, 0.5),
+        BlendMode::Alpha,
+    );
+    graphics_state.render().unwrap();
+
+    let path = std::env::temp_dir().join("time_game_offscreen_test.png");
+    graphics_state.save_png(&path).unwrap();
+    let saved = image::open(&path).unwrap().to_rgba8();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(saved, graphics_state.read_pixels().unwrap());
+    assert!(saved.pixels().any(|&pixel| pixel != BACKGROUND));
+}
+
+#[test]
+fn games_see_the_viewport_change_when_drawn() {
+    let mut graphics_state = offscreen(64, 48);
+    let mut game = Game::new(0);
+    // Sprites missing from the atlas are skipped, so this draws only shapes
+    let atlas = graphics_state
+        .build_atlas(AtlasBuilder::premultiplied())
+        .unwrap();
+
+    game.draw(&mut graphics_state, &atlas, 1.0);
+    assert_eq!(game.viewport(), Vector2::new(64.0, 48.0));
+    graphics_state.clear_instances();
+
+    graphics_state.resize(120, 30);
+    graphics_state.scale_factor_changed(2.0);
+    game.draw(&mut graphics_state, &atlas, 1.0);
+    assert_eq!(game.viewport(), Vector2::new(60.0, 15.0));
+}
+
+/// Draws a red square over the top left quarter of a 64x48 target
+fn draw_square(graphics_state: &mut GraphicsState) {
+    graphics_state.push_debug_square(
+        Vector2::new(16.0, 36.0),
+        Vector2::new(32.0, 24.0),
+        0.0,
+        Color::rgb(1.0, 0.0, 0.0),
+        BlendMode::Opaque,
+    );
+}
+
+#[test]
+fn lost_or_outdated_surfaces_are_reconfigured() {
+    let mut graphics_state = offscreen(64, 48);
+    draw_square(&mut graphics_state);
+    let red = Rgba([255, 0, 0, 255]);
+
+    for error in [SurfaceError::Lost, SurfaceError::Outdated] {
+        graphics_state.render().unwrap();
+        assert_eq!(*graphics_state.read_pixels().unwrap().get_pixel(4, 4), red);
+
+        // The frame is skipped, and reconfiguring gave the target a new,
+        // empty texture of the same size
+        graphics_state.simulate_surface_error(error);
+        graphics_state.render().unwrap();
+        let image = graphics_state.read_pixels().unwrap();
+        assert_eq!(image.dimensions(), (64, 48));
+        assert!(image.pixels().all(|&pixel| pixel == Rgba([0, 0, 0, 0])));
+    }
+
+    // Timeouts skip the frame without touching the target
+    graphics_state.render().unwrap();
+    graphics_state.simulate_surface_error(SurfaceError::Timeout);
+    graphics_state.render().unwrap();
+    assert_eq!(*graphics_state.read_pixels().unwrap().get_pixel(4, 4), red);
+
+    graphics_state.simulate_surface_error(SurfaceError::OutOfMemory);
+    assert!(matches!(
+        graphics_state.render(),
+        Err(RenderError::OutOfMemory)
+    ));
+    graphics_state.simulate_surface_error(SurfaceError::Other);
+    assert!(matches!(
+        graphics_state.render(),
+        Err(RenderError::Surface(SurfaceError::Other))
+    ));
+}
+
+#[test]
+fn lost_devices_are_recreated_with_the_same_view() {
+    let mut graphics_state = offscreen(64, 48);
+    graphics_state.resize(80, 60);
+    graphics_state.scale_factor_changed(2.0);
+    graphics_state.camera_mut().position = Vector2::new(5.0, -3.0);
+    graphics_state.camera_mut().zoom = 2.0;
+
+    graphics_state.simulate_device_lost("driver reset");
+    match graphics_state.render() {
+        Err(RenderError::DeviceLost(message)) => assert_eq!(message, "driver reset"),
+        other => panic!("expected the device to be lost, got {:?}", other),
+    }
+
+    let camera = *graphics_state.camera();
+    let mut graphics_state = pollster::block_on(graphics_state.recreate()).unwrap();
+    let recreated = graphics_state.camera();
+    assert_eq!(recreated.position, camera.position);
+    assert_eq!(recreated.zoom, camera.zoom);
+    assert_eq!(recreated.viewport, Vector2::new(40.0, 30.0));
+    assert_eq!(graphics_state.scale_factor(), 2.0);
+    assert_eq!(graphics_state.physical_size(), PhysicalSize::new(80, 60));
+
+    // The new device draws normally
+    graphics_state.render().unwrap();
+    let image = graphics_state.read_pixels().unwrap();
+    assert_eq!(image.dimensions(), (80, 60));
+    assert!(image.pixels().all(|&pixel| pixel == BACKGROUND));
+}
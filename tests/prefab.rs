@@ -0,0 +1,177 @@
+use cgmath::Vector2;
+use time_game::{
+    components::Transform2D, game::Game, prefab::PrefabLibrary, scene::Scene, world::World,
+};
+
+fn parse(text: &str) -> Scene {
+    Scene::parse(text, "test.ron").unwrap()
+}
+
+/// A lamp post: a pole with a light attached above it
+fn library() -> PrefabLibrary {
+    let mut library = PrefabLibrary::new();
+    library.insert(
+        "light",
+        parse(
+            r#"#![enable(implicit_some)]
+            (
+                version: 1,
+                entities: [
+                    (transform: (position: (0.0, 0.0)), collider: (1.0, 1.0), time_scale: 0.5),
+                ],
+            )"#,
+        ),
+    );
+    library.insert(
+        "lamp",
+        parse(
+            r#"#![enable(implicit_some)]
+            (
+                version: 1,
+                entities: [
+                    (name: "pole", transform: (position: (0.0, 0.0)), collider: (1.0, 10.0)),
+                    (prefab: "light", transform: (position: (0.0, 10.0), parent: "pole")),
+                ],
+            )"#,
+        ),
+    );
+    library
+}
+
+#[test]
+fn nested_prefabs_expand_with_overrides() {
+    let scene = parse(
+        r#"#![enable(implicit_some)]
+        (
+            version: 1,
+            entities: [
+                (prefab: "lamp", transform: (position: (100.0, 0.0)), collider: (2.0, 20.0)),
+                (prefab: "lamp", transform: (position: (200.0, 0.0))),
+            ],
+        )"#,
+    );
+    let mut world = World::new();
+    let entities = scene.instantiate(&mut world, &library()).unwrap();
+    let [first_pole, first_light, second_pole, second_light] = entities[..] else {
+        panic!("Expected two poles and two lights, got {:?}", entities);
+    };
+
+    // Each instance's light is attached to its own pole
+    assert_eq!(
+        world.world_position(first_light),
+        Some(Vector2::new(100.0, 10.0))
+    );
+    assert_eq!(
+        world.world_position(second_light),
+        Some(Vector2::new(200.0, 10.0))
+    );
+
+    // Overrides only apply to their own instance's root
+    assert_eq!(
+        world.colliders.get(first_pole).unwrap().half_extents,
+        Vector2::new(2.0, 20.0)
+    );
+    assert_eq!(
+        world.colliders.get(second_pole).unwrap().half_extents,
+        Vector2::new(1.0, 10.0)
+    );
+    assert_eq!(world.time_scales.get(first_light).unwrap().0, 0.5);
+}
+
+#[test]
+fn instance_overrides_refer_to_the_surrounding_scene() {
+    let scene = parse(
+        r#"#![enable(implicit_some)]
+        (
+            version: 1,
+            entities: [
+                (name: "base", transform: (position: (50.0, 50.0))),
+                (prefab: "lamp", transform: (position: (0.0, 5.0), parent: "base")),
+            ],
+        )"#,
+    );
+    let mut world = World::new();
+    let entities = scene.instantiate(&mut world, &library()).unwrap();
+    assert_eq!(
+        world.world_position(entities[2]),
+        Some(Vector2::new(50.0, 65.0))
+    );
+}
+
+#[test]
+fn prefab_names_are_private_to_each_instance() {
+    let text = r#"#![enable(implicit_some)]
+(
+    version: 1,
+    entities: [
+        (prefab: "lamp"),
+        (transform: (position: (0.0, 0.0), parent: "pole")),
+    ],
+)"#;
+    let error = Scene::parse(text, "test.ron").unwrap_err().to_string();
+    assert!(error.starts_with("test.ron:6:"), "{}", error);
+}
+
+#[test]
+fn self_containing_prefabs_are_rejected() {
+    let mut library = library();
+    library.insert(
+        "light",
+        parse(r#"(version: 1, entities: [(prefab: "lamp")])"#),
+    );
+    let scene = parse(r#"(version: 1, entities: [(prefab: "lamp")])"#);
+    let error = scene.instantiate(&mut World::new(), &library).unwrap_err();
+    assert!(error.to_string().contains("contains itself"), "{}", error);
+}
+
+#[test]
+fn unknown_prefabs_are_reported() {
+    let scene = parse(r#"(version: 1, entities: [(prefab: "lmap")])"#);
+    let error = scene
+        .instantiate(&mut World::new(), &library())
+        .unwrap_err();
+    assert!(error.to_string().contains("lmap"), "{}", error);
+}
+
+#[test]
+fn spawn_prefab_places_the_root_at_runtime() {
+    let mut world = World::new();
+    let library = library();
+    let parent = world.spawn(time_game::world::Bundle {
+        transform: Some(Transform2D::from_position(Vector2::new(10.0, 0.0))),
+        ..Default::default()
+    });
+    let root = library
+        .spawn(
+            &mut world,
+            "lamp",
+            Transform2D::from_position(Vector2::new(5.0, 0.0)).with_parent(parent),
+        )
+        .unwrap();
+
+    assert_eq!(world.world_position(root), Some(Vector2::new(15.0, 0.0)));
+    let light = world.children(root).next().unwrap();
+    assert_eq!(world.world_position(light), Some(Vector2::new(15.0, 10.0)));
+}
+
+#[test]
+fn built_in_prefabs_spawn_in_game() {
+    let mut game = Game::new(1);
+    let before = game.world.entities().count();
+    let crystal = game
+        .spawn_prefab(
+            "time-crystal",
+            Transform2D::from_position(Vector2::new(-300.0, 0.0)),
+        )
+        .unwrap();
+    assert_eq!(game.world.entities().count(), before + 2);
+    assert!(game.world.sprites.contains(crystal));
+
+    game.update(1.0 / 60.0);
+    let field = game.world.children(crystal).next().unwrap();
+    assert_eq!(
+        game.world.world_position(field),
+        game.world.world_position(crystal)
+    );
+    assert!(game.spawn_prefab("nope", Transform2D::default()).is_err());
+}
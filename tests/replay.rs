@@ -0,0 +1,91 @@
+use std::fs;
+
+use time_game::{
+    game::Game,
+    input::event::InputEvent,
+    replay::{self, REPLAY_VERSION, Replay},
+};
+use winit::keyboard::KeyCode;
+
+fn key(code: KeyCode, pressed: bool) -> InputEvent {
+    InputEvent::Key { code, pressed }
+}
+
+/// Walks right, spawns an echo, rewinds a little and walks up
+fn recorded_replay() -> Replay {
+    let mut replay = Replay::new(42);
+    replay.record(10, key(KeyCode::ArrowRight, true));
+    replay.record(70, key(KeyCode::ArrowRight, false));
+    replay.record(70, key(KeyCode::KeyE, true));
+    replay.record(71, key(KeyCode::KeyE, false));
+    replay.record(100, key(KeyCode::KeyR, true));
+    replay.record(130, key(KeyCode::KeyR, false));
+    replay.record(130, key(KeyCode::ArrowUp, true));
+    replay.record(160, InputEvent::CursorMoved { x: 10.0, y: 20.0 });
+    replay.record(200, key(KeyCode::ArrowUp, false));
+    replay.ticks = 240;
+    replay
+}
+
+fn play(replay: &Replay) -> Game {
+    let mut game = Game::new(replay.seed);
+    replay::play_headless(&mut game, replay.clone());
+    game
+}
+
+#[test]
+fn headless_playback_is_repeatable() {
+    let replay = recorded_replay();
+    let first = play(&replay);
+    let second = play(&replay);
+    assert_eq!(first.ticks(), 240);
+    assert_eq!(first.world.checksum(), second.world.checksum());
+
+    // The echo was spawned, then rewound away after 30 ticks of rewinding
+    // took the world back to before tick 70
+    assert_eq!(first.world.tick(), 240 - 60);
+}
+
+#[test]
+fn input_changes_the_outcome() {
+    let replay = recorded_replay();
+    let mut changed = replay.clone();
+    changed.events[0].tick = 11;
+    assert_ne!(
+        play(&replay).world.checksum(),
+        play(&changed).world.checksum()
+    );
+}
+
+#[test]
+fn replays_survive_saving_and_loading() {
+    let mut replay = recorded_replay();
+    let checksum = play(&replay).world.checksum();
+    replay.finish(240, checksum);
+
+    let path = std::env::temp_dir().join(format!("time_game_replay_{}.ron", std::process::id()));
+    replay.save(&path).unwrap();
+    let loaded = Replay::load(&path);
+    fs::remove_file(&path).unwrap();
+
+    let loaded = loaded.unwrap();
+    assert_eq!(loaded, replay);
+    assert_eq!(play(&loaded).world.checksum(), checksum);
+}
+
+#[test]
+fn other_versions_are_rejected() {
+    let mut replay = recorded_replay();
+    replay.version = REPLAY_VERSION + 1;
+
+    let path = std::env::temp_dir().join(format!(
+        "time_game_replay_version_{}.ron",
+        std::process::id()
+    ));
+    replay.save(&path).unwrap();
+    let loaded = Replay::load(&path);
+    fs::remove_file(&path).unwrap();
+
+    let error = format!("{:#}", loaded.unwrap_err());
+    assert!(error.contains("version"), "{}", error);
+}
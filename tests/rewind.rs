@@ -0,0 +1,117 @@
+use cgmath::Vector2;
+use time_game::{
+    components::{Transform2D, Velocity},
+    ecs::entity::Entity,
+    rewind::{Rewind, RewindBuffer},
+    world::{Bundle, World},
+};
+
+const DT: f32 = 1.0 / 60.0;
+
+fn moving_world() -> (World, Entity) {
+    let mut world = World::new();
+    let entity = world.spawn(Bundle {
+        transform: Some(Transform2D::from_position(Vector2::new(0.0, 0.0))),
+        velocity: Some(Velocity {
+            linear: Vector2::new(60.0, 0.0),
+            angular: 1.0,
+        }),
+        ..Bundle::default()
+    });
+    (world, entity)
+}
+
+#[test]
+fn buffer_forgets_oldest_when_full() {
+    let mut buffer = RewindBuffer::new(3);
+    for i in 0..5 {
+        buffer.record(i);
+    }
+    assert_eq!(buffer.len(), 3);
+    assert_eq!(buffer.rewind(), Some(4));
+    assert_eq!(buffer.rewind(), Some(3));
+    assert_eq!(buffer.rewind(), Some(2));
+    assert_eq!(buffer.rewind(), None);
+}
+
+#[test]
+fn rewinding_returns_to_earlier_states() {
+    let (mut world, _) = moving_world();
+    let mut rewind = Rewind::new(1.0, 60);
+
+    for _ in 0..10 {
+        rewind.update(&mut world, DT);
+    }
+    let halfway = world.snapshot();
+    for _ in 0..10 {
+        rewind.update(&mut world, DT);
+    }
+    assert_eq!(world.tick(), 20);
+
+    rewind.set_rewinding(true);
+    for _ in 0..10 {
+        rewind.update(&mut world, DT);
+    }
+    assert_eq!(world.snapshot(), halfway);
+}
+
+#[test]
+fn rewinding_stops_at_oldest_snapshot() {
+    let (mut world, _) = moving_world();
+    let mut rewind = Rewind::new(0.5, 60);
+
+    for _ in 0..60 {
+        rewind.update(&mut world, DT);
+    }
+    assert_eq!(rewind.history_len(), 30);
+
+    rewind.set_rewinding(true);
+    for _ in 0..100 {
+        rewind.update(&mut world, DT);
+    }
+    assert_eq!(world.tick(), 30);
+}
+
+#[test]
+fn resuming_discards_rewound_future() {
+    let (mut world, entity) = moving_world();
+    let mut rewind = Rewind::new(1.0, 60);
+
+    for _ in 0..20 {
+        rewind.update(&mut world, DT);
+    }
+    rewind.set_rewinding(true);
+    for _ in 0..5 {
+        rewind.update(&mut world, DT);
+    }
+    rewind.set_rewinding(false);
+    assert_eq!(rewind.history_len(), 15);
+
+    // Change course so the new future differs from the rewound one
+    world.velocities.get_mut(entity).unwrap().linear = Vector2::new(0.0, 60.0);
+    for _ in 0..5 {
+        rewind.update(&mut world, DT);
+    }
+    assert_eq!(world.tick(), 20);
+    assert_eq!(rewind.history_len(), 20);
+    let position = world.transforms.get(entity).unwrap().position;
+    assert!((position.x - 15.0).abs() < 1e-3);
+    assert!((position.y - 5.0).abs() < 1e-3);
+}
+
+#[test]
+fn rewinding_undoes_spawns_and_despawns() {
+    let (mut world, entity) = moving_world();
+    let mut rewind = Rewind::new(1.0, 60);
+
+    rewind.update(&mut world, DT);
+    world.despawn(entity);
+    let spawned = world.spawn(Bundle::default());
+    rewind.update(&mut world, DT);
+
+    rewind.set_rewinding(true);
+    rewind.update(&mut world, DT);
+    rewind.update(&mut world, DT);
+    assert!(world.is_alive(entity));
+    assert!(!world.is_alive(spawned));
+}
@@ -0,0 +1,262 @@
+use std::{fs, path::PathBuf};
+
+use time_game::{
+    determinism::Checksum,
+    game::Game,
+    input::event::InputEvent,
+    progress::Progress,
+    save::{self, Migration, SAVE_SCHEMA, SLOT_COUNT, SaveData, SaveSlots},
+};
+use winit::keyboard::KeyCode;
+
+/// A fresh, empty directory for one test's saves
+fn save_dir(test: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("time_game-{}-{}", test, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    dir
+}
+
+fn sample() -> SaveData {
+    let mut progress = Progress::default();
+    progress.add_item("crystal", 3);
+    progress.set_flag("met_echo");
+    progress.rewind_charges = 4;
+    progress.play_seconds = 125.5;
+    SaveData {
+        scene: None,
+        player_position: (12.5, -3.0),
+        progress,
+        saved_at: 1_700_000_000,
+    }
+}
+
+#[test]
+fn slots_round_trip() {
+    let dir = save_dir("round-trip");
+    let slots = SaveSlots::new(&dir);
+    assert_eq!(slots.load(2).unwrap(), None);
+
+    slots.save(2, &sample()).unwrap();
+    assert_eq!(slots.load(2).unwrap(), Some(sample()));
+    assert_eq!(slots.list(), vec![(2, sample())]);
+
+    slots.delete(2).unwrap();
+    slots.delete(2).unwrap();
+    assert_eq!(slots.load(2).unwrap(), None);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn saving_replaces_the_old_save_without_leftovers() {
+    let dir = save_dir("replace");
+    let slots = SaveSlots::new(&dir);
+    slots.save(1, &sample()).unwrap();
+    let newer = SaveData {
+        player_position: (0.0, 0.0),
+        ..sample()
+    };
+    slots.save(1, &newer).unwrap();
+
+    assert_eq!(slots.load(1).unwrap(), Some(newer));
+    let files: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+    assert_eq!(files.len(), 1);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn corruption_is_detected() {
+    let dir = save_dir("corruption");
+    let slots = SaveSlots::new(&dir);
+    slots.save(3, &sample()).unwrap();
+
+    let path = slots.path(3);
+    let text = fs::read_to_string(&path).unwrap();
+    fs::write(&path, text.replace("crystal", "crystat")).unwrap();
+    let error = format!("{:#}", slots.load(3).unwrap_err());
+    assert!(error.contains("corrupted"), "{}", error);
+    assert!(slots.list().is_empty());
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn slots_with_a_wrong_checksum_are_rejected() {
+    let dir = save_dir("checksum");
+    let slots = SaveSlots::new(&dir);
+    slots.save(4, &sample()).unwrap();
+
+    let path = slots.path(4);
+    let text = fs::read_to_string(&path).unwrap();
+    let (header, body) = text.split_once('\n').unwrap();
+    let (start, checksum) = header.rsplit_once(' ').unwrap();
+    let flipped = if checksum.starts_with('0') { "1" } else { "0" };
+    let header = format!("{} {}{}", start, flipped, &checksum[1..]);
+    fs::write(&path, format!("{}\n{}", header, body)).unwrap();
+
+    let error = format!("{:#}", slots.load(4).unwrap_err());
+    assert!(error.contains("corrupted"), "{}", error);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+/// A schema before the current one kept the position flat and the items
+/// and flags at the top level
+fn upgrade_schema_0(value: &mut ron::Value) -> anyhow::Result<()> {
+    let ron::Value::Map(map) = value else {
+        anyhow::bail!("Expected a map");
+    };
+    let mut take = |field: &str| {
+        map.remove(&ron::Value::String(field.into()))
+            .ok_or_else(|| anyhow::anyhow!("No {}", field))
+    };
+    let x = take("x")?;
+    let y = take("y")?;
+    let inventory = take("inventory")?;
+    let flags = take("flags")?;
+    let progress = ron::from_str(&format!(
+        "(inventory: {}, flags: {}, rewind_charges: 10, play_seconds: 0.0)",
+        ron::to_string(&inventory)?,
+        ron::to_string(&flags)?
+    ))?;
+    let player_position = ron::Value::Seq(vec![x, y]);
+    for (field, value) in [("player_position", player_position), ("progress", progress)] {
+        map.insert(ron::Value::String(field.into()), value);
+    }
+    Ok(())
+}
+
+static SCHEMA_0_MIGRATIONS: &[Migration] = &[Migration {
+    from: 0,
+    migrate: upgrade_schema_0,
+}];
+
+#[test]
+fn old_slots_are_migrated_on_load() {
+    assert_eq!(
+        SAVE_SCHEMA, 1,
+        "the schema 0 migration below only reaches schema 1"
+    );
+    let dir = save_dir("migrate");
+    fs::create_dir_all(&dir).unwrap();
+    let body = r#"(
+    scene: None,
+    x: 4.5,
+    y: -2.0,
+    inventory: {"crystal": 2},
+    flags: ["met_echo"],
+    saved_at: 1600000000,
+)"#;
+    let mut checksum = Checksum::new();
+    checksum.write_bytes(body.as_bytes());
+    let slots = SaveSlots::new(&dir).with_migrations(SCHEMA_0_MIGRATIONS);
+    let text = format!("time_game save 0 {:016x}\n{}", checksum.finish(), body);
+    fs::write(slots.path(1), text).unwrap();
+
+    let save = slots.load(1).unwrap().unwrap();
+    assert_eq!(save.player_position, (4.5, -2.0));
+    assert_eq!(save.progress.item_count("crystal"), 2);
+    assert!(save.progress.has_flag("met_echo"));
+    assert_eq!(save.progress.rewind_charges, 10);
+    assert_eq!(save.saved_at, 1_600_000_000);
+
+    // Without the migration the old slot can't be read
+    let error = format!("{:#}", SaveSlots::new(&dir).load(1).unwrap_err());
+    assert!(error.contains("No migration"), "{}", error);
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn slots_are_bounded() {
+    let slots = SaveSlots::new(save_dir("bounds"));
+    assert!(slots.save(0, &sample()).is_err());
+    assert!(slots.load(SLOT_COUNT + 1).is_err());
+}
+
+#[test]
+fn newer_schemas_are_rejected() {
+    let text = save::encode(&sample()).unwrap();
+    let newer = text.replacen(
+        &format!("save {} ", SAVE_SCHEMA),
+        &format!("save {} ", SAVE_SCHEMA + 1),
+        1,
+    );
+    let error = save::decode(&newer).unwrap_err().to_string();
+    assert!(error.contains("schema"), "{}", error);
+}
+
+#[test]
+fn migrations_run_in_order() {
+    fn rename_position(value: &mut ron::Value) -> anyhow::Result<()> {
+        let ron::Value::Map(map) = value else {
+            anyhow::bail!("Expected a map");
+        };
+        let position = map
+            .remove(&ron::Value::String("position".into()))
+            .ok_or_else(|| anyhow::anyhow!("No position"))?;
+        map.insert(ron::Value::String("player_position".into()), position);
+        Ok(())
+    }
+    fn add_charges(value: &mut ron::Value) -> anyhow::Result<()> {
+        if let ron::Value::Map(map) = value {
+            map.insert(
+                ron::Value::String("rewind_charges".into()),
+                ron::Value::Number(7u8.into()),
+            );
+        }
+        Ok(())
+    }
+    let migrations = [
+        Migration {
+            from: 2,
+            migrate: add_charges,
+        },
+        Migration {
+            from: 1,
+            migrate: rename_position,
+        },
+    ];
+
+    let mut value: ron::Value = ron::from_str("(position: (1.0, 2.0))").unwrap();
+    save::migrate(&mut value, 1, 3, &migrations).unwrap();
+    let expected: ron::Value =
+        ron::from_str("(player_position: (1.0, 2.0), rewind_charges: 7)").unwrap();
+    assert_eq!(value, expected);
+
+    assert!(save::migrate(&mut value, 3, 4, &migrations).is_err());
+}
+
+#[test]
+fn games_resume_from_saves() {
+    let mut game = Game::new(5);
+    game.handle_event(&InputEvent::Key {
+        code: KeyCode::ArrowRight,
+        pressed: true,
+    });
+    for _ in 0..30 {
+        game.update(1.0 / 60.0);
+    }
+    game.progress.add_item("key", 1);
+    let save = game.save_data();
+
+    let resumed = Game::from_save(5, &save).unwrap();
+    assert_eq!(resumed.save_data().player_position, save.player_position);
+    assert_eq!(resumed.progress, game.progress);
+}
+
+#[test]
+fn rewinding_does_not_spend_charges() {
+    let mut game = Game::new(5);
+    game.progress.rewind_charges = 0;
+    let key = |pressed| InputEvent::Key {
+        code: KeyCode::KeyR,
+        pressed,
+    };
+
+    for _ in 0..3 {
+        game.handle_event(&key(true));
+        game.update(1.0 / 60.0);
+        assert!(game.rewind.is_rewinding());
+        game.handle_event(&key(false));
+        game.update(1.0 / 60.0);
+        assert!(!game.rewind.is_rewinding());
+    }
+    assert_eq!(game.progress.rewind_charges, 0);
+}
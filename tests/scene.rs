@@ -0,0 +1,124 @@
+use time_game::{
+    game::Game,
+    input::event::InputEvent,
+    prefab::PrefabLibrary,
+    scene::{DEFAULT_SCENE, SCENE_VERSION, Scene},
+    world::World,
+};
+use winit::keyboard::KeyCode;
+
+fn error(text: &str) -> String {
+    format!("{:#}", Scene::parse(text, "test.ron").unwrap_err())
+}
+
+#[test]
+fn default_scene_resolves_references() {
+    let scene = Scene::parse(DEFAULT_SCENE, "sandbox.ron").unwrap();
+    let mut world = World::new();
+    let entities = scene
+        .instantiate(&mut world, &PrefabLibrary::built_in())
+        .unwrap();
+    // The time crystal prefab adds its time field
+    assert_eq!(entities.len(), scene.entities.len() + 1);
+
+    let parents: Vec<_> = world
+        .transforms
+        .iter()
+        .filter_map(|(_, transform)| transform.parent)
+        .collect();
+    assert_eq!(parents.len(), 3);
+    assert!(parents.iter().all(|&parent| world.is_alive(parent)));
+
+    let door = world.doors.iter().next().unwrap().1;
+    assert_eq!(door.switches.len(), 2);
+    assert!(door.switches.iter().all(|&s| world.switches.contains(s)));
+}
+
+#[test]
+fn saved_world_loads_back_identically() {
+    let mut game = Game::new(3);
+    game.handle_event(&InputEvent::Key {
+        code: KeyCode::ArrowRight,
+        pressed: true,
+    });
+    for _ in 0..90 {
+        game.update(1.0 / 60.0);
+    }
+    game.spawn_echo();
+
+    let scene = Scene::from_world(&game.world, game.textures());
+    let text = scene.to_ron().unwrap();
+    let loaded = Scene::parse(&text, "saved.ron").unwrap();
+    assert_eq!(loaded, scene);
+
+    // Echoes aren't saved
+    let mut world = World::new();
+    loaded
+        .instantiate(&mut world, &PrefabLibrary::new())
+        .unwrap();
+    assert_eq!(world.entities().count(), game.world.entities().count() - 1);
+    assert_eq!(Scene::from_world(&world, game.textures()), scene);
+}
+
+#[test]
+fn syntax_errors_point_at_the_line() {
+    let text = "(\n    version: 1,\n    entities: [\n        (transform: (position: (1.0, oops))),\n    ],\n)";
+    assert!(error(text).starts_with("test.ron:4:"), "{}", error(text));
+}
+
+#[test]
+fn unknown_fields_point_at_the_line() {
+    let text = "(\n    version: 1,\n    entities: [\n        (colour: 3),\n    ],\n)";
+    assert!(error(text).starts_with("test.ron:4:"), "{}", error(text));
+}
+
+#[test]
+fn dangling_references_point_at_the_line() {
+    let text = r#"#![enable(implicit_some)]
+(
+    version: 1,
+    entities: [
+        (name: "body", transform: (position: (0.0, 0.0))),
+        (transform: (position: (1.0, 0.0), parent: "bdoy")),
+    ],
+)"#;
+    let message = error(text);
+    assert!(message.starts_with("test.ron:6:"), "{}", message);
+    assert!(message.contains("bdoy"), "{}", message);
+}
+
+#[test]
+fn duplicate_names_point_at_the_second_one() {
+    let text = r#"#![enable(implicit_some)]
+(
+    version: 1,
+    entities: [
+        (name: "switch", switch: true),
+        (name: "door", door: (switches: ["switch"])),
+        (name: "switch", switch: true),
+    ],
+)"#;
+    assert!(error(text).starts_with("test.ron:7:"), "{}", error(text));
+}
+
+#[test]
+fn doors_only_open_with_switches() {
+    let text = r#"(
+    version: 1,
+    entities: [
+        (name: "lever"),
+        (door: (switches: ["lever"])),
+    ],
+)"#;
+    let message = error(text);
+    assert!(message.starts_with("test.ron:5:"), "{}", message);
+    assert!(message.contains("not a switch"), "{}", message);
+}
+
+#[test]
+fn other_versions_are_rejected() {
+    let text = format!("(version: {}, entities: [])", SCENE_VERSION + 1);
+    assert!(error(&text).contains("version"));
+    let text = format!("(version: {}, entities: [])", SCENE_VERSION - 1);
+    assert!(error(&text).contains("version"));
+}
@@ -0,0 +1,107 @@
+use std::path::PathBuf;
+
+use cgmath::{Matrix3, Vector2};
+use image::{Rgba, RgbaImage};
+use time_game::graphics::{
+    GraphicsState,
+    blend::BlendMode,
+    color::Color,
+    textured_pipeline::{TexturedQuad, UvRect},
+};
+
+fn offscreen() -> GraphicsState {
+    pollster::block_on(GraphicsState::offscreen(16, 16)).unwrap()
+}
+
+/// A solid PNG written for one test, since the checked in images may only
+/// be LFS pointers
+fn png(test: &str, width: u32, height: u32) -> PathBuf {
+    let path = std::env::temp_dir().join(format!(
+        "time_game-texture-{}-{}.png",
+        test,
+        std::process::id()
+    ));
+    RgbaImage::from_pixel(width, height, Rgba([255, 0, 255, 255]))
+        .save(&path)
+        .unwrap();
+    path
+}
+
+#[test]
+fn loading_a_file_twice_shares_one_texture() {
+    let mut graphics_state = offscreen();
+    let path = png("twice", 4, 2);
+    let first = graphics_state.load_texture(&path).unwrap();
+    let second = graphics_state.load_texture(&path).unwrap();
+    assert_eq!(first, second);
+    assert_eq!(graphics_state.texture_size(first), Some((4, 2)));
+
+    // Once released, loading the file again makes a new texture
+    assert!(graphics_state.release_texture(first));
+    let third = graphics_state.load_texture(&path).unwrap();
+    assert_ne!(third, first);
+    assert_eq!(graphics_state.texture_size(third), Some((4, 2)));
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn unknown_handles_are_not_found() {
+    let paths = [png("unknown-a", 1, 1), png("unknown-b", 1, 1)];
+    let mut other = offscreen();
+    let foreign: Vec<_> = paths
+        .iter()
+        .map(|path| other.load_texture(path).unwrap())
+        .collect();
+
+    // A handle from another registry, for a slot this one doesn't have
+    let mut graphics_state = offscreen();
+    graphics_state.load_texture(&paths[0]).unwrap();
+    assert_eq!(graphics_state.texture_size(foreign[1]), None);
+    assert!(!graphics_state.release_texture(foreign[1]));
+
+    // Quads with unknown textures are skipped rather than drawn
+    graphics_state.push_textured_quad(TexturedQuad {
+        transform: Matrix3::from_translation(Vector2::new(8.0, 8.0)),
+        dimensions: Vector2::new(16.0, 16.0),
+        layer: 0,
+        texture: foreign[1],
+        uv: UvRect::FULL,
+        tint: Color::WHITE,
+        blend: BlendMode::Opaque,
+    });
+    graphics_state.render().unwrap();
+    let image = graphics_state.read_pixels().unwrap();
+    assert_ne!(*image.get_pixel(8, 8), Rgba([255, 0, 255, 255]));
+
+    for path in paths {
+        std::fs::remove_file(path).unwrap();
+    }
+}
+
+#[test]
+fn releasing_a_texture_frees_its_slot() {
+    let mut graphics_state = offscreen();
+    let paths = [
+        png("release-big", 8, 8),
+        png("release-kept", 2, 2),
+        png("release-small", 1, 1),
+    ];
+    let big = graphics_state.load_texture(&paths[0]).unwrap();
+    let kept = graphics_state.load_texture(&paths[1]).unwrap();
+
+    assert!(graphics_state.release_texture(big));
+    assert!(!graphics_state.release_texture(big));
+    assert_eq!(graphics_state.texture_size(big), None);
+
+    let small = graphics_state.load_texture(&paths[2]).unwrap();
+    assert_eq!(small.index(), big.index());
+    assert_ne!(small, big);
+    // The old handle doesn't alias the texture now in its slot
+    assert_eq!(graphics_state.texture_size(big), None);
+    assert_eq!(graphics_state.texture_size(small), Some((1, 1)));
+    assert_eq!(graphics_state.texture_size(kept), Some((2, 2)));
+
+    for path in paths {
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,83 @@
+use cgmath::Vector2;
+use time_game::{
+    components::{TimeScale, Transform2D, Velocity},
+    time_field::TimeField,
+    world::{Bundle, World},
+};
+
+fn spinning_at(x: f32, y: f32) -> Bundle {
+    Bundle {
+        transform: Some(Transform2D::from_position(Vector2::new(x, y))),
+        velocity: Some(Velocity {
+            angular: 1.0,
+            ..Velocity::ZERO
+        }),
+        ..Bundle::default()
+    }
+}
+
+fn field_at(x: f32, y: f32, field: TimeField) -> Bundle {
+    Bundle {
+        transform: Some(Transform2D::from_position(Vector2::new(x, y))),
+        time_field: Some(field),
+        ..Bundle::default()
+    }
+}
+
+#[test]
+fn overlapping_fields_multiply_with_local_scale() {
+    let mut world = World::new();
+    let entity = world.spawn(Bundle {
+        time_scale: Some(TimeScale(2.0)),
+        ..spinning_at(0.0, 0.0)
+    });
+    world.spawn(field_at(
+        0.0,
+        0.0,
+        TimeField::new(Vector2::new(10.0, 10.0), 0.5),
+    ));
+    world.spawn(field_at(
+        5.0,
+        0.0,
+        TimeField::new(Vector2::new(10.0, 10.0), 0.25),
+    ));
+    world.spawn(field_at(
+        100.0,
+        0.0,
+        TimeField::new(Vector2::new(10.0, 10.0), 0.0),
+    ));
+
+    assert_eq!(world.effective_time_scale(entity), 0.25);
+
+    world.update(1.0);
+    assert_eq!(world.transforms.get(entity).unwrap().rotation, 0.25);
+}
+
+#[test]
+fn anchored_field_follows_its_entity_and_skips_it() {
+    let mut world = World::new();
+    let player = world.spawn(Bundle {
+        velocity: Some(Velocity {
+            linear: Vector2::new(10.0, 0.0),
+            angular: 1.0,
+        }),
+        ..spinning_at(0.0, 0.0)
+    });
+    let other = world.spawn(spinning_at(20.0, 0.0));
+    let field = world.spawn(field_at(
+        0.0,
+        0.0,
+        TimeField::anchored(player, Vector2::new(5.0, 5.0), 0.0),
+    ));
+
+    world.update(1.0);
+    assert_eq!(world.effective_time_scale(player), 1.0);
+    assert_eq!(world.transforms.get(other).unwrap().rotation, 1.0);
+
+    // The field is recentered at the end of each tick, so the player
+    // reaching x = 20 freezes the other entity from the following tick on
+    world.update(1.0);
+    world.update(1.0);
+    assert_eq!(world.world_position(field), Some(Vector2::new(30.0, 0.0)));
+    assert_eq!(world.transforms.get(other).unwrap().rotation, 2.0);
+}